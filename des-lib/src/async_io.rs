@@ -0,0 +1,169 @@
+//! Tokio counterparts of the [`crate::stream`] adapters.
+
+use crate::{
+    BlockCipher,
+    mode::Mode,
+    padding::{Padding, PaddingError},
+    stream::{Decryptor, Encryptor, READ_CHUNK, invalid_data},
+};
+use std::{
+    io,
+    pin::Pin,
+    task::{Context, Poll, ready},
+};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+/// Encrypts everything written to it and forwards the ciphertext to `W`.
+///
+/// Async version of [`crate::stream::EncryptWriter`]. The final padded block
+/// is written on shutdown, so call
+/// [`AsyncWriteExt::shutdown`](tokio::io::AsyncWriteExt::shutdown) once all
+/// data has been written.
+#[derive(Debug)]
+pub struct AsyncEncryptWriter<C, W> {
+    inner: W,
+    encryptor: Encryptor<C>,
+    buffer: Vec<u8>,
+    written: usize,
+    finished: bool,
+}
+
+impl<C: BlockCipher, W: AsyncWrite + Unpin> AsyncEncryptWriter<C, W> {
+    pub const fn new(inner: W, cipher: C, mode: Mode, padding: Padding) -> Self {
+        Self {
+            inner,
+            encryptor: Encryptor::new(cipher, mode, padding),
+            buffer: Vec::new(),
+            written: 0,
+            finished: false,
+        }
+    }
+
+    /// Return the inner writer.
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+
+    /// Write out the buffered ciphertext.
+    fn poll_drain(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        while self.written < self.buffer.len() {
+            let count =
+                ready!(Pin::new(&mut self.inner).poll_write(cx, &self.buffer[self.written..]))?;
+            if count == 0 {
+                return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+            }
+            self.written += count;
+        }
+
+        self.buffer.clear();
+        self.written = 0;
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<C: BlockCipher + Unpin, W: AsyncWrite + Unpin> AsyncWrite for AsyncEncryptWriter<C, W> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        if this.finished {
+            return Poll::Ready(Err(io::Error::other(
+                "write after the stream was shut down",
+            )));
+        }
+
+        ready!(this.poll_drain(cx))?;
+        this.buffer = this.encryptor.update(buf);
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_drain(cx))?;
+        Pin::new(&mut this.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        if !this.finished {
+            ready!(this.poll_drain(cx))?;
+            this.finished = true;
+            this.buffer = this.encryptor.finalize()?;
+        }
+
+        ready!(this.poll_drain(cx))?;
+        ready!(Pin::new(&mut this.inner).poll_flush(cx))?;
+        Pin::new(&mut this.inner).poll_shutdown(cx)
+    }
+}
+
+/// Decrypts the ciphertext read from `R`.
+///
+/// Async version of [`crate::stream::DecryptReader`].
+#[derive(Debug)]
+pub struct AsyncDecryptReader<C, R> {
+    inner: R,
+    decryptor: Decryptor<C>,
+    output: Vec<u8>,
+    position: usize,
+    eof: bool,
+    failed: Option<PaddingError>,
+}
+
+impl<C: BlockCipher, R: AsyncRead + Unpin> AsyncDecryptReader<C, R> {
+    pub const fn new(inner: R, cipher: C, mode: Mode, padding: Padding) -> Self {
+        Self {
+            inner,
+            decryptor: Decryptor::new(cipher, mode, padding),
+            output: Vec::new(),
+            position: 0,
+            eof: false,
+            failed: None,
+        }
+    }
+
+    /// Return the inner reader.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<C: BlockCipher + Unpin, R: AsyncRead + Unpin> AsyncRead for AsyncDecryptReader<C, R> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+
+        if let Some(error) = this.failed {
+            return Poll::Ready(Err(invalid_data(error)));
+        }
+        while this.position == this.output.len() && !this.eof {
+            let mut chunk = [0; READ_CHUNK];
+            let mut chunk = ReadBuf::new(&mut chunk);
+            ready!(Pin::new(&mut this.inner).poll_read(cx, &mut chunk))?;
+
+            this.output = if chunk.filled().is_empty() {
+                let tail = this
+                    .decryptor
+                    .finalize()
+                    .inspect_err(|&e| this.failed = Some(e))
+                    .map_err(invalid_data)?;
+                this.eof = true;
+                tail
+            } else {
+                this.decryptor.update(chunk.filled())
+            };
+            this.position = 0;
+        }
+
+        let available = &this.output[this.position..];
+        let count = available.len().min(buf.remaining());
+        buf.put_slice(&available[..count]);
+        this.position += count;
+        Poll::Ready(Ok(()))
+    }
+}
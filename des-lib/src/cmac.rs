@@ -0,0 +1,147 @@
+//! CMAC (NIST SP 800-38B) over 64-bit block ciphers.
+//!
+//! [`cmac`] computes the tag of a message held in memory; [`Cmac`] computes
+//! it incrementally for messages that arrive in pieces, and verifies tags
+//! truncated to their leftmost bytes, down to [`MIN_MAC_LENGTH`].
+
+use crate::{
+    BLOCK_SIZE, BlockCipher,
+    mac::{MIN_MAC_LENGTH, MacError},
+};
+
+/// Constant for subkey generation with a 64-bit block size.
+const RB: u64 = 0x1B;
+
+/// Compute the full 64-bit CMAC tag of `message`.
+#[must_use]
+pub fn cmac<C: BlockCipher>(cipher: &C, message: &[u8]) -> u64 {
+    let mut mac = Cmac::new(cipher);
+    mac.update(message);
+    mac.finalize()
+}
+
+/// Incremental CMAC computation.
+///
+/// The last complete block is held back until more data arrives or the tag
+/// is finalized, since it is masked with a different subkey than a padded
+/// partial block.
+#[derive(Clone)]
+pub struct Cmac<C: BlockCipher> {
+    cipher: C,
+    k1: u64,
+    k2: u64,
+    state: u64,
+    buffer: [u8; BLOCK_SIZE],
+    buffered: usize,
+}
+
+impl<C: BlockCipher> Cmac<C> {
+    /// Start a CMAC computation, deriving the subkeys from `cipher`. Pass a
+    /// reference to keep using the cipher elsewhere.
+    #[must_use]
+    pub fn new(cipher: C) -> Self {
+        let k1 = double(cipher.encrypt_block(0));
+        Self {
+            cipher,
+            k1,
+            k2: double(k1),
+            state: 0,
+            buffer: [0; BLOCK_SIZE],
+            buffered: 0,
+        }
+    }
+
+    /// Feed the next part of the message.
+    pub fn update(&mut self, mut data: &[u8]) {
+        while !data.is_empty() {
+            if self.buffered == BLOCK_SIZE {
+                self.state = self
+                    .cipher
+                    .encrypt_block(self.state ^ u64::from_be_bytes(self.buffer));
+                self.buffered = 0;
+            }
+            let take = data.len().min(BLOCK_SIZE - self.buffered);
+            self.buffer[self.buffered..self.buffered + take].copy_from_slice(&data[..take]);
+            self.buffered += take;
+            data = &data[take..];
+        }
+    }
+
+    /// Compute the full 64-bit tag.
+    #[must_use]
+    pub fn finalize(self) -> u64 {
+        // The last block is complete only if the message is non-empty and aligned.
+        let last = if self.buffered == BLOCK_SIZE {
+            u64::from_be_bytes(self.buffer) ^ self.k1
+        } else {
+            pad(&self.buffer[..self.buffered]) ^ self.k2
+        };
+        self.cipher.encrypt_block(self.state ^ last)
+    }
+
+    /// Compute the tag truncated to its leftmost `length` bytes.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`MacError::InvalidLength`] for tags shorter than
+    /// [`MIN_MAC_LENGTH`] or longer than a block.
+    pub fn finalize_truncated(self, length: usize) -> Result<Vec<u8>, MacError> {
+        check_length(length)?;
+        Ok(self.finalize().to_be_bytes()[..length].to_vec())
+    }
+
+    /// Check a tag against the leftmost bytes of the computed tag, in
+    /// constant time.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`MacError::InvalidLength`] for tags shorter than
+    /// [`MIN_MAC_LENGTH`] or longer than a block, and [`MacError::Mismatch`]
+    /// if the tag is wrong.
+    pub fn verify(self, tag: &[u8]) -> Result<(), MacError> {
+        check_length(tag.len())?;
+        if constant_time_eq(&self.finalize().to_be_bytes()[..tag.len()], tag) {
+            Ok(())
+        } else {
+            Err(MacError::Mismatch)
+        }
+    }
+}
+
+impl<C: BlockCipher> std::fmt::Debug for Cmac<C> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Cmac")
+            .field("buffered", &self.buffered)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Compare equal-length byte strings without an early exit.
+pub(crate) fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    let difference = a
+        .iter()
+        .zip(b)
+        .fold(0, |difference, (x, y)| difference | (x ^ y));
+    a.len() == b.len() && std::hint::black_box(difference) == 0
+}
+
+const fn check_length(length: usize) -> Result<(), MacError> {
+    if length < MIN_MAC_LENGTH || length > BLOCK_SIZE {
+        return Err(MacError::InvalidLength(length));
+    }
+    Ok(())
+}
+
+/// Multiply by `x` in GF(2^64).
+const fn double(value: u64) -> u64 {
+    let carry = value >> 63;
+    (value << 1) ^ (carry * RB)
+}
+
+/// Pad a partial block with `0x80` followed by zeros.
+fn pad(tail: &[u8]) -> u64 {
+    let mut block = [0; BLOCK_SIZE];
+    block[..tail.len()].copy_from_slice(tail);
+    block[tail.len()] = 0x80;
+    u64::from_be_bytes(block)
+}
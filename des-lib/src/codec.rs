@@ -0,0 +1,119 @@
+//! Length-prefixed CBC frames for [`tokio_util::codec`].
+//!
+//! Each frame on the wire is a 4-byte big-endian length followed by that many
+//! bytes: a fresh random 8-byte IV and the PKCS#7-padded CBC ciphertext of
+//! the payload.
+
+use crate::{
+    BLOCK_SIZE, BlockCipher, blocks_to_bytes, bytes_to_blocks,
+    mode::{cbc_decrypt, cbc_encrypt},
+    padding::Padding,
+    stream::invalid_data,
+};
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use std::io;
+use tokio_util::codec::{Decoder, Encoder};
+
+/// Size of the big-endian length prefix.
+const LENGTH_PREFIX: usize = 4;
+
+/// Encodes and decodes DES-CBC encrypted, length-prefixed frames.
+#[derive(Debug, Clone)]
+pub struct CbcFrameCodec<C> {
+    cipher: C,
+    max_frame_length: usize,
+}
+
+impl<C: BlockCipher> CbcFrameCodec<C> {
+    /// Default limit on the encrypted frame length (8 MiB).
+    pub const DEFAULT_MAX_FRAME_LENGTH: usize = 8 * 1024 * 1024;
+
+    pub const fn new(cipher: C) -> Self {
+        Self {
+            cipher,
+            max_frame_length: Self::DEFAULT_MAX_FRAME_LENGTH,
+        }
+    }
+
+    /// Set the largest accepted encrypted frame length (IV included).
+    #[must_use]
+    pub const fn max_frame_length(mut self, length: usize) -> Self {
+        self.max_frame_length = length;
+        self
+    }
+}
+
+impl<C: BlockCipher> Encoder<Bytes> for CbcFrameCodec<C> {
+    type Error = io::Error;
+
+    fn encode(&mut self, item: Bytes, dst: &mut BytesMut) -> io::Result<()> {
+        let mut data = item.to_vec();
+        Padding::Pkcs7.pad(&mut data);
+
+        let length = BLOCK_SIZE + data.len();
+        if length > self.max_frame_length {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "frame of {length} bytes exceeds the {} byte limit",
+                    self.max_frame_length
+                ),
+            ));
+        }
+        let prefix = u32::try_from(length)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "frame is too large"))?;
+
+        let iv = rand::random();
+        let mut blocks = bytes_to_blocks(&data);
+        cbc_encrypt(&self.cipher, iv, &mut blocks);
+
+        dst.reserve(LENGTH_PREFIX + length);
+        dst.put_u32(prefix);
+        dst.put_u64(iv);
+        dst.extend_from_slice(&blocks_to_bytes(&blocks));
+        Ok(())
+    }
+}
+
+impl<C: BlockCipher> Decoder for CbcFrameCodec<C> {
+    type Item = BytesMut;
+    type Error = io::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> io::Result<Option<BytesMut>> {
+        let Some(prefix) = src.first_chunk::<LENGTH_PREFIX>() else {
+            return Ok(None);
+        };
+        let length = u32::from_be_bytes(*prefix) as usize;
+
+        if length > self.max_frame_length {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "frame of {length} bytes exceeds the {} byte limit",
+                    self.max_frame_length
+                ),
+            ));
+        }
+        if length < 2 * BLOCK_SIZE || !length.is_multiple_of(BLOCK_SIZE) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("invalid encrypted frame length {length}"),
+            ));
+        }
+
+        if src.len() < LENGTH_PREFIX + length {
+            src.reserve(LENGTH_PREFIX + length - src.len());
+            return Ok(None);
+        }
+
+        src.advance(LENGTH_PREFIX);
+        let mut frame = src.split_to(length);
+        let iv = frame.get_u64();
+        let mut blocks = bytes_to_blocks(&frame);
+        cbc_decrypt(&self.cipher, iv, &mut blocks);
+
+        let plaintext = blocks_to_bytes(&blocks);
+        let plaintext = Padding::Pkcs7.unpad(&plaintext).map_err(invalid_data)?;
+        Ok(Some(BytesMut::from(plaintext)))
+    }
+}
@@ -0,0 +1,309 @@
+//! Derived Unique Key Per Transaction (ANSI X9.24-1, TDEA).
+//!
+//! A terminal is loaded with an initial PIN encryption key (IPEK) derived from
+//! the base derivation key (BDK) and its key serial number (KSN). Every
+//! transaction uses a new key, derived from the IPEK by applying the
+//! non-reversible key generation process once for each set bit of the 21-bit
+//! transaction counter at the end of the KSN. The host derives the same key
+//! from the BDK and the KSN sent with the transaction; the terminal keeps a
+//! [`Terminal`] future key register so that it never stores a key that could
+//! recover earlier ones.
+
+use crate::{
+    BlockCipher, Des,
+    encoding::Encoding,
+    key::{Key, KeyLength},
+    tdes::TripleDes,
+};
+use std::{
+    fmt::{self, Display},
+    str::FromStr,
+};
+use thiserror::Error;
+
+/// Largest transaction counter.
+pub const MAX_COUNTER: u32 = 0x1F_FFFF;
+
+/// Counters with more set bits are skipped by terminals.
+pub const MAX_COUNTER_BITS: u32 = 10;
+
+/// Number of bits in the transaction counter, and of future key registers.
+const COUNTER_BITS: usize = 21;
+
+/// Variant applied to a key for the IPEK and for key generation.
+const KEY_MASK: u64 = 0xC0C0_C0C0_0000_0000;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum DukptError {
+    #[error("DUKPT needs a 16-byte key, got {0} bytes")]
+    KeyLength(usize),
+
+    #[error("KSN must be 10 bytes (20 hex digits)")]
+    InvalidKsn,
+
+    #[error("KSN counter {0:#X} has more than {MAX_COUNTER_BITS} bits set")]
+    InvalidCounter(u32),
+
+    #[error("The terminal has used all of its transaction keys")]
+    Exhausted,
+}
+
+/// An 80-bit key serial number: key set ID, device ID and a 21-bit
+/// transaction counter.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Ksn([u8; 10]);
+
+impl Ksn {
+    #[must_use]
+    pub const fn new(bytes: [u8; 10]) -> Self {
+        Self(bytes)
+    }
+
+    #[must_use]
+    pub const fn to_bytes(self) -> [u8; 10] {
+        self.0
+    }
+
+    /// The transaction counter.
+    #[must_use]
+    pub fn counter(self) -> u32 {
+        u32::try_from(self.register() & u64::from(MAX_COUNTER))
+            .unwrap_or_else(|_| unreachable!("Counter has 21 bits"))
+    }
+
+    /// This KSN with the transaction counter replaced by the low 21 bits of
+    /// `counter`.
+    #[must_use]
+    pub fn with_counter(self, counter: u32) -> Self {
+        let register = self.register() & !u64::from(MAX_COUNTER) | u64::from(counter & MAX_COUNTER);
+        let mut bytes = self.0;
+        bytes[2..].copy_from_slice(&register.to_be_bytes());
+        Self(bytes)
+    }
+
+    /// The rightmost 64 bits.
+    const fn register(self) -> u64 {
+        let [_, _, rest @ ..] = self.0;
+        u64::from_be_bytes(rest)
+    }
+
+    /// The leftmost 64 bits with the counter cleared, from which the IPEK is
+    /// derived.
+    fn initial_data(self) -> u64 {
+        let [rest @ .., _, _] = self.with_counter(0).0;
+        u64::from_be_bytes(rest)
+    }
+}
+
+impl FromStr for Ksn {
+    type Err = DukptError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Encoding::Hex
+            .decode(s)
+            .ok()
+            .and_then(|bytes| bytes.try_into().ok())
+            .map(Self)
+            .ok_or(DukptError::InvalidKsn)
+    }
+}
+
+impl Display for Ksn {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&Encoding::Hex.encode(&self.0))
+    }
+}
+
+impl fmt::Debug for Ksn {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Ksn({self})")
+    }
+}
+
+/// What a transaction key is used for. Each variant XORs a different
+/// constant into both halves of the key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyVariant {
+    /// PIN encryption.
+    PinEncryption,
+    /// MACs on messages from the terminal.
+    MacRequest,
+    /// MACs on messages to the terminal.
+    MacResponse,
+    /// Data encryption from the terminal.
+    DataRequest,
+    /// Data encryption to the terminal.
+    DataResponse,
+}
+
+impl KeyVariant {
+    const fn mask(self) -> u64 {
+        match self {
+            Self::PinEncryption => 0x0000_0000_0000_00FF,
+            Self::MacRequest => 0x0000_0000_0000_FF00,
+            Self::DataRequest => 0x0000_0000_00FF_0000,
+            Self::MacResponse => 0x0000_0000_FF00_0000,
+            Self::DataResponse => 0x0000_00FF_0000_0000,
+        }
+    }
+
+    const fn is_data(self) -> bool {
+        matches!(self, Self::DataRequest | Self::DataResponse)
+    }
+}
+
+/// Derive the IPEK for the device identified by `ksn`.
+///
+/// # Errors
+///
+/// Returns [`DukptError::KeyLength`] unless `bdk` is a 2TDEA key.
+pub fn ipek(bdk: &Key, ksn: Ksn) -> Result<Key, DukptError> {
+    let (left, right) = halves(bdk)?;
+    let data = ksn.initial_data();
+    let encrypt = |left, right| TripleDes::with_two_keys(left, right).encrypt_block(data);
+    Ok(Key::double(
+        encrypt(left, right),
+        encrypt(left ^ KEY_MASK, right ^ KEY_MASK),
+    ))
+}
+
+/// Derive the transaction key for `ksn` from the device's IPEK.
+///
+/// # Errors
+///
+/// Returns [`DukptError::KeyLength`] unless `ipek` is a 2TDEA key, and
+/// [`DukptError::InvalidCounter`] for counters that terminals never use.
+pub fn transaction_key(ipek: &Key, ksn: Ksn) -> Result<Key, DukptError> {
+    let counter = ksn.counter();
+    if counter.count_ones() > MAX_COUNTER_BITS {
+        return Err(DukptError::InvalidCounter(counter));
+    }
+    let mut key = halves(ipek)?;
+    let mut register = ksn.with_counter(0).register();
+    for bit in (0..COUNTER_BITS).rev() {
+        let shift = 1 << bit;
+        if u64::from(counter) & shift != 0 {
+            register |= shift;
+            key = generate_key(key, register);
+        }
+    }
+    Ok(Key::double(key.0, key.1))
+}
+
+/// Derive the transaction key for `ksn` from the BDK, as the host does.
+///
+/// # Errors
+///
+/// Returns the errors of [`ipek`] and [`transaction_key`].
+pub fn derive_key(bdk: &Key, ksn: Ksn) -> Result<Key, DukptError> {
+    transaction_key(&ipek(bdk, ksn)?, ksn)
+}
+
+/// The key used for `variant` from a transaction key.
+///
+/// The data encryption variants are additionally encrypted with themselves,
+/// half by half, so that they do not share a structure with the other
+/// variants.
+///
+/// # Errors
+///
+/// Returns [`DukptError::KeyLength`] unless `transaction_key` is a 2TDEA key.
+pub fn variant_key(transaction_key: &Key, variant: KeyVariant) -> Result<Key, DukptError> {
+    let (left, right) = halves(transaction_key)?;
+    let mask = variant.mask();
+    let (left, right) = (left ^ mask, right ^ mask);
+    if variant.is_data() {
+        let cipher = TripleDes::with_two_keys(left, right);
+        Ok(Key::double(
+            cipher.encrypt_block(left),
+            cipher.encrypt_block(right),
+        ))
+    } else {
+        Ok(Key::double(left, right))
+    }
+}
+
+/// The terminal side of DUKPT: a future key register holding one key per
+/// counter bit, each derived ahead of time and erased once used.
+#[derive(Clone)]
+pub struct Terminal {
+    /// KSN with the counter of the next transaction.
+    ksn: Ksn,
+    future_keys: [Option<(u64, u64)>; COUNTER_BITS],
+}
+
+impl Terminal {
+    /// Load the IPEK and the initial KSN; its counter is reset to zero.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`DukptError::KeyLength`] unless `ipek` is a 2TDEA key.
+    pub fn new(ipek: &Key, ksn: Ksn) -> Result<Self, DukptError> {
+        let key = halves(ipek)?;
+        let register = ksn.with_counter(0).register();
+        Ok(Self {
+            ksn: ksn.with_counter(1),
+            future_keys: std::array::from_fn(|bit| Some(generate_key(key, register | 1 << bit))),
+        })
+    }
+
+    /// The KSN of the next transaction.
+    #[must_use]
+    pub const fn ksn(&self) -> Ksn {
+        self.ksn
+    }
+
+    /// Take the key for the next transaction with its KSN, and prepare the
+    /// keys for the following ones.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`DukptError::Exhausted`] once the counter has run out.
+    pub fn next_key(&mut self) -> Result<(Ksn, Key), DukptError> {
+        let ksn = self.ksn;
+        let counter = ksn.counter();
+        if counter == 0 {
+            return Err(DukptError::Exhausted);
+        }
+        let current = counter.trailing_zeros() as usize;
+        let key = self.future_keys[current]
+            .take()
+            .unwrap_or_else(|| unreachable!("Key for the lowest counter bit is always loaded"));
+
+        let register = ksn.register();
+        for bit in 0..current {
+            self.future_keys[bit] = Some(generate_key(key, register | 1 << bit));
+        }
+
+        // The counter wraps to zero once every key has been used.
+        let mut next = counter + 1;
+        while next.count_ones() > MAX_COUNTER_BITS {
+            next += 1 << next.trailing_zeros();
+        }
+        self.ksn = ksn.with_counter(next);
+        Ok((ksn, Key::double(key.0, key.1)))
+    }
+}
+
+impl fmt::Debug for Terminal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Terminal")
+            .field("ksn", &self.ksn)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The non-reversible key generation process: each half of the new key is
+/// `DES(R ^ right, left) ^ right`, once under the key and once under its
+/// `C0C0C0C0` variant.
+fn generate_key((left, right): (u64, u64), register: u64) -> (u64, u64) {
+    let half = |left, right| Des::new(left).encrypt(register ^ right) ^ right;
+    (half(left ^ KEY_MASK, right ^ KEY_MASK), half(left, right))
+}
+
+fn halves(key: &Key) -> Result<(u64, u64), DukptError> {
+    match (key.length(), key.parts()) {
+        (KeyLength::Double, &[left, right]) => Ok((left, right)),
+        (length, _) => Err(DukptError::KeyLength(length.bytes())),
+    }
+}
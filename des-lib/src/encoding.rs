@@ -0,0 +1,78 @@
+//! Text encodings for binary data: hex, and base64 with the `base64` feature.
+//!
+//! Key blocks, keystore files and the command line all print keys, check
+//! values and ciphertext with [`Encoding`], so every part of the crate writes
+//! and accepts the same digits.
+
+#[cfg(feature = "base64")]
+use base64::{Engine, engine::general_purpose::STANDARD};
+use std::fmt::Write;
+use thiserror::Error;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum EncodingError {
+    #[error("Hex string contains invalid characters")]
+    InvalidHex,
+
+    #[error("Hex data must have an even number of digits")]
+    OddLength,
+
+    #[cfg(feature = "base64")]
+    #[error("Base64 decoding failed: {0}")]
+    Base64(#[from] base64::DecodeError),
+}
+
+/// Text encoding of binary data.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Encoding {
+    /// Hexadecimal, written with uppercase digits and read in either case.
+    #[default]
+    Hex,
+    /// Standard base64 with padding.
+    #[cfg(feature = "base64")]
+    Base64,
+}
+
+impl Encoding {
+    /// Encode `bytes`, using uppercase digits for hex.
+    #[must_use]
+    pub fn encode(self, bytes: &[u8]) -> String {
+        match self {
+            Self::Hex => bytes.iter().fold(String::new(), |mut out, byte| {
+                let _ = write!(out, "{byte:02X}");
+                out
+            }),
+            #[cfg(feature = "base64")]
+            Self::Base64 => STANDARD.encode(bytes),
+        }
+    }
+
+    /// Decode `s`. Hex takes bare digits only, without a prefix or
+    /// separators.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error for characters outside the encoding, an odd number of
+    /// hex digits or malformed base64.
+    pub fn decode(self, s: &str) -> Result<Vec<u8>, EncodingError> {
+        match self {
+            Self::Hex => decode_hex(s),
+            #[cfg(feature = "base64")]
+            Self::Base64 => Ok(STANDARD.decode(s)?),
+        }
+    }
+}
+
+fn decode_hex(s: &str) -> Result<Vec<u8>, EncodingError> {
+    let digits = s
+        .chars()
+        .map(|ch| ch.to_digit(16).ok_or(EncodingError::InvalidHex))
+        .collect::<Result<Vec<_>, _>>()?;
+    if !digits.len().is_multiple_of(2) {
+        return Err(EncodingError::OddLength);
+    }
+    Ok(digits
+        .chunks_exact(2)
+        .map(|pair| u8::try_from(pair[0] << 4 | pair[1]).expect("Two hex digits fit a byte"))
+        .collect())
+}
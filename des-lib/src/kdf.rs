@@ -0,0 +1,234 @@
+//! Password-based key derivation (PBKDF2, RFC 8018).
+//!
+//! Passphrases are stretched into DES and TDEA keys instead of being packed
+//! into a block directly, which would limit them to eight characters and
+//! leave most of the key space unused.
+//!
+//! [`KdfParams`] records everything but the passphrase needed to derive the
+//! key again, and serializes to a short header that is stored in front of
+//! the ciphertext:
+//!
+//! ```text
+//! "DESKDF" | version (1) | PRF (1) | key parts (1) | iterations (4, BE) | salt length (1) | salt
+//! ```
+
+use crate::key::{Key, KeyLength};
+use pbkdf2::pbkdf2_hmac;
+use sha1::Sha1;
+use sha2::Sha256;
+use std::io::{self, Read};
+use thiserror::Error;
+
+/// Iteration count used when none is specified.
+pub const DEFAULT_ITERATIONS: u32 = 100_000;
+
+/// Salt length used by [`KdfParams::generate`].
+pub const SALT_LEN: usize = 16;
+
+const MAGIC: &[u8; 6] = b"DESKDF";
+const VERSION: u8 = 1;
+
+#[derive(Debug, Error)]
+pub enum KdfError {
+    #[error("Input does not start with a key derivation header")]
+    MissingHeader,
+
+    #[error("Key derivation header is malformed")]
+    InvalidHeader,
+
+    #[error("Unsupported key derivation header version {0}")]
+    UnsupportedVersion(u8),
+
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+/// Pseudorandom function used by PBKDF2.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Prf {
+    /// HMAC-SHA1, for interoperability with older systems.
+    HmacSha1,
+    /// HMAC-SHA256.
+    #[default]
+    HmacSha256,
+}
+
+impl Prf {
+    const fn id(self) -> u8 {
+        match self {
+            Self::HmacSha1 => 1,
+            Self::HmacSha256 => 2,
+        }
+    }
+
+    const fn from_id(id: u8) -> Option<Self> {
+        match id {
+            1 => Some(Self::HmacSha1),
+            2 => Some(Self::HmacSha256),
+            _ => None,
+        }
+    }
+}
+
+/// Derive a key of `length` from `passphrase` with PBKDF2, with the parity
+/// bits set to odd parity.
+#[must_use]
+pub fn derive_key(
+    prf: Prf,
+    passphrase: &[u8],
+    salt: &[u8],
+    iterations: u32,
+    length: KeyLength,
+) -> Key {
+    let mut bytes = vec![0; length.bytes()];
+    match prf {
+        Prf::HmacSha1 => pbkdf2_hmac::<Sha1>(passphrase, salt, iterations, &mut bytes),
+        Prf::HmacSha256 => pbkdf2_hmac::<Sha256>(passphrase, salt, iterations, &mut bytes),
+    }
+    Key::from_bytes(&bytes).map_or_else(
+        |_| unreachable!("Derived key has a valid length"),
+        Key::with_odd_parity,
+    )
+}
+
+/// Parameters needed to derive a key from a passphrase again.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KdfParams {
+    prf: Prf,
+    length: KeyLength,
+    iterations: u32,
+    salt: Vec<u8>,
+}
+
+impl KdfParams {
+    /// Create parameters with the given salt.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `iterations` is zero or the salt is longer than 255 bytes.
+    #[must_use]
+    pub fn new(prf: Prf, length: KeyLength, iterations: u32, salt: &[u8]) -> Self {
+        assert!(iterations > 0, "PBKDF2 requires at least one iteration");
+        assert!(u8::try_from(salt.len()).is_ok(), "Salt is too long");
+        Self {
+            prf,
+            length,
+            iterations,
+            salt: salt.to_vec(),
+        }
+    }
+
+    /// Create parameters with a random salt of [`SALT_LEN`] bytes.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `iterations` is zero.
+    #[cfg(feature = "rand")]
+    #[must_use]
+    pub fn generate(prf: Prf, length: KeyLength, iterations: u32) -> Self {
+        Self::new(prf, length, iterations, &rand::random::<[u8; SALT_LEN]>())
+    }
+
+    #[must_use]
+    pub const fn prf(&self) -> Prf {
+        self.prf
+    }
+
+    #[must_use]
+    pub const fn length(&self) -> KeyLength {
+        self.length
+    }
+
+    #[must_use]
+    pub const fn iterations(&self) -> u32 {
+        self.iterations
+    }
+
+    #[must_use]
+    pub fn salt(&self) -> &[u8] {
+        &self.salt
+    }
+
+    /// Derive the key from `passphrase`.
+    #[must_use]
+    pub fn derive(&self, passphrase: &[u8]) -> Key {
+        derive_key(
+            self.prf,
+            passphrase,
+            &self.salt,
+            self.iterations,
+            self.length,
+        )
+    }
+
+    /// Serialize as a header to store in front of the ciphertext.
+    #[must_use]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.push(VERSION);
+        bytes.push(self.prf.id());
+        bytes.push(match self.length {
+            KeyLength::Single => 1,
+            KeyLength::Double => 2,
+            KeyLength::Triple => 3,
+        });
+        bytes.extend_from_slice(&self.iterations.to_be_bytes());
+        // The salt length is checked in `new`.
+        #[allow(clippy::cast_possible_truncation)]
+        bytes.push(self.salt.len() as u8);
+        bytes.extend_from_slice(&self.salt);
+        bytes
+    }
+
+    /// Read a header written by [`KdfParams::to_bytes`], leaving `reader` at
+    /// the first byte after it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the input does not start with a valid header or
+    /// cannot be read.
+    pub fn read<R: Read>(reader: &mut R) -> Result<Self, KdfError> {
+        let mut fixed = [0; 14];
+        read_header(reader, &mut fixed)?;
+        let (magic, rest) = fixed.split_at(MAGIC.len());
+        if magic != MAGIC {
+            return Err(KdfError::MissingHeader);
+        }
+        let &[version, prf, parts, i0, i1, i2, i3, salt_len] = rest else {
+            unreachable!("Header is 14 bytes long");
+        };
+        if version != VERSION {
+            return Err(KdfError::UnsupportedVersion(version));
+        }
+
+        let prf = Prf::from_id(prf).ok_or(KdfError::InvalidHeader)?;
+        let length = match parts {
+            1 => KeyLength::Single,
+            2 => KeyLength::Double,
+            3 => KeyLength::Triple,
+            _ => return Err(KdfError::InvalidHeader),
+        };
+        let iterations = u32::from_be_bytes([i0, i1, i2, i3]);
+        if iterations == 0 {
+            return Err(KdfError::InvalidHeader);
+        }
+        let mut salt = vec![0; usize::from(salt_len)];
+        read_header(reader, &mut salt)?;
+
+        Ok(Self {
+            prf,
+            length,
+            iterations,
+            salt,
+        })
+    }
+}
+
+/// Like [`Read::read_exact`], reporting truncated input as a malformed
+/// header.
+fn read_header<R: Read>(reader: &mut R, buffer: &mut [u8]) -> Result<(), KdfError> {
+    reader.read_exact(buffer).map_err(|e| match e.kind() {
+        io::ErrorKind::UnexpectedEof => KdfError::InvalidHeader,
+        _ => KdfError::Io(e),
+    })
+}
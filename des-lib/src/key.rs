@@ -0,0 +1,400 @@
+//! DES and TDEA keys: parity, weak key checks, check values and generation.
+
+use crate::{BLOCK_SIZE, cmac::cmac, tdes::TripleDes};
+use std::fmt;
+use thiserror::Error;
+
+/// The 4 weak and 12 semi-weak DES keys (FIPS 74, section 3.6), with odd
+/// parity.
+pub const WEAK_KEYS: [u64; 16] = [
+    0x0101_0101_0101_0101,
+    0xFEFE_FEFE_FEFE_FEFE,
+    0xE0E0_E0E0_F1F1_F1F1,
+    0x1F1F_1F1F_0E0E_0E0E,
+    0x011F_011F_010E_010E,
+    0x1F01_1F01_0E01_0E01,
+    0x01E0_01E0_01F1_01F1,
+    0xE001_E001_F101_F101,
+    0x01FE_01FE_01FE_01FE,
+    0xFE01_FE01_FE01_FE01,
+    0x1FE0_1FE0_0EF1_0EF1,
+    0xE01F_E01F_F10E_F10E,
+    0x1FFE_1FFE_0EFE_0EFE,
+    0xFE1F_FE1F_FE0E_FE0E,
+    0xE0FE_E0FE_F1FE_F1FE,
+    0xFEE0_FEE0_FEF1_FEF1,
+];
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum KeyError {
+    #[error("Key must be 8, 16 or 24 bytes long, got {0}")]
+    InvalidLength(usize),
+
+    #[error("Key part {0} is a weak or semi-weak DES key")]
+    WeakKey(usize),
+
+    #[error("Key parts {0} and {1} are equal")]
+    RepeatedPart(usize, usize),
+
+    #[error("At least 2 key components are required, got {0}")]
+    TooFewComponents(usize),
+
+    #[error("Key components have different lengths")]
+    MismatchedComponents,
+
+    #[error("Key check value mismatch")]
+    KcvMismatch,
+}
+
+/// How a key check value is computed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum KcvMethod {
+    /// First 3 bytes of an all-zero block encrypted under the key.
+    #[default]
+    ZeroBlock,
+    /// First 5 bytes of the CMAC of an all-zero block (ANSI X9.24-1:2017).
+    Cmac,
+}
+
+impl KcvMethod {
+    /// Length of the check value in bytes.
+    #[must_use]
+    pub const fn length(self) -> usize {
+        match self {
+            Self::ZeroBlock => 3,
+            Self::Cmac => 5,
+        }
+    }
+}
+
+/// Number of independent DES keys in a key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyLength {
+    /// Single DES.
+    Single,
+    /// Two-key TDEA (keying option 2).
+    Double,
+    /// Three-key TDEA (keying option 1).
+    Triple,
+}
+
+impl KeyLength {
+    /// Number of 64-bit key parts.
+    #[must_use]
+    pub const fn parts(self) -> usize {
+        match self {
+            Self::Single => 1,
+            Self::Double => 2,
+            Self::Triple => 3,
+        }
+    }
+
+    /// Key length in bytes, parity bits included.
+    #[must_use]
+    pub const fn bytes(self) -> usize {
+        self.parts() * BLOCK_SIZE
+    }
+}
+
+impl fmt::Display for KeyLength {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(match self {
+            Self::Single => "DES",
+            Self::Double => "2TDEA",
+            Self::Triple => "3TDEA",
+        })
+    }
+}
+
+/// A DES, 2TDEA or 3TDEA key.
+///
+/// The [`fmt::Debug`] output shows the key check value instead of the key
+/// material.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Key {
+    parts: [u64; 3],
+    length: KeyLength,
+}
+
+impl Key {
+    /// Create a key from one, two or three 64-bit parts.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`KeyError::InvalidLength`] for any other number of parts.
+    pub fn from_parts(parts: &[u64]) -> Result<Self, KeyError> {
+        let length = match parts.len() {
+            1 => KeyLength::Single,
+            2 => KeyLength::Double,
+            3 => KeyLength::Triple,
+            other => return Err(KeyError::InvalidLength(other * BLOCK_SIZE)),
+        };
+        let mut key = Self {
+            parts: [0; 3],
+            length,
+        };
+        key.parts[..parts.len()].copy_from_slice(parts);
+        Ok(key)
+    }
+
+    /// Create a single DES key.
+    #[must_use]
+    pub const fn single(key: u64) -> Self {
+        Self {
+            parts: [key, 0, 0],
+            length: KeyLength::Single,
+        }
+    }
+
+    /// Create a 2TDEA key.
+    #[must_use]
+    pub const fn double(left: u64, right: u64) -> Self {
+        Self {
+            parts: [left, right, 0],
+            length: KeyLength::Double,
+        }
+    }
+
+    /// Create a key from 8, 16 or 24 big-endian bytes.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`KeyError::InvalidLength`] for any other length.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, KeyError> {
+        let (parts, rest) = bytes.as_chunks::<BLOCK_SIZE>();
+        if !rest.is_empty() {
+            return Err(KeyError::InvalidLength(bytes.len()));
+        }
+        let parts = parts
+            .iter()
+            .copied()
+            .map(u64::from_be_bytes)
+            .collect::<Vec<_>>();
+        Self::from_parts(&parts).map_err(|_| KeyError::InvalidLength(bytes.len()))
+    }
+
+    #[must_use]
+    pub const fn length(&self) -> KeyLength {
+        self.length
+    }
+
+    /// The independent 64-bit key parts.
+    #[must_use]
+    pub fn parts(&self) -> &[u64] {
+        &self.parts[..self.length.parts()]
+    }
+
+    /// The key as big-endian bytes.
+    #[must_use]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.parts()
+            .iter()
+            .flat_map(|part| part.to_be_bytes())
+            .collect()
+    }
+
+    /// A TDEA cipher for this key; single-length keys repeat the same part,
+    /// which is equivalent to DES.
+    #[must_use]
+    pub fn cipher(&self) -> TripleDes {
+        let [k1, k2, k3] = self.parts;
+        match self.length {
+            KeyLength::Single => TripleDes::new(k1, k1, k1),
+            KeyLength::Double => TripleDes::with_two_keys(k1, k2),
+            KeyLength::Triple => TripleDes::new(k1, k2, k3),
+        }
+    }
+
+    /// Legacy key check value: the first three bytes of an all-zero block
+    /// encrypted under the key.
+    #[must_use]
+    pub fn kcv(&self) -> [u8; 3] {
+        let check = self.cipher().encrypt(0).to_be_bytes();
+        [check[0], check[1], check[2]]
+    }
+
+    /// Key check value computed with `method`.
+    #[must_use]
+    pub fn check_value(&self, method: KcvMethod) -> Vec<u8> {
+        let cipher = self.cipher();
+        let check = match method {
+            KcvMethod::ZeroBlock => cipher.encrypt(0),
+            KcvMethod::Cmac => cmac(&cipher, &[0; BLOCK_SIZE]),
+        };
+        check.to_be_bytes()[..method.length()].to_vec()
+    }
+
+    /// Whether every byte of the key has odd parity.
+    #[must_use]
+    pub fn has_odd_parity(&self) -> bool {
+        self.parts()
+            .iter()
+            .all(|&part| set_odd_parity(part) == part)
+    }
+
+    /// Return the key with the parity bit of every byte set to odd parity.
+    #[must_use]
+    pub fn with_odd_parity(mut self) -> Self {
+        for part in &mut self.parts[..self.length.parts()] {
+            *part = set_odd_parity(*part);
+        }
+        self
+    }
+
+    /// Check that no part is weak or semi-weak and that TDEA parts differ,
+    /// so the key does not degrade to fewer independent stages.
+    ///
+    /// # Errors
+    ///
+    /// Returns the first problem found, with 1-based part numbers.
+    pub fn validate(&self) -> Result<(), KeyError> {
+        let parts = self.parts();
+        if let Some(index) = parts.iter().position(|&part| is_weak_key(part)) {
+            return Err(KeyError::WeakKey(index + 1));
+        }
+        for (i, &first) in parts.iter().enumerate() {
+            for (j, &second) in parts.iter().enumerate().skip(i + 1) {
+                if set_odd_parity(first) == set_odd_parity(second) {
+                    return Err(KeyError::RepeatedPart(i + 1, j + 1));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Verify the key against an expected check value.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`KeyError::KcvMismatch`] if the check values differ.
+    pub fn verify_check_value(&self, method: KcvMethod, expected: &[u8]) -> Result<(), KeyError> {
+        if self.check_value(method) == expected {
+            Ok(())
+        } else {
+            Err(KeyError::KcvMismatch)
+        }
+    }
+
+    /// Combine XOR key components into a key with odd parity.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if fewer than two components are given or their
+    /// lengths differ.
+    pub fn combine(components: &[Self]) -> Result<Self, KeyError> {
+        let [first, rest @ ..] = components else {
+            return Err(KeyError::TooFewComponents(0));
+        };
+        if rest.is_empty() {
+            return Err(KeyError::TooFewComponents(1));
+        }
+        if rest
+            .iter()
+            .any(|component| component.length != first.length)
+        {
+            return Err(KeyError::MismatchedComponents);
+        }
+
+        let mut key = first.clone();
+        for component in rest {
+            for (part, other) in key.parts.iter_mut().zip(component.parts) {
+                *part ^= other;
+            }
+        }
+        Ok(key.with_odd_parity())
+    }
+
+    /// Split the key into `count` random XOR components with odd parity.
+    ///
+    /// Combining all components with [`Key::combine`] yields the key with
+    /// odd parity; any subset of fewer components reveals nothing about it.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`KeyError::TooFewComponents`] if `count` is less than two.
+    #[cfg(feature = "rand")]
+    pub fn split(&self, count: usize) -> Result<Vec<Self>, KeyError> {
+        use rand::Rng;
+
+        if count < 2 {
+            return Err(KeyError::TooFewComponents(count));
+        }
+
+        let mut rng = rand::rng();
+        let mut last = self.clone();
+        let mut components = (1..count)
+            .map(|_| {
+                let mut component = Self {
+                    parts: [0; 3],
+                    length: self.length,
+                };
+                for (part, rest) in component.parts[..self.length.parts()]
+                    .iter_mut()
+                    .zip(&mut last.parts)
+                {
+                    *part = set_odd_parity(rng.random());
+                    *rest ^= *part;
+                }
+                component
+            })
+            .collect::<Vec<_>>();
+        components.push(last.with_odd_parity());
+        Ok(components)
+    }
+
+    /// Generate a random key with odd parity that passes [`Key::validate`],
+    /// using the operating system seeded thread-local generator.
+    #[cfg(feature = "rand")]
+    #[must_use]
+    pub fn generate(length: KeyLength) -> Self {
+        Self::generate_with(&mut rand::rng(), length)
+    }
+
+    /// Like [`Key::generate`], drawing from `rng`.
+    #[cfg(feature = "rand")]
+    #[must_use]
+    pub fn generate_with<R: rand::Rng + ?Sized>(rng: &mut R, length: KeyLength) -> Self {
+        loop {
+            let mut key = Self {
+                parts: [0; 3],
+                length,
+            };
+            for part in &mut key.parts[..length.parts()] {
+                *part = set_odd_parity(rng.random());
+            }
+            if key.validate().is_ok() {
+                return key;
+            }
+        }
+    }
+}
+
+impl fmt::Debug for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let [a, b, c] = self.kcv();
+        f.debug_struct("Key")
+            .field("length", &self.length)
+            .field("kcv", &format_args!("{a:02X}{b:02X}{c:02X}"))
+            .finish_non_exhaustive()
+    }
+}
+
+/// Set the lowest bit of every byte so that each byte has an odd number of
+/// set bits.
+#[must_use]
+pub const fn set_odd_parity(key: u64) -> u64 {
+    let mut bytes = key.to_be_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        let data = bytes[i] & 0xFE;
+        bytes[i] = data | ((data.count_ones() & 1) as u8 ^ 1);
+        i += 1;
+    }
+    u64::from_be_bytes(bytes)
+}
+
+/// Whether `key` is one of the [`WEAK_KEYS`], ignoring parity bits.
+#[must_use]
+pub fn is_weak_key(key: u64) -> bool {
+    WEAK_KEYS.contains(&set_odd_parity(key))
+}
@@ -0,0 +1,821 @@
+//! TR-31 (ANSI X9.143) key blocks with TDEA protection.
+//!
+//! A key block is printable ASCII: a 16-character header, optional blocks,
+//! the encrypted key data in hex and a MAC in hex. The MAC covers the header,
+//! so its attributes cannot be changed without the key block protection key
+//! (KBPK):
+//!
+//! | Version | Binding                                   | MAC                   |
+//! |---------|-------------------------------------------|-----------------------|
+//! | A, C    | KBPK variants (`0x45` and `0x4D` per byte) | 4-byte TDEA CBC-MAC   |
+//! | B       | KBPK derivation with the CMAC counter KDF  | 8-byte TDEA CMAC      |
+//!
+//! Version A is deprecated and computed like version C. The AES version D is
+//! not supported.
+
+#[cfg(feature = "rand")]
+use crate::mode::cbc_encrypt;
+use crate::{
+    blocks_to_bytes, bytes_to_blocks,
+    cmac::{Cmac, cmac, constant_time_eq},
+    encoding::Encoding,
+    key::{Key, KeyLength},
+    mac::{self, MacAlgorithm, MacPadding},
+    mode::cbc_decrypt,
+    tdes::TripleDes,
+};
+#[cfg(feature = "rand")]
+use std::fmt::Write;
+use std::{
+    fmt::{self, Display},
+    str::FromStr,
+};
+use thiserror::Error;
+
+/// Length of the fixed header.
+pub const HEADER_LEN: usize = 16;
+
+/// Longest key block, limited by the four-digit length field.
+pub const MAX_LEN: usize = 9999;
+
+/// Block size that the header and optional blocks are padded to.
+const BLOCK_LEN: usize = 8;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum KeyBlockError {
+    #[error("Malformed key block: {0}")]
+    Malformed(&'static str),
+
+    #[error("Unsupported key block version '{0}'; only A, B and C are supported")]
+    UnsupportedVersion(char),
+
+    #[error("Invalid {0} in key block header")]
+    InvalidField(&'static str),
+
+    #[error("Key block protection keys must be 16 or 24 bytes, got {0} bytes")]
+    KbpkLength(usize),
+
+    #[error("Only DES and TDEA keys can be wrapped, not algorithm '{0}'")]
+    UnsupportedAlgorithm(Algorithm),
+
+    #[error("Key block MAC does not match: the block was modified or the key is wrong")]
+    Authentication,
+}
+
+/// Key block version, which determines how the key is protected.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Version {
+    /// Key variant binding; deprecated in favour of B and C.
+    A,
+    /// Key derivation binding.
+    #[default]
+    B,
+    /// Key variant binding.
+    C,
+}
+
+impl Version {
+    const fn id(self) -> u8 {
+        match self {
+            Self::A => b'A',
+            Self::B => b'B',
+            Self::C => b'C',
+        }
+    }
+
+    const fn from_id(id: u8) -> Option<Self> {
+        match id {
+            b'A' => Some(Self::A),
+            b'B' => Some(Self::B),
+            b'C' => Some(Self::C),
+            _ => None,
+        }
+    }
+
+    #[must_use]
+    pub const fn description(self) -> &'static str {
+        match self {
+            Self::A => "TDEA key variant binding (deprecated)",
+            Self::B => "TDEA key derivation binding",
+            Self::C => "TDEA key variant binding",
+        }
+    }
+
+    const fn mac_len(self) -> usize {
+        match self {
+            Self::A | Self::C => 4,
+            Self::B => 8,
+        }
+    }
+}
+
+impl Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", char::from(self.id()))
+    }
+}
+
+/// Two-character key usage, such as `P0` for PIN encryption. Values
+/// starting with a digit are reserved for proprietary use.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Usage([u8; 2]);
+
+impl Usage {
+    pub const BDK: Self = Self(*b"B0");
+    pub const DUKPT_INITIAL_KEY: Self = Self(*b"B1");
+    pub const CARD_VERIFICATION: Self = Self(*b"C0");
+    pub const DATA_ENCRYPTION: Self = Self(*b"D0");
+    pub const EMV_APPLICATION_CRYPTOGRAMS: Self = Self(*b"E0");
+    pub const EMV_SECURE_MESSAGING_CONFIDENTIALITY: Self = Self(*b"E1");
+    pub const EMV_SECURE_MESSAGING_INTEGRITY: Self = Self(*b"E2");
+    pub const EMV_OTHER: Self = Self(*b"E6");
+    pub const KEY_ENCRYPTION: Self = Self(*b"K0");
+    pub const KEY_BLOCK_PROTECTION: Self = Self(*b"K1");
+    pub const MAC_ISO_16609: Self = Self(*b"M0");
+    pub const MAC_ALGORITHM_1: Self = Self(*b"M1");
+    pub const MAC_ALGORITHM_3: Self = Self(*b"M3");
+    pub const MAC_CMAC: Self = Self(*b"M6");
+    pub const PIN_ENCRYPTION: Self = Self(*b"P0");
+    pub const PIN_VERIFICATION_IBM_3624: Self = Self(*b"V1");
+    pub const PIN_VERIFICATION_VISA_PVV: Self = Self(*b"V2");
+
+    /// What the usage means, for the usages named above.
+    #[must_use]
+    pub const fn description(self) -> Option<&'static str> {
+        Some(match &self.0 {
+            b"B0" => "Base derivation key",
+            b"B1" => "Initial DUKPT key",
+            b"C0" => "Card verification key",
+            b"D0" => "Data encryption",
+            b"E0" => "EMV master key for application cryptograms",
+            b"E1" => "EMV master key for secure messaging confidentiality",
+            b"E2" => "EMV master key for secure messaging integrity",
+            b"E6" => "EMV master key, other",
+            b"K0" => "Key encryption or wrapping",
+            b"K1" => "Key block protection key",
+            b"M0" => "ISO 16609 MAC",
+            b"M1" => "ISO/IEC 9797-1 MAC algorithm 1",
+            b"M3" => "ISO/IEC 9797-1 MAC algorithm 3",
+            b"M6" => "ISO/IEC 9797-1 MAC algorithm 5 (CMAC)",
+            b"P0" => "PIN encryption",
+            b"V1" => "PIN verification, IBM 3624",
+            b"V2" => "PIN verification, Visa PVV",
+            _ => return None,
+        })
+    }
+}
+
+impl FromStr for Usage {
+    type Err = KeyBlockError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.as_bytes()
+            .try_into()
+            .ok()
+            .filter(|usage: &[u8; 2]| usage.iter().all(u8::is_ascii_alphanumeric))
+            .map(Self)
+            .ok_or(KeyBlockError::InvalidField("key usage"))
+    }
+}
+
+impl Display for Usage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let [a, b] = self.0;
+        write!(f, "{}{}", char::from(a), char::from(b))
+    }
+}
+
+impl fmt::Debug for Usage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Usage({self})")
+    }
+}
+
+/// Algorithm of the wrapped key.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Algorithm {
+    Aes,
+    Des,
+    EllipticCurve,
+    Hmac,
+    Rsa,
+    Dsa,
+    #[default]
+    Tdea,
+}
+
+impl Algorithm {
+    const fn id(self) -> u8 {
+        match self {
+            Self::Aes => b'A',
+            Self::Des => b'D',
+            Self::EllipticCurve => b'E',
+            Self::Hmac => b'H',
+            Self::Rsa => b'R',
+            Self::Dsa => b'S',
+            Self::Tdea => b'T',
+        }
+    }
+
+    const fn from_id(id: u8) -> Option<Self> {
+        match id {
+            b'A' => Some(Self::Aes),
+            b'D' => Some(Self::Des),
+            b'E' => Some(Self::EllipticCurve),
+            b'H' => Some(Self::Hmac),
+            b'R' => Some(Self::Rsa),
+            b'S' => Some(Self::Dsa),
+            b'T' => Some(Self::Tdea),
+            _ => None,
+        }
+    }
+
+    #[must_use]
+    pub const fn description(self) -> &'static str {
+        match self {
+            Self::Aes => "AES",
+            Self::Des => "DES",
+            Self::EllipticCurve => "Elliptic curve",
+            Self::Hmac => "HMAC",
+            Self::Rsa => "RSA",
+            Self::Dsa => "DSA",
+            Self::Tdea => "TDEA",
+        }
+    }
+}
+
+impl Display for Algorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", char::from(self.id()))
+    }
+}
+
+/// Operations the wrapped key may be used for.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ModeOfUse {
+    /// Encrypt and decrypt, or wrap and unwrap.
+    Both,
+    /// Generate and verify.
+    Calculate,
+    /// Decrypt or unwrap only.
+    Decrypt,
+    /// Encrypt or wrap only.
+    Encrypt,
+    /// Generate only.
+    Generate,
+    /// No special restrictions.
+    #[default]
+    NoRestrictions,
+    /// Signature only.
+    Sign,
+    /// Sign and decrypt.
+    SignAndDecrypt,
+    /// Verify only.
+    Verify,
+    /// Derive other keys.
+    Derive,
+    /// Create key variants.
+    Variant,
+}
+
+impl ModeOfUse {
+    const fn id(self) -> u8 {
+        match self {
+            Self::Both => b'B',
+            Self::Calculate => b'C',
+            Self::Decrypt => b'D',
+            Self::Encrypt => b'E',
+            Self::Generate => b'G',
+            Self::NoRestrictions => b'N',
+            Self::Sign => b'S',
+            Self::SignAndDecrypt => b'T',
+            Self::Verify => b'V',
+            Self::Derive => b'X',
+            Self::Variant => b'Y',
+        }
+    }
+
+    const fn from_id(id: u8) -> Option<Self> {
+        match id {
+            b'B' => Some(Self::Both),
+            b'C' => Some(Self::Calculate),
+            b'D' => Some(Self::Decrypt),
+            b'E' => Some(Self::Encrypt),
+            b'G' => Some(Self::Generate),
+            b'N' => Some(Self::NoRestrictions),
+            b'S' => Some(Self::Sign),
+            b'T' => Some(Self::SignAndDecrypt),
+            b'V' => Some(Self::Verify),
+            b'X' => Some(Self::Derive),
+            b'Y' => Some(Self::Variant),
+            _ => None,
+        }
+    }
+
+    #[must_use]
+    pub const fn description(self) -> &'static str {
+        match self {
+            Self::Both => "Encrypt and decrypt",
+            Self::Calculate => "Generate and verify",
+            Self::Decrypt => "Decrypt only",
+            Self::Encrypt => "Encrypt only",
+            Self::Generate => "Generate only",
+            Self::NoRestrictions => "No special restrictions",
+            Self::Sign => "Signature only",
+            Self::SignAndDecrypt => "Sign and decrypt",
+            Self::Verify => "Verify only",
+            Self::Derive => "Key derivation",
+            Self::Variant => "Key variants",
+        }
+    }
+}
+
+impl Display for ModeOfUse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", char::from(self.id()))
+    }
+}
+
+/// Whether the wrapped key may be exported again.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Exportability {
+    /// Exportable under a trusted key.
+    #[default]
+    Exportable,
+    /// Not exportable.
+    NonExportable,
+    /// Sensitive; exportable only in forms not covered by X9.143.
+    Sensitive,
+}
+
+impl Exportability {
+    const fn id(self) -> u8 {
+        match self {
+            Self::Exportable => b'E',
+            Self::NonExportable => b'N',
+            Self::Sensitive => b'S',
+        }
+    }
+
+    const fn from_id(id: u8) -> Option<Self> {
+        match id {
+            b'E' => Some(Self::Exportable),
+            b'N' => Some(Self::NonExportable),
+            b'S' => Some(Self::Sensitive),
+            _ => None,
+        }
+    }
+
+    #[must_use]
+    pub const fn description(self) -> &'static str {
+        match self {
+            Self::Exportable => "Exportable under a trusted key",
+            Self::NonExportable => "Not exportable",
+            Self::Sensitive => "Sensitive",
+        }
+    }
+}
+
+impl Display for Exportability {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", char::from(self.id()))
+    }
+}
+
+/// An optional header block, such as `KS` for a DUKPT key serial number.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OptionalBlock {
+    /// Two alphanumeric characters.
+    pub id: String,
+    /// Printable ASCII.
+    pub data: String,
+}
+
+impl OptionalBlock {
+    /// Identifier of the padding block, which is added and removed
+    /// automatically.
+    pub const PADDING: &str = "PB";
+
+    #[cfg(feature = "rand")]
+    fn encode(&self, out: &mut String) -> Result<(), KeyBlockError> {
+        let invalid = KeyBlockError::InvalidField("optional block");
+        let length = 4 + self.data.len();
+        if self.id.len() != 2
+            || !self.id.bytes().all(|byte| byte.is_ascii_alphanumeric())
+            || self.id == Self::PADDING
+            || !self
+                .data
+                .bytes()
+                .all(|byte| byte.is_ascii_graphic() || byte == b' ')
+            || length > 0xFF
+        {
+            return Err(invalid);
+        }
+        let _ = write!(out, "{}{length:02X}{}", self.id, self.data);
+        Ok(())
+    }
+}
+
+/// The attributes of a wrapped key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Header {
+    pub version: Version,
+    pub usage: Usage,
+    pub algorithm: Algorithm,
+    pub mode_of_use: ModeOfUse,
+    /// Two alphanumeric characters; `00` when key versions are not used.
+    pub key_version: String,
+    pub exportability: Exportability,
+    /// Optional blocks other than padding.
+    pub optional_blocks: Vec<OptionalBlock>,
+}
+
+impl Header {
+    /// A header with version B, key version `00`, exportable and without
+    /// optional blocks.
+    #[must_use]
+    pub fn new(usage: Usage, algorithm: Algorithm, mode_of_use: ModeOfUse) -> Self {
+        Self {
+            version: Version::default(),
+            usage,
+            algorithm,
+            mode_of_use,
+            key_version: "00".to_owned(),
+            exportability: Exportability::default(),
+            optional_blocks: Vec::new(),
+        }
+    }
+
+    /// The header and optional blocks, padded to a multiple of the block
+    /// size, for a key block with `payload_len` characters of key data and
+    /// MAC.
+    #[cfg(feature = "rand")]
+    fn encode(&self, payload_len: usize) -> Result<String, KeyBlockError> {
+        if self.key_version.len() != 2
+            || !self
+                .key_version
+                .bytes()
+                .all(|byte| byte.is_ascii_alphanumeric())
+        {
+            return Err(KeyBlockError::InvalidField("key version"));
+        }
+
+        let mut blocks = String::new();
+        for block in &self.optional_blocks {
+            block.encode(&mut blocks)?;
+        }
+        let mut count = self.optional_blocks.len();
+        let unaligned = (HEADER_LEN + blocks.len()) % BLOCK_LEN;
+        if unaligned != 0 {
+            // The padding block needs at least its ID and length.
+            let mut length = BLOCK_LEN - unaligned;
+            if length < 4 {
+                length += BLOCK_LEN;
+            }
+            let _ = write!(
+                blocks,
+                "{}{length:02X}{}",
+                OptionalBlock::PADDING,
+                "0".repeat(length - 4)
+            );
+            count += 1;
+        }
+        if count > 99 {
+            return Err(KeyBlockError::InvalidField("optional block count"));
+        }
+
+        let length = HEADER_LEN + blocks.len() + payload_len;
+        if length > MAX_LEN {
+            return Err(KeyBlockError::InvalidField("length"));
+        }
+        Ok(format!(
+            "{}{length:04}{}{}{}{}{}{count:02}00{blocks}",
+            self.version,
+            self.usage,
+            self.algorithm,
+            self.mode_of_use,
+            self.key_version,
+            self.exportability,
+        ))
+    }
+}
+
+/// Parse and validate the header and optional blocks of a key block without
+/// checking its MAC.
+///
+/// # Errors
+///
+/// Returns an error if the key block is malformed or uses an unsupported
+/// version.
+pub fn inspect(block: &str) -> Result<Header, KeyBlockError> {
+    parse(block).map(|parsed| parsed.header)
+}
+
+/// Wrap `key` under `kbpk` with random padding.
+///
+/// # Errors
+///
+/// Returns an error if `kbpk` is not a 2TDEA or 3TDEA key, the header is
+/// invalid, or its algorithm is not DES or TDEA.
+#[cfg(feature = "rand")]
+pub fn wrap(kbpk: &Key, header: &Header, key: &Key) -> Result<String, KeyBlockError> {
+    wrap_with(&mut rand::rng(), kbpk, header, key)
+}
+
+/// Like [`wrap`], drawing the padding from `rng`.
+///
+/// # Errors
+///
+/// Returns the errors of [`wrap`].
+#[cfg(feature = "rand")]
+pub fn wrap_with<R: rand::Rng + ?Sized>(
+    rng: &mut R,
+    kbpk: &Key,
+    header: &Header,
+    key: &Key,
+) -> Result<String, KeyBlockError> {
+    check_algorithm(header.algorithm, key.length())?;
+    let keys = BindingKeys::derive(header.version, kbpk)?;
+
+    // Key length in bits, the key, and random padding to a whole block.
+    let key_bytes = key.to_bytes();
+    let bits = u16::try_from(key_bytes.len() * 8)
+        .unwrap_or_else(|_| unreachable!("Keys are at most 24 bytes"));
+    let mut data = bits.to_be_bytes().to_vec();
+    data.extend_from_slice(&key_bytes);
+    let mut padding = vec![0; BLOCK_LEN - data.len() % BLOCK_LEN];
+    rng.fill_bytes(&mut padding);
+    data.extend_from_slice(&padding);
+
+    let payload_len = 2 * (data.len() + header.version.mac_len());
+    let mut block = header.encode(payload_len)?;
+    let (encrypted, mac) = keys.seal(block.as_bytes(), &data);
+    block.push_str(&Encoding::Hex.encode(&encrypted));
+    block.push_str(&Encoding::Hex.encode(&mac));
+    Ok(block)
+}
+
+/// Check the MAC of a key block and return its header and key.
+///
+/// # Errors
+///
+/// Returns [`KeyBlockError::Authentication`] if the block was modified or
+/// `kbpk` is wrong, [`KeyBlockError::InvalidField`] if the header algorithm
+/// does not match the length of the key, and other errors for malformed or
+/// unsupported blocks.
+pub fn unwrap(kbpk: &Key, block: &str) -> Result<(Header, Key), KeyBlockError> {
+    let parsed = parse(block)?;
+    let keys = BindingKeys::derive(parsed.header.version, kbpk)?;
+    let data = keys.open(parsed.authenticated, &parsed.encrypted, &parsed.mac)?;
+
+    let [high, low, rest @ ..] = data.as_slice() else {
+        return Err(KeyBlockError::Malformed("key data"));
+    };
+    let bits = usize::from(u16::from_be_bytes([*high, *low]));
+    let key = rest
+        .get(..bits / 8)
+        .filter(|_| bits % 8 == 0)
+        .ok_or(KeyBlockError::Malformed("key length"))?;
+    let key = Key::from_bytes(key).map_err(|_| KeyBlockError::Malformed("key length"))?;
+    check_algorithm(parsed.header.algorithm, key.length())?;
+    Ok((parsed.header, key))
+}
+
+/// Check that the algorithm in the header is DES for a single-length key
+/// and TDEA for a 2TDEA or 3TDEA key.
+const fn check_algorithm(algorithm: Algorithm, length: KeyLength) -> Result<(), KeyBlockError> {
+    match (algorithm, length) {
+        (Algorithm::Des, KeyLength::Single)
+        | (Algorithm::Tdea, KeyLength::Double | KeyLength::Triple) => Ok(()),
+        (Algorithm::Des | Algorithm::Tdea, _) => Err(KeyBlockError::InvalidField("algorithm")),
+        (algorithm, _) => Err(KeyBlockError::UnsupportedAlgorithm(algorithm)),
+    }
+}
+
+/// A key block split into its parts.
+struct Parsed<'a> {
+    header: Header,
+    /// Header and optional blocks, as covered by the MAC.
+    authenticated: &'a [u8],
+    encrypted: Vec<u8>,
+    mac: Vec<u8>,
+}
+
+fn parse(block: &str) -> Result<Parsed<'_>, KeyBlockError> {
+    let bytes = block.as_bytes();
+    if !block.is_ascii() || bytes.len() < HEADER_LEN {
+        return Err(KeyBlockError::Malformed("too short"));
+    }
+    let version = Version::from_id(bytes[0])
+        .ok_or_else(|| KeyBlockError::UnsupportedVersion(char::from(bytes[0])))?;
+    let length = decimal(&block[1..5]).ok_or(KeyBlockError::InvalidField("length"))?;
+    if length != bytes.len() {
+        return Err(KeyBlockError::Malformed("length does not match the header"));
+    }
+    let usage = block[5..7].parse()?;
+    let algorithm = Algorithm::from_id(bytes[7]).ok_or(KeyBlockError::InvalidField("algorithm"))?;
+    let mode_of_use =
+        ModeOfUse::from_id(bytes[8]).ok_or(KeyBlockError::InvalidField("mode of use"))?;
+    let key_version = &block[9..11];
+    if !key_version.bytes().all(|byte| byte.is_ascii_alphanumeric()) {
+        return Err(KeyBlockError::InvalidField("key version"));
+    }
+    let exportability =
+        Exportability::from_id(bytes[11]).ok_or(KeyBlockError::InvalidField("exportability"))?;
+    let count =
+        decimal(&block[12..14]).ok_or(KeyBlockError::InvalidField("optional block count"))?;
+
+    let mut position = HEADER_LEN;
+    let mut optional_blocks = Vec::with_capacity(count);
+    for _ in 0..count {
+        let invalid = || KeyBlockError::InvalidField("optional block");
+        let id = block.get(position..position + 2).ok_or_else(invalid)?;
+        let length = block
+            .get(position + 2..position + 4)
+            .and_then(|length| usize::from_str_radix(length, 16).ok())
+            .filter(|&length| length >= 4)
+            .ok_or_else(invalid)?;
+        let data = block
+            .get(position + 4..position + length)
+            .ok_or_else(invalid)?;
+        if id != OptionalBlock::PADDING {
+            optional_blocks.push(OptionalBlock {
+                id: id.to_owned(),
+                data: data.to_owned(),
+            });
+        }
+        position += length;
+    }
+    if !position.is_multiple_of(BLOCK_LEN) {
+        return Err(KeyBlockError::Malformed("optional blocks are not padded"));
+    }
+
+    let payload = &block[position..];
+    let mac_hex = 2 * version.mac_len();
+    let Some(split) = payload
+        .len()
+        .checked_sub(mac_hex)
+        .filter(|&split| split > 0 && split % (2 * BLOCK_LEN) == 0)
+    else {
+        return Err(KeyBlockError::Malformed("key data length"));
+    };
+    let encrypted = Encoding::Hex
+        .decode(&payload[..split])
+        .map_err(|_| KeyBlockError::Malformed("key data"))?;
+    let mac = Encoding::Hex
+        .decode(&payload[split..])
+        .map_err(|_| KeyBlockError::Malformed("MAC"))?;
+
+    Ok(Parsed {
+        header: Header {
+            version,
+            usage,
+            algorithm,
+            mode_of_use,
+            key_version: key_version.to_owned(),
+            exportability,
+            optional_blocks,
+        },
+        authenticated: &bytes[..position],
+        encrypted,
+        mac,
+    })
+}
+
+/// Encryption and authentication keys bound to the KBPK.
+struct BindingKeys {
+    version: Version,
+    encryption: TripleDes,
+    authentication: Key,
+}
+
+impl BindingKeys {
+    fn derive(version: Version, kbpk: &Key) -> Result<Self, KeyBlockError> {
+        if kbpk.length() == KeyLength::Single {
+            return Err(KeyBlockError::KbpkLength(kbpk.length().bytes()));
+        }
+        Ok(match version {
+            Version::A | Version::C => Self {
+                version,
+                encryption: variant(kbpk, 0x45).cipher(),
+                authentication: variant(kbpk, 0x4D),
+            },
+            Version::B => Self {
+                version,
+                encryption: derive(kbpk, 0x0000).cipher(),
+                authentication: derive(kbpk, 0x0001),
+            },
+        })
+    }
+
+    /// Encrypt the key data and compute the MAC.
+    #[cfg(feature = "rand")]
+    fn seal(&self, header: &[u8], data: &[u8]) -> (Vec<u8>, Vec<u8>) {
+        let mut blocks = bytes_to_blocks(data);
+        match self.version {
+            Version::A | Version::C => {
+                cbc_encrypt(&self.encryption, header_iv(header), &mut blocks);
+                let encrypted = blocks_to_bytes(&blocks);
+                let mac = self.cbc_mac(header, &encrypted);
+                (encrypted, mac)
+            }
+            Version::B => {
+                let mac = self.cmac(header, data);
+                cbc_encrypt(&self.encryption, mac, &mut blocks);
+                (blocks_to_bytes(&blocks), mac.to_be_bytes().to_vec())
+            }
+        }
+    }
+
+    /// Check the MAC and decrypt the key data.
+    fn open(&self, header: &[u8], encrypted: &[u8], mac: &[u8]) -> Result<Vec<u8>, KeyBlockError> {
+        let mut blocks = bytes_to_blocks(encrypted);
+        let (data, expected) = match self.version {
+            Version::A | Version::C => {
+                let expected = self.cbc_mac(header, encrypted);
+                cbc_decrypt(&self.encryption, header_iv(header), &mut blocks);
+                (blocks_to_bytes(&blocks), expected)
+            }
+            Version::B => {
+                let iv = mac
+                    .try_into()
+                    .map(u64::from_be_bytes)
+                    .map_err(|_| KeyBlockError::Malformed("MAC"))?;
+                cbc_decrypt(&self.encryption, iv, &mut blocks);
+                let data = blocks_to_bytes(&blocks);
+                let expected = self.cmac(header, &data).to_be_bytes().to_vec();
+                (data, expected)
+            }
+        };
+        if constant_time_eq(&expected, mac) {
+            Ok(data)
+        } else {
+            Err(KeyBlockError::Authentication)
+        }
+    }
+
+    fn cbc_mac(&self, header: &[u8], encrypted: &[u8]) -> Vec<u8> {
+        let message = [header, encrypted].concat();
+        let mac = mac::mac(
+            &self.authentication,
+            MacAlgorithm::Cbc,
+            MacPadding::Method1,
+            &message,
+        )
+        .unwrap_or_else(|_| unreachable!("CBC-MAC accepts any key length"));
+        mac.to_be_bytes()[..self.version.mac_len()].to_vec()
+    }
+
+    fn cmac(&self, header: &[u8], data: &[u8]) -> u64 {
+        let mut mac = Cmac::new(self.authentication.cipher());
+        mac.update(header);
+        mac.update(data);
+        mac.finalize()
+    }
+}
+
+/// The KBPK with `byte` combined into every byte by exclusive or.
+fn variant(kbpk: &Key, byte: u8) -> Key {
+    let mask = u64::from_be_bytes([byte; 8]);
+    let parts = kbpk
+        .parts()
+        .iter()
+        .map(|part| part ^ mask)
+        .collect::<Vec<_>>();
+    Key::from_parts(&parts).unwrap_or_else(|_| unreachable!("Same number of parts"))
+}
+
+/// Version B key derivation: CMAC under the KBPK of a counter, the key usage
+/// indicator, a separator, the algorithm and the length in bits.
+fn derive(kbpk: &Key, usage: u16) -> Key {
+    let cipher = kbpk.cipher();
+    let (algorithm, bits): (u16, u16) = match kbpk.length() {
+        KeyLength::Triple => (0x0001, 192),
+        _ => (0x0000, 128),
+    };
+    let parts = (1..=u8::MAX)
+        .take(usize::from(bits / 64))
+        .map(|counter| {
+            let [u0, u1] = usage.to_be_bytes();
+            let [a0, a1] = algorithm.to_be_bytes();
+            let [l0, l1] = bits.to_be_bytes();
+            cmac(&cipher, &[counter, u0, u1, 0, a0, a1, l0, l1])
+        })
+        .collect::<Vec<_>>();
+    Key::from_parts(&parts).unwrap_or_else(|_| unreachable!("Two or three parts"))
+}
+
+/// Versions A and C encrypt with the first eight header bytes as the IV.
+fn header_iv(header: &[u8]) -> u64 {
+    let [b0, b1, b2, b3, b4, b5, b6, b7, ..] = *header else {
+        unreachable!("Header is at least 16 bytes")
+    };
+    u64::from_be_bytes([b0, b1, b2, b3, b4, b5, b6, b7])
+}
+
+fn decimal(s: &str) -> Option<usize> {
+    s.bytes()
+        .all(|byte| byte.is_ascii_digit())
+        .then(|| s.parse().ok())
+        .flatten()
+}
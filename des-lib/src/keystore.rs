@@ -0,0 +1,573 @@
+//! Named DES and TDEA keys stored encrypted under a passphrase.
+//!
+//! A 3TDEA master key is derived from the passphrase with PBKDF2-HMAC-SHA256,
+//! and a 3TDEA encryption key and a 3TDEA CMAC key are derived from it with
+//! the SP 800-108 KDF used for sealing. Every stored key is
+//! encrypted in CBC mode under a random IV and authenticated together with
+//! its metadata, so entries cannot be altered or swapped between names
+//! without detection. A final CMAC over everything before it covers the
+//! file as a whole, so removing, adding or rolling back single entries is
+//! detected when the keystore is unlocked. Replacing the whole file with an
+//! older copy under the same passphrase is not. The metadata itself
+//! (algorithm, KCV, creation time and usage) is readable without the
+//! passphrase, but only authenticated once the keystore is unlocked.
+//!
+//! The file format is line based text:
+//!
+//! ```text
+//! des-keystore 1
+//! kdf pbkdf2-sha256 <iterations> <salt>
+//! verifier <tag>
+//! key <name> <algorithm> <usage> <created> <kcv> <iv> <ciphertext> <tag>
+//! mac <tag>
+//! ```
+//!
+//! Binary fields are upper-case hex and `created` is in seconds since the
+//! Unix epoch.
+
+use crate::{
+    blocks_to_bytes, bytes_to_blocks,
+    cmac::{cmac, constant_time_eq},
+    encoding::Encoding,
+    kdf::{Prf, derive_key},
+    key::{Key, KeyLength},
+    mode::{cbc_decrypt, cbc_encrypt},
+    seal::derive_subkey,
+    tdes::TripleDes,
+};
+use rand::Rng;
+use std::{
+    fmt::{self, Display},
+    str::FromStr,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use thiserror::Error;
+
+/// First line of every keystore file.
+const HEADER: &str = "des-keystore 1";
+
+/// Message authenticated to check the passphrase before any entry is read.
+const VERIFIER_MESSAGE: &[u8] = b"des-keystore verifier";
+
+const ENCRYPTION_LABEL: &[u8] = b"des-keystore encryption";
+const AUTHENTICATION_LABEL: &[u8] = b"des-keystore authentication";
+
+const SALT_LEN: usize = 16;
+
+/// Highest PBKDF2 iteration count accepted, so that a tampered file cannot
+/// make unlocking run for hours.
+pub const MAX_ITERATIONS: u32 = 10_000_000;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum KeystoreError {
+    #[error("Keystore line {0} is malformed")]
+    Format(usize),
+
+    #[error("Wrong keystore passphrase")]
+    WrongPassphrase,
+
+    #[error("Key name '{0}' is invalid; names must be non-empty without whitespace")]
+    InvalidName(String),
+
+    #[error("Key '{0}' already exists")]
+    DuplicateName(String),
+
+    #[error("Key '{0}' not found")]
+    NotFound(String),
+
+    #[error("Key '{0}' failed authentication; the keystore was modified")]
+    Tampered(String),
+
+    #[error("Keystore failed authentication; keys were added, removed or replaced")]
+    Modified,
+
+    #[error("Key '{name}' may not be used for {usage}")]
+    UsageNotPermitted { name: String, usage: KeyUsage },
+}
+
+/// What a stored key may be used for.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum KeyUsage {
+    /// Any purpose.
+    #[default]
+    General,
+    /// Data encryption and decryption.
+    Encryption,
+    /// Message authentication codes.
+    Mac,
+    /// Encryption of other keys.
+    KeyWrapping,
+    /// Encryption of PIN blocks.
+    PinEncryption,
+    /// Card verification values (CVV and CVC).
+    CardVerification,
+    /// EMV issuer master keys for application cryptograms.
+    ApplicationCryptogram,
+}
+
+impl KeyUsage {
+    const ALL: [Self; 7] = [
+        Self::General,
+        Self::Encryption,
+        Self::Mac,
+        Self::KeyWrapping,
+        Self::PinEncryption,
+        Self::CardVerification,
+        Self::ApplicationCryptogram,
+    ];
+
+    /// Whether a key with this usage may be used for `usage`.
+    #[must_use]
+    pub fn permits(self, usage: Self) -> bool {
+        self == Self::General || self == usage
+    }
+
+    const fn name(self) -> &'static str {
+        match self {
+            Self::General => "general",
+            Self::Encryption => "encryption",
+            Self::Mac => "mac",
+            Self::KeyWrapping => "key-wrapping",
+            Self::PinEncryption => "pin-encryption",
+            Self::CardVerification => "card-verification",
+            Self::ApplicationCryptogram => "application-cryptogram",
+        }
+    }
+}
+
+impl Display for KeyUsage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(self.name())
+    }
+}
+
+/// Keys derived from the keystore passphrase.
+#[derive(Clone)]
+pub struct MasterKey {
+    encryption: TripleDes,
+    authentication: TripleDes,
+}
+
+impl MasterKey {
+    fn derive(passphrase: &[u8], salt: &[u8], iterations: u32) -> Self {
+        let master = derive_key(
+            Prf::HmacSha256,
+            passphrase,
+            salt,
+            iterations,
+            KeyLength::Triple,
+        )
+        .cipher();
+        Self {
+            encryption: derive_subkey(&master, ENCRYPTION_LABEL, KeyLength::Triple),
+            authentication: derive_subkey(&master, AUTHENTICATION_LABEL, KeyLength::Triple),
+        }
+    }
+
+    fn verifier(&self) -> u64 {
+        cmac(&self.authentication, VERIFIER_MESSAGE)
+    }
+}
+
+impl fmt::Debug for MasterKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MasterKey").finish_non_exhaustive()
+    }
+}
+
+/// A stored key with its metadata.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    name: String,
+    length: KeyLength,
+    usage: KeyUsage,
+    created: u64,
+    kcv: [u8; 3],
+    iv: u64,
+    ciphertext: Vec<u64>,
+    tag: u64,
+}
+
+impl Entry {
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    #[must_use]
+    pub const fn length(&self) -> KeyLength {
+        self.length
+    }
+
+    #[must_use]
+    pub const fn usage(&self) -> KeyUsage {
+        self.usage
+    }
+
+    /// When the key was added to the keystore.
+    #[must_use]
+    pub fn created(&self) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(self.created)
+    }
+
+    /// Key check value recorded when the key was stored.
+    #[must_use]
+    pub const fn kcv(&self) -> [u8; 3] {
+        self.kcv
+    }
+
+    /// Check that the key may be used for `usage`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`KeystoreError::UsageNotPermitted`] otherwise.
+    pub fn check_usage(&self, usage: KeyUsage) -> Result<(), KeystoreError> {
+        if self.usage.permits(usage) {
+            Ok(())
+        } else {
+            Err(KeystoreError::UsageNotPermitted {
+                name: self.name.clone(),
+                usage,
+            })
+        }
+    }
+
+    /// Everything the tag covers: the metadata, IV and ciphertext.
+    fn authenticated_data(&self) -> Vec<u8> {
+        let mut data = format!(
+            "{} {} {} {} ",
+            self.name,
+            algorithm_name(self.length),
+            self.usage,
+            self.created
+        )
+        .into_bytes();
+        data.extend_from_slice(&self.kcv);
+        data.extend_from_slice(&self.iv.to_be_bytes());
+        data.extend(blocks_to_bytes(&self.ciphertext));
+        data
+    }
+}
+
+/// A set of named keys encrypted under a passphrase.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Keystore {
+    iterations: u32,
+    salt: [u8; SALT_LEN],
+    verifier: u64,
+    entries: Vec<Entry>,
+    tag: u64,
+}
+
+impl Keystore {
+    /// Create an empty keystore protected by `passphrase`, returning it with
+    /// its unlocked master key.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `iterations` is zero or above [`MAX_ITERATIONS`].
+    #[must_use]
+    pub fn create(passphrase: &[u8], iterations: u32) -> (Self, MasterKey) {
+        assert!(
+            (1..=MAX_ITERATIONS).contains(&iterations),
+            "Keystore iterations must be between 1 and {MAX_ITERATIONS}"
+        );
+        let salt: [u8; SALT_LEN] = rand::rng().random();
+        let master = MasterKey::derive(passphrase, &salt, iterations);
+        let mut keystore = Self {
+            iterations,
+            salt,
+            verifier: master.verifier(),
+            entries: Vec::new(),
+            tag: 0,
+        };
+        keystore.seal(&master);
+        (keystore, master)
+    }
+
+    /// Derive the master key from `passphrase` and authenticate the
+    /// keystore as a whole.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`KeystoreError::WrongPassphrase`] if the passphrase does not
+    /// match the one the keystore was created with, and
+    /// [`KeystoreError::Modified`] if the file fails authentication.
+    pub fn unlock(&self, passphrase: &[u8]) -> Result<MasterKey, KeystoreError> {
+        let master = MasterKey::derive(passphrase, &self.salt, self.iterations);
+        if !constant_time_eq(
+            &master.verifier().to_be_bytes(),
+            &self.verifier.to_be_bytes(),
+        ) {
+            return Err(KeystoreError::WrongPassphrase);
+        }
+        self.authenticate(&master)?;
+        Ok(master)
+    }
+
+    #[must_use]
+    pub fn entries(&self) -> &[Entry] {
+        &self.entries
+    }
+
+    /// Look up the metadata of a key.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`KeystoreError::NotFound`] if there is no key called `name`.
+    pub fn entry(&self, name: &str) -> Result<&Entry, KeystoreError> {
+        self.entries
+            .iter()
+            .find(|entry| entry.name == name)
+            .ok_or_else(|| KeystoreError::NotFound(name.into()))
+    }
+
+    /// Encrypt and store `key` under `name`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the name is invalid or already taken.
+    pub fn add(
+        &mut self,
+        master: &MasterKey,
+        name: &str,
+        key: &Key,
+        usage: KeyUsage,
+    ) -> Result<(), KeystoreError> {
+        if name.is_empty() || name.contains(char::is_whitespace) {
+            return Err(KeystoreError::InvalidName(name.into()));
+        }
+        if self.entry(name).is_ok() {
+            return Err(KeystoreError::DuplicateName(name.into()));
+        }
+
+        let iv = rand::random();
+        let mut ciphertext = key.parts().to_vec();
+        cbc_encrypt(&master.encryption, iv, &mut ciphertext);
+        let created = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs());
+        let mut entry = Entry {
+            name: name.into(),
+            length: key.length(),
+            usage,
+            created,
+            kcv: key.kcv(),
+            iv,
+            ciphertext,
+            tag: 0,
+        };
+        entry.tag = cmac(&master.authentication, &entry.authenticated_data());
+        self.entries.push(entry);
+        self.seal(master);
+        Ok(())
+    }
+
+    /// Authenticate and decrypt the key called `name`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`KeystoreError::NotFound`] for unknown names,
+    /// [`KeystoreError::Tampered`] if the entry fails authentication and
+    /// [`KeystoreError::Modified`] if the keystore as a whole does.
+    pub fn key(&self, master: &MasterKey, name: &str) -> Result<Key, KeystoreError> {
+        let entry = self.entry(name)?;
+        let tag = cmac(&master.authentication, &entry.authenticated_data());
+        if !constant_time_eq(&tag.to_be_bytes(), &entry.tag.to_be_bytes()) {
+            return Err(KeystoreError::Tampered(name.into()));
+        }
+        self.authenticate(master)?;
+
+        let mut parts = entry.ciphertext.clone();
+        cbc_decrypt(&master.encryption, entry.iv, &mut parts);
+        let key = Key::from_parts(&parts).map_err(|_| KeystoreError::Tampered(name.into()))?;
+        if key.kcv() != entry.kcv {
+            return Err(KeystoreError::Tampered(name.into()));
+        }
+        Ok(key)
+    }
+
+    /// Remove the key called `name`, returning its metadata.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`KeystoreError::NotFound`] if there is no such key.
+    pub fn remove(&mut self, master: &MasterKey, name: &str) -> Result<Entry, KeystoreError> {
+        let index = self
+            .entries
+            .iter()
+            .position(|entry| entry.name == name)
+            .ok_or_else(|| KeystoreError::NotFound(name.into()))?;
+        let entry = self.entries.remove(index);
+        self.seal(master);
+        Ok(entry)
+    }
+
+    /// Everything the final tag covers: all lines but the last.
+    fn body(&self) -> String {
+        let mut body = String::new();
+        self.write_body(&mut body)
+            .unwrap_or_else(|_| unreachable!("Writing to a String cannot fail"));
+        body
+    }
+
+    fn seal(&mut self, master: &MasterKey) {
+        self.tag = cmac(&master.authentication, self.body().as_bytes());
+    }
+
+    fn authenticate(&self, master: &MasterKey) -> Result<(), KeystoreError> {
+        let tag = cmac(&master.authentication, self.body().as_bytes());
+        if constant_time_eq(&tag.to_be_bytes(), &self.tag.to_be_bytes()) {
+            Ok(())
+        } else {
+            Err(KeystoreError::Modified)
+        }
+    }
+
+    fn write_body(&self, f: &mut impl fmt::Write) -> fmt::Result {
+        writeln!(f, "{HEADER}")?;
+        writeln!(
+            f,
+            "kdf pbkdf2-sha256 {} {}",
+            self.iterations,
+            Encoding::Hex.encode(&self.salt)
+        )?;
+        writeln!(f, "verifier {:016X}", self.verifier)?;
+        for entry in &self.entries {
+            writeln!(
+                f,
+                "key {} {} {} {} {} {:016X} {} {:016X}",
+                entry.name,
+                algorithm_name(entry.length),
+                entry.usage,
+                entry.created,
+                Encoding::Hex.encode(&entry.kcv),
+                entry.iv,
+                Encoding::Hex.encode(&blocks_to_bytes(&entry.ciphertext)),
+                entry.tag
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for Keystore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write_body(f)?;
+        writeln!(f, "mac {:016X}", self.tag)
+    }
+}
+
+impl FromStr for Keystore {
+    type Err = KeystoreError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut lines = s
+            .lines()
+            .enumerate()
+            .map(|(index, line)| (index + 1, line.split_whitespace().collect::<Vec<_>>()))
+            .filter(|(_, fields)| !fields.is_empty());
+
+        match lines.next() {
+            Some((_, fields)) if fields.join(" ") == HEADER => {}
+            Some((number, _)) => return Err(KeystoreError::Format(number)),
+            None => return Err(KeystoreError::Format(1)),
+        }
+        let (number, kdf) = lines.next().ok_or(KeystoreError::Format(2))?;
+        let (iterations, salt) = match kdf.as_slice() {
+            ["kdf", "pbkdf2-sha256", iterations, salt] => (
+                iterations
+                    .parse()
+                    .ok()
+                    .filter(|iterations| (1..=MAX_ITERATIONS).contains(iterations)),
+                Encoding::Hex
+                    .decode(salt)
+                    .ok()
+                    .and_then(|salt| salt.try_into().ok()),
+            ),
+            _ => (None, None),
+        };
+        let (Some(iterations), Some(salt)) = (iterations, salt) else {
+            return Err(KeystoreError::Format(number));
+        };
+        let (number, verifier) = lines.next().ok_or(KeystoreError::Format(3))?;
+        let verifier = match verifier.as_slice() {
+            ["verifier", tag] => u64::from_str_radix(tag, 16).ok(),
+            _ => None,
+        }
+        .ok_or(KeystoreError::Format(number))?;
+
+        let mut keystore = Self {
+            iterations,
+            salt,
+            verifier,
+            entries: Vec::new(),
+            tag: 0,
+        };
+        let mut last = number;
+        let mut tag = None;
+        for (number, fields) in lines {
+            last = number;
+            if tag.is_some() {
+                return Err(KeystoreError::Format(number));
+            }
+            if let ["mac", value] = fields.as_slice() {
+                tag = Some(
+                    u64::from_str_radix(value, 16).map_err(|_| KeystoreError::Format(number))?,
+                );
+                continue;
+            }
+            let entry = parse_entry(&fields).ok_or(KeystoreError::Format(number))?;
+            if keystore.entry(&entry.name).is_ok() {
+                return Err(KeystoreError::DuplicateName(entry.name));
+            }
+            keystore.entries.push(entry);
+        }
+        keystore.tag = tag.ok_or(KeystoreError::Format(last + 1))?;
+        Ok(keystore)
+    }
+}
+
+fn parse_entry(fields: &[&str]) -> Option<Entry> {
+    let [
+        "key",
+        name,
+        algorithm,
+        usage,
+        created,
+        kcv,
+        iv,
+        ciphertext,
+        tag,
+    ] = fields
+    else {
+        return None;
+    };
+    let length = [KeyLength::Single, KeyLength::Double, KeyLength::Triple]
+        .into_iter()
+        .find(|&length| algorithm_name(length) == *algorithm)?;
+    let ciphertext = Encoding::Hex.decode(ciphertext).ok()?;
+    if ciphertext.len() != length.bytes() {
+        return None;
+    }
+    Some(Entry {
+        name: (*name).into(),
+        length,
+        usage: KeyUsage::ALL
+            .into_iter()
+            .find(|candidate| candidate.name() == *usage)?,
+        created: created.parse().ok()?,
+        kcv: Encoding::Hex.decode(kcv).ok()?.try_into().ok()?,
+        iv: u64::from_str_radix(iv, 16).ok()?,
+        ciphertext: bytes_to_blocks(&ciphertext),
+        tag: u64::from_str_radix(tag, 16).ok()?,
+    })
+}
+
+const fn algorithm_name(length: KeyLength) -> &'static str {
+    match length {
+        KeyLength::Single => "des",
+        KeyLength::Double => "2tdea",
+        KeyLength::Triple => "3tdea",
+    }
+}
@@ -0,0 +1,134 @@
+//! ISO/IEC 9797-1 MAC algorithms 1 and 3 over DES.
+//!
+//! Algorithm 1 is the plain CBC-MAC of ANSI X9.9. Algorithm 3, the ANSI
+//! X9.19 "retail MAC", runs the CBC-MAC with single DES under the first key
+//! and finishes the last block with a decryption under the second key and an
+//! encryption under the first, so only one block pays for the TDEA strength.
+
+use crate::{BlockCipher, Des, cmac::constant_time_eq, key::Key, key::KeyLength};
+use thiserror::Error;
+
+/// Shortest MAC accepted by [`verify`]; ISO/IEC 9797-1 advises against
+/// anything shorter than 32 bits.
+pub const MIN_MAC_LENGTH: usize = 4;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum MacError {
+    #[error("The retail MAC needs a 16-byte key, got {0} bytes")]
+    RetailKeyLength(usize),
+
+    #[error("MAC must be between {MIN_MAC_LENGTH} and 8 bytes long, got {0}")]
+    InvalidLength(usize),
+
+    #[error("MAC does not match")]
+    Mismatch,
+}
+
+/// ISO/IEC 9797-1 MAC algorithm.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MacAlgorithm {
+    /// MAC algorithm 1: CBC-MAC (ANSI X9.9 with a single DES key).
+    #[default]
+    Cbc,
+    /// MAC algorithm 3: single DES CBC-MAC with a final TDEA step (ANSI
+    /// X9.19 retail MAC). Requires a double-length key.
+    Retail,
+}
+
+/// ISO/IEC 9797-1 padding method.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MacPadding {
+    /// Method 1: zero bytes up to a full block; an empty message becomes a
+    /// single zero block.
+    #[default]
+    Method1,
+    /// Method 2: a single `0x80` byte, then zero bytes up to a full block.
+    Method2,
+    /// Method 3: a block holding the message length in bits, followed by the
+    /// message padded with zero bytes.
+    Method3,
+}
+
+impl MacPadding {
+    /// Pad `message` into big-endian 64-bit blocks.
+    fn pad(self, message: &[u8]) -> Vec<u64> {
+        let mut blocks = Vec::with_capacity(message.len() / 8 + 2);
+        if self == Self::Method3 {
+            blocks.push((message.len() as u64).wrapping_mul(8));
+        }
+
+        let (chunks, tail) = message.as_chunks::<8>();
+        blocks.extend(chunks.iter().copied().map(u64::from_be_bytes));
+
+        let mut last = [0; 8];
+        last[..tail.len()].copy_from_slice(tail);
+        match self {
+            Self::Method1 if !tail.is_empty() || message.is_empty() => {}
+            Self::Method3 if !tail.is_empty() => {}
+            Self::Method2 => last[tail.len()] = 0x80,
+            Self::Method1 | Self::Method3 => return blocks,
+        }
+        blocks.push(u64::from_be_bytes(last));
+        blocks
+    }
+}
+
+/// Compute the full 64-bit MAC of `message`.
+///
+/// Algorithm 1 uses DES for single-length keys and TDEA otherwise.
+///
+/// # Errors
+///
+/// Returns [`MacError::RetailKeyLength`] if the retail MAC is used without
+/// a double-length key.
+pub fn mac(
+    key: &Key,
+    algorithm: MacAlgorithm,
+    padding: MacPadding,
+    message: &[u8],
+) -> Result<u64, MacError> {
+    let blocks = padding.pad(message);
+    match (algorithm, key.length()) {
+        (MacAlgorithm::Cbc, KeyLength::Single) => Ok(cbc_mac(&Des::new(key.parts()[0]), &blocks)),
+        (MacAlgorithm::Cbc, _) => Ok(cbc_mac(&key.cipher(), &blocks)),
+        (MacAlgorithm::Retail, KeyLength::Double) => {
+            let (first, second) = (Des::new(key.parts()[0]), Des::new(key.parts()[1]));
+            let state = cbc_mac(&first, &blocks);
+            Ok(first.encrypt(second.decrypt(state)))
+        }
+        (MacAlgorithm::Retail, length) => Err(MacError::RetailKeyLength(length.bytes())),
+    }
+}
+
+/// Verify a MAC truncated to its leftmost `expected.len()` bytes, comparing
+/// in constant time.
+///
+/// # Errors
+///
+/// Returns [`MacError::InvalidLength`] for MACs shorter than
+/// [`MIN_MAC_LENGTH`] or longer than a block, and [`MacError::Mismatch`] if
+/// the MAC is wrong.
+pub fn verify(
+    key: &Key,
+    algorithm: MacAlgorithm,
+    padding: MacPadding,
+    message: &[u8],
+    expected: &[u8],
+) -> Result<(), MacError> {
+    if !(MIN_MAC_LENGTH..=8).contains(&expected.len()) {
+        return Err(MacError::InvalidLength(expected.len()));
+    }
+    let computed = mac(key, algorithm, padding, message)?.to_be_bytes();
+    if constant_time_eq(&computed[..expected.len()], expected) {
+        Ok(())
+    } else {
+        Err(MacError::Mismatch)
+    }
+}
+
+/// CBC-MAC with a zero IV: the last ciphertext block.
+fn cbc_mac<C: BlockCipher>(cipher: &C, blocks: &[u64]) -> u64 {
+    blocks
+        .iter()
+        .fold(0, |state, &block| cipher.encrypt_block(state ^ block))
+}
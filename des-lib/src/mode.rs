@@ -0,0 +1,194 @@
+//! Bulk modes of operation over slices of 64-bit blocks.
+//!
+//! The serial functions process blocks in place. With the `parallel` feature
+//! enabled, the `par_*` variants split the buffer into fixed-size chunks and
+//! process them on the rayon thread pool. Chunking never affects the output:
+//! the parallel functions are bit-identical to their serial counterparts.
+//! [`Mode`] picks the parallel variants automatically when they are available.
+
+use crate::BlockCipher;
+
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+#[cfg(not(feature = "parallel"))]
+use self::{
+    cbc_decrypt as bulk_cbc_decrypt, ctr_apply as bulk_ctr_apply, ecb_decrypt as bulk_ecb_decrypt,
+    ecb_encrypt as bulk_ecb_encrypt,
+};
+#[cfg(feature = "parallel")]
+use self::{
+    par_cbc_decrypt as bulk_cbc_decrypt, par_ctr_apply as bulk_ctr_apply,
+    par_ecb_decrypt as bulk_ecb_decrypt, par_ecb_encrypt as bulk_ecb_encrypt,
+};
+
+/// Number of blocks handed to a single worker (32 KiB of data).
+#[cfg(feature = "parallel")]
+pub const PARALLEL_CHUNK_BLOCKS: usize = 4096;
+
+/// Number of blocks buffered per batch in CBC decryption and CTR mode.
+const CTR_BATCH_BLOCKS: usize = 64;
+
+/// Encrypt every block independently (ECB).
+pub fn ecb_encrypt<C: BlockCipher>(cipher: &C, blocks: &mut [u64]) {
+    cipher.encrypt_blocks(blocks);
+}
+
+/// Decrypt every block independently (ECB).
+pub fn ecb_decrypt<C: BlockCipher>(cipher: &C, blocks: &mut [u64]) {
+    cipher.decrypt_blocks(blocks);
+}
+
+/// Encrypt the blocks in CBC mode, returning the IV for the next call.
+pub fn cbc_encrypt<C: BlockCipher>(cipher: &C, iv: u64, blocks: &mut [u64]) -> u64 {
+    blocks.iter_mut().fold(iv, |previous, block| {
+        *block = cipher.encrypt_block(*block ^ previous);
+        *block
+    })
+}
+
+/// Decrypt the blocks in CBC mode, returning the IV for the next call.
+pub fn cbc_decrypt<C: BlockCipher>(cipher: &C, iv: u64, blocks: &mut [u64]) -> u64 {
+    let Some(&next_iv) = blocks.last() else {
+        return iv;
+    };
+
+    let mut previous = iv;
+    for chunk in blocks.chunks_mut(CTR_BATCH_BLOCKS) {
+        let mut ciphertext = [0; CTR_BATCH_BLOCKS];
+        ciphertext[..chunk.len()].copy_from_slice(chunk);
+
+        cipher.decrypt_blocks(chunk);
+        for (block, &cipher_block) in chunk.iter_mut().zip(ciphertext.iter()) {
+            *block ^= previous;
+            previous = cipher_block;
+        }
+    }
+
+    next_iv
+}
+
+/// XOR the blocks with the CTR keystream starting at `counter`, returning the
+/// counter for the next call.
+///
+/// The counter block for the `n`-th block is `counter + n` (wrapping), so
+/// encryption and decryption are the same operation.
+pub fn ctr_apply<C: BlockCipher>(cipher: &C, counter: u64, blocks: &mut [u64]) -> u64 {
+    let mut counter = counter;
+    let mut keystream = [0; CTR_BATCH_BLOCKS];
+
+    for chunk in blocks.chunks_mut(CTR_BATCH_BLOCKS) {
+        let keystream = &mut keystream[..chunk.len()];
+        for slot in keystream.iter_mut() {
+            *slot = counter;
+            counter = counter.wrapping_add(1);
+        }
+        cipher.encrypt_blocks(keystream);
+
+        for (block, key) in chunk.iter_mut().zip(keystream.iter()) {
+            *block ^= key;
+        }
+    }
+
+    counter
+}
+
+/// A mode of operation together with its running state.
+///
+/// The IV or counter advances as blocks are processed, so consecutive calls
+/// continue the same stream.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    /// Electronic codebook.
+    Ecb,
+    /// Cipher block chaining with the given IV.
+    Cbc { iv: u64 },
+    /// Counter mode starting at the given counter block.
+    Ctr { counter: u64 },
+}
+
+impl Mode {
+    /// Whether the mode turns the cipher into a stream cipher, allowing a
+    /// partial final block without padding.
+    #[must_use]
+    pub const fn is_streaming(&self) -> bool {
+        matches!(self, Self::Ctr { .. })
+    }
+
+    /// Encrypt the blocks in place, advancing the mode state.
+    pub fn encrypt<C: BlockCipher>(&mut self, cipher: &C, blocks: &mut [u64]) {
+        match self {
+            Self::Ecb => bulk_ecb_encrypt(cipher, blocks),
+            Self::Cbc { iv } => *iv = cbc_encrypt(cipher, *iv, blocks),
+            Self::Ctr { counter } => *counter = bulk_ctr_apply(cipher, *counter, blocks),
+        }
+    }
+
+    /// Decrypt the blocks in place, advancing the mode state.
+    pub fn decrypt<C: BlockCipher>(&mut self, cipher: &C, blocks: &mut [u64]) {
+        match self {
+            Self::Ecb => bulk_ecb_decrypt(cipher, blocks),
+            Self::Cbc { iv } => *iv = bulk_cbc_decrypt(cipher, *iv, blocks),
+            Self::Ctr { counter } => *counter = bulk_ctr_apply(cipher, *counter, blocks),
+        }
+    }
+}
+
+/// Parallel [`ecb_encrypt`].
+#[cfg(feature = "parallel")]
+pub fn par_ecb_encrypt<C: BlockCipher>(cipher: &C, blocks: &mut [u64]) {
+    blocks
+        .par_chunks_mut(PARALLEL_CHUNK_BLOCKS)
+        .for_each(|chunk| ecb_encrypt(cipher, chunk));
+}
+
+/// Parallel [`ecb_decrypt`].
+#[cfg(feature = "parallel")]
+pub fn par_ecb_decrypt<C: BlockCipher>(cipher: &C, blocks: &mut [u64]) {
+    blocks
+        .par_chunks_mut(PARALLEL_CHUNK_BLOCKS)
+        .for_each(|chunk| ecb_decrypt(cipher, chunk));
+}
+
+/// Parallel [`cbc_decrypt`].
+///
+/// Each chunk is chained from the last ciphertext block of the chunk before.
+#[cfg(feature = "parallel")]
+pub fn par_cbc_decrypt<C: BlockCipher>(cipher: &C, iv: u64, blocks: &mut [u64]) -> u64 {
+    let Some(&next_iv) = blocks.last() else {
+        return iv;
+    };
+
+    let chunk_ivs = std::iter::once(iv)
+        .chain(
+            blocks
+                .chunks(PARALLEL_CHUNK_BLOCKS)
+                .filter_map(|chunk| chunk.last().copied()),
+        )
+        .collect::<Vec<_>>();
+
+    blocks
+        .par_chunks_mut(PARALLEL_CHUNK_BLOCKS)
+        .zip(chunk_ivs)
+        .for_each(|(chunk, iv)| {
+            cbc_decrypt(cipher, iv, chunk);
+        });
+
+    next_iv
+}
+
+/// Parallel [`ctr_apply`].
+///
+/// Each chunk starts from the counter value it would have reached serially.
+#[cfg(feature = "parallel")]
+pub fn par_ctr_apply<C: BlockCipher>(cipher: &C, counter: u64, blocks: &mut [u64]) -> u64 {
+    blocks
+        .par_chunks_mut(PARALLEL_CHUNK_BLOCKS)
+        .enumerate()
+        .for_each(|(idx, chunk)| {
+            let offset = (idx * PARALLEL_CHUNK_BLOCKS) as u64;
+            ctr_apply(cipher, counter.wrapping_add(offset), chunk);
+        });
+
+    counter.wrapping_add(blocks.len() as u64)
+}
@@ -0,0 +1,91 @@
+//! Block padding schemes.
+
+use crate::BLOCK_SIZE;
+use thiserror::Error;
+
+#[derive(Debug, Clone, Copy, Error, PartialEq, Eq)]
+pub enum PaddingError {
+    #[error("Padded data must be a non-empty multiple of {BLOCK_SIZE} bytes")]
+    InvalidLength,
+
+    #[error("Padding bytes are malformed")]
+    InvalidPadding,
+}
+
+/// How the final partial block is filled up to [`BLOCK_SIZE`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Padding {
+    /// PKCS#7: `n` bytes of value `n`; a full block is added to aligned data.
+    #[default]
+    Pkcs7,
+    /// ISO/IEC 7816-4 (ISO/IEC 9797-1 method 2): `0x80` followed by zeros.
+    Iso7816,
+    /// No padding; the data must already be block-aligned.
+    None,
+}
+
+impl Padding {
+    /// Append padding so that `data.len()` becomes a multiple of [`BLOCK_SIZE`].
+    ///
+    /// [`Padding::None`] leaves the data untouched.
+    pub fn pad(self, data: &mut Vec<u8>) {
+        let remainder = data.len() % BLOCK_SIZE;
+        match self {
+            Self::Pkcs7 => pkcs7_pad(data, BLOCK_SIZE - remainder),
+            Self::Iso7816 => {
+                data.push(0x80);
+                data.resize(data.len().next_multiple_of(BLOCK_SIZE), 0);
+            }
+            Self::None => {}
+        }
+    }
+
+    /// Strip the padding from decrypted, block-aligned data.
+    ///
+    /// [`Padding::None`] returns the data as is, whatever its length.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`PaddingError`] if the data is not block-aligned or the
+    /// padding bytes do not match the scheme.
+    pub fn unpad(self, data: &[u8]) -> Result<&[u8], PaddingError> {
+        if self != Self::None && !data.len().is_multiple_of(BLOCK_SIZE) {
+            return Err(PaddingError::InvalidLength);
+        }
+
+        match self {
+            Self::Pkcs7 => {
+                let &last = data.last().ok_or(PaddingError::InvalidLength)?;
+                let count = usize::from(last);
+                if count == 0 || count > BLOCK_SIZE {
+                    return Err(PaddingError::InvalidPadding);
+                }
+                let (content, padding) = data.split_at(data.len() - count);
+                if padding.iter().any(|&byte| byte != last) {
+                    return Err(PaddingError::InvalidPadding);
+                }
+                Ok(content)
+            }
+            Self::Iso7816 => {
+                if data.is_empty() {
+                    return Err(PaddingError::InvalidLength);
+                }
+                let tail = &data[data.len() - BLOCK_SIZE..];
+                let marker = tail
+                    .iter()
+                    .rposition(|&byte| byte != 0)
+                    .ok_or(PaddingError::InvalidPadding)?;
+                if tail[marker] != 0x80 {
+                    return Err(PaddingError::InvalidPadding);
+                }
+                Ok(&data[..data.len() - BLOCK_SIZE + marker])
+            }
+            Self::None => Ok(data),
+        }
+    }
+}
+
+fn pkcs7_pad(data: &mut Vec<u8>, count: usize) {
+    let byte = u8::try_from(count).expect("Padding length fits in a byte");
+    data.resize(data.len() + count, byte);
+}
@@ -0,0 +1,301 @@
+//! ISO 9564-1 PIN blocks.
+//!
+//! Formats 0 to 3 pack a PIN of 4 to 12 digits into a 64-bit block that is
+//! encrypted with DES or TDEA under a PIN encryption key:
+//!
+//! | Format | Fill digits       | Combined with the PAN |
+//! |--------|-------------------|-----------------------|
+//! | 0      | `F`               | yes                   |
+//! | 1      | transaction field | no                    |
+//! | 2      | `F`               | no (ICC only)         |
+//! | 3      | random `A`-`F`    | yes                   |
+//!
+//! Format 4 is a 128-bit block encrypted with AES, so only its plaintext PIN
+//! and PAN fields are built and parsed here.
+
+use crate::{BlockCipher, key::Key};
+use std::fmt::{self, Display};
+use thiserror::Error;
+
+/// Shortest PIN allowed by ISO 9564-1.
+pub const MIN_PIN_LENGTH: usize = 4;
+
+/// Longest PIN allowed by ISO 9564-1.
+pub const MAX_PIN_LENGTH: usize = 12;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum PinError {
+    #[error("PIN must be {MIN_PIN_LENGTH} to {MAX_PIN_LENGTH} decimal digits")]
+    InvalidPin,
+
+    #[error("PAN must be 12 to 19 decimal digits")]
+    InvalidPan,
+
+    #[error("{0} PIN blocks require the PAN")]
+    PanRequired(PinFormat),
+
+    #[error("Not a valid PIN block; wrong key or PAN?")]
+    InvalidBlock,
+}
+
+/// ISO 9564-1 PIN block format for 64-bit block ciphers.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PinFormat {
+    /// Format 0 (ANSI X9.8): PIN padded with `F`, combined with the PAN.
+    #[default]
+    Iso0,
+    /// Format 1: PIN followed by a transaction field, without the PAN.
+    Iso1,
+    /// Format 2: PIN padded with `F`, for offline use between card and
+    /// terminal.
+    Iso2,
+    /// Format 3: PIN padded with random digits `A` to `F`, combined with the
+    /// PAN.
+    Iso3,
+}
+
+impl PinFormat {
+    const fn control(self) -> u8 {
+        match self {
+            Self::Iso0 => 0,
+            Self::Iso1 => 1,
+            Self::Iso2 => 2,
+            Self::Iso3 => 3,
+        }
+    }
+
+    /// Whether the PIN field is combined with the PAN.
+    #[must_use]
+    pub const fn uses_pan(self) -> bool {
+        matches!(self, Self::Iso0 | Self::Iso3)
+    }
+
+    /// Fill digit made from the `random` nibble.
+    const fn fill(self, random: u8) -> u8 {
+        match self {
+            Self::Iso0 | Self::Iso2 => 0xF,
+            Self::Iso1 => random,
+            Self::Iso3 => 0xA + random % 6,
+        }
+    }
+
+    const fn accepts_fill(self, nibble: u8) -> bool {
+        match self {
+            Self::Iso0 | Self::Iso2 => nibble == 0xF,
+            Self::Iso1 => true,
+            Self::Iso3 => nibble >= 0xA,
+        }
+    }
+}
+
+impl Display for PinFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ISO format {}", self.control())
+    }
+}
+
+/// Build a plaintext PIN block. `fill` supplies the fill digits of formats 1
+/// and 3 and must be unpredictable for them; the other formats ignore it.
+///
+/// # Errors
+///
+/// Returns an error for an invalid PIN or PAN, or if the format requires a
+/// PAN and none is given.
+pub fn build(format: PinFormat, pin: &str, pan: Option<&str>, fill: u64) -> Result<u64, PinError> {
+    let digits = pin_digits(pin)?;
+    let random = unpack(&fill.to_be_bytes());
+    let mut nibbles = vec![format.control(), length_nibble(&digits)];
+    nibbles.extend_from_slice(&digits);
+    nibbles.extend(
+        random[nibbles.len()..]
+            .iter()
+            .map(|&nibble| format.fill(nibble)),
+    );
+    let field = u64::from_be_bytes(pack(&nibbles));
+
+    if format.uses_pan() {
+        let pan = pan.ok_or(PinError::PanRequired(format))?;
+        Ok(field ^ pan_field(pan)?)
+    } else {
+        Ok(field)
+    }
+}
+
+/// Parse a plaintext PIN block, returning its format and the PIN.
+///
+/// # Errors
+///
+/// Returns [`PinError::InvalidBlock`] if the block is not well formed, which
+/// is also how a wrong key or PAN shows, and [`PinError::PanRequired`] if the
+/// format requires a PAN and none is given.
+pub fn parse(block: u64, pan: Option<&str>) -> Result<(PinFormat, String), PinError> {
+    let format = match block >> 60 {
+        0 => PinFormat::Iso0,
+        1 => PinFormat::Iso1,
+        2 => PinFormat::Iso2,
+        3 => PinFormat::Iso3,
+        _ => return Err(PinError::InvalidBlock),
+    };
+    let field = if format.uses_pan() {
+        block ^ pan_field(pan.ok_or(PinError::PanRequired(format))?)?
+    } else {
+        block
+    };
+
+    let nibbles = unpack(&field.to_be_bytes());
+    let (pin, fill) = split_pin(&nibbles[1..])?;
+    if !fill.iter().all(|&nibble| format.accepts_fill(nibble)) {
+        return Err(PinError::InvalidBlock);
+    }
+    Ok((format, pin))
+}
+
+/// Build a PIN block and encrypt it under a PIN encryption key, with random
+/// fill digits.
+///
+/// # Errors
+///
+/// Returns the errors of [`build`].
+#[cfg(feature = "rand")]
+pub fn encrypt(
+    key: &Key,
+    format: PinFormat,
+    pin: &str,
+    pan: Option<&str>,
+) -> Result<u64, PinError> {
+    let block = build(format, pin, pan, rand::random())?;
+    Ok(key.cipher().encrypt_block(block))
+}
+
+/// Decrypt a PIN block, returning its format and the PIN.
+///
+/// # Errors
+///
+/// Returns the errors of [`parse`].
+pub fn decrypt(
+    key: &Key,
+    encrypted: u64,
+    pan: Option<&str>,
+) -> Result<(PinFormat, String), PinError> {
+    parse(key.cipher().decrypt_block(encrypted), pan)
+}
+
+/// Re-encrypt a PIN block from one key to another, changing it to `format`
+/// if given and keeping its format otherwise.
+///
+/// # Errors
+///
+/// Returns an error if the block does not decrypt to a valid PIN block, or
+/// if a format that needs the PAN is involved and none is given.
+#[cfg(feature = "rand")]
+pub fn translate(
+    encrypted: u64,
+    pan: Option<&str>,
+    from: &Key,
+    to: &Key,
+    format: Option<PinFormat>,
+) -> Result<u64, PinError> {
+    let (original, pin) = decrypt(from, encrypted, pan)?;
+    encrypt(to, format.unwrap_or(original), &pin, pan)
+}
+
+/// Build the plaintext PIN field and PAN field of a format 4 PIN block.
+/// `fill` supplies the random second half of the PIN field.
+///
+/// # Errors
+///
+/// Returns an error for an invalid PIN or PAN.
+pub fn build_format4(pin: &str, pan: &str, fill: u64) -> Result<(u128, u128), PinError> {
+    let digits = pin_digits(pin)?;
+    let mut nibbles = vec![4, length_nibble(&digits)];
+    nibbles.extend_from_slice(&digits);
+    nibbles.resize(16, 0xA);
+    nibbles.extend(unpack(&fill.to_be_bytes()));
+    let pin_field = u128::from_be_bytes(pack(&nibbles));
+
+    let pan = pan_digits(pan)?;
+    // The first nibble counts the digits beyond 12.
+    let mut nibbles = vec![length_nibble(&pan[12..])];
+    nibbles.extend_from_slice(&pan);
+    nibbles.resize(32, 0);
+    Ok((pin_field, u128::from_be_bytes(pack(&nibbles))))
+}
+
+/// Parse the plaintext PIN field of a format 4 PIN block.
+///
+/// # Errors
+///
+/// Returns [`PinError::InvalidBlock`] if the field is not well formed.
+pub fn parse_format4(pin_field: u128) -> Result<String, PinError> {
+    let nibbles = unpack(&pin_field.to_be_bytes());
+    if nibbles[0] != 4 {
+        return Err(PinError::InvalidBlock);
+    }
+    let (pin, fill) = split_pin(&nibbles[1..16])?;
+    if fill.iter().any(|&nibble| nibble != 0xA) {
+        return Err(PinError::InvalidBlock);
+    }
+    Ok(pin)
+}
+
+/// The PAN field of formats 0 and 3: the rightmost 12 digits of the PAN
+/// without its check digit.
+fn pan_field(pan: &str) -> Result<u64, PinError> {
+    let mut digits = pan_digits(pan)?;
+    if digits.len() < 13 {
+        digits.insert(0, 0);
+    }
+    let account = &digits[digits.len() - 13..digits.len() - 1];
+    let mut nibbles = vec![0; 4];
+    nibbles.extend_from_slice(account);
+    Ok(u64::from_be_bytes(pack(&nibbles)))
+}
+
+pub(crate) fn pin_digits(pin: &str) -> Result<Vec<u8>, PinError> {
+    decimal_digits(pin, MIN_PIN_LENGTH..=MAX_PIN_LENGTH).ok_or(PinError::InvalidPin)
+}
+
+pub(crate) fn pan_digits(pan: &str) -> Result<Vec<u8>, PinError> {
+    decimal_digits(pan, 12..=19).ok_or(PinError::InvalidPan)
+}
+
+fn decimal_digits(s: &str, lengths: std::ops::RangeInclusive<usize>) -> Option<Vec<u8>> {
+    lengths.contains(&s.len()).then_some(())?;
+    s.bytes()
+        .map(|byte| byte.is_ascii_digit().then(|| byte - b'0'))
+        .collect()
+}
+
+/// The number of digits as a nibble; callers pass at most 12.
+fn length_nibble(digits: &[u8]) -> u8 {
+    u8::try_from(digits.len()).map_or(0xF, |length| length.min(0xF))
+}
+
+/// Split the length nibble, the PIN and the fill digits after the control
+/// nibble.
+fn split_pin(nibbles: &[u8]) -> Result<(String, &[u8]), PinError> {
+    let length = usize::from(nibbles[0]);
+    if !(MIN_PIN_LENGTH..=MAX_PIN_LENGTH).contains(&length) {
+        return Err(PinError::InvalidBlock);
+    }
+    let (digits, fill) = nibbles[1..].split_at(length);
+    let pin = digits
+        .iter()
+        .map(|&digit| char::from_digit(u32::from(digit), 10))
+        .collect::<Option<String>>()
+        .ok_or(PinError::InvalidBlock)?;
+    Ok((pin, fill))
+}
+
+/// Pack nibbles into bytes, high nibble first.
+fn pack<const N: usize>(nibbles: &[u8]) -> [u8; N] {
+    std::array::from_fn(|i| nibbles[2 * i] << 4 | nibbles[2 * i + 1])
+}
+
+/// Split bytes into nibbles, high nibble first.
+pub(crate) fn unpack(bytes: &[u8]) -> Vec<u8> {
+    bytes
+        .iter()
+        .flat_map(|byte| [byte >> 4, byte & 0xF])
+        .collect()
+}
@@ -0,0 +1,237 @@
+//! PIN verification with IBM 3624 offsets and Visa PIN verification values.
+//!
+//! Both schemes encrypt account data under a PIN verification key (PVK) and
+//! turn the hex result into decimal digits:
+//!
+//! - IBM 3624 encrypts issuer-chosen validation data, usually taken from the
+//!   PAN, and maps every nibble through a [`DecimalizationTable`] to get the
+//!   natural PIN. The offset stored by the issuer is the digit-wise difference
+//!   between the customer's PIN and the natural PIN, modulo 10.
+//! - Visa PVV encrypts the transformed security parameter: the 11 rightmost
+//!   PAN digits without the check digit, the PVK index and the 4 leftmost PIN
+//!   digits. The PVV is the first 4 decimal digits of the result, topped up
+//!   from its hex digits `A` to `F` minus 10.
+
+use crate::{
+    BlockCipher,
+    cmac::constant_time_eq,
+    key::Key,
+    pin::{MAX_PIN_LENGTH, MIN_PIN_LENGTH, PinError, pan_digits, pin_digits, unpack},
+};
+use std::{fmt, str::FromStr};
+use thiserror::Error;
+
+/// Number of digits in a Visa PVV.
+pub const PVV_LENGTH: usize = 4;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum PinVerificationError {
+    #[error(transparent)]
+    Pin(#[from] PinError),
+
+    #[error("Decimalization table must be 16 decimal digits")]
+    InvalidTable,
+
+    #[error("Offset must be as many decimal digits as the PIN")]
+    InvalidOffset,
+
+    #[error("PVV must be {PVV_LENGTH} decimal digits")]
+    InvalidPvv,
+
+    #[error("PVK index must be a single digit, not {0}")]
+    InvalidPvki(u8),
+
+    #[error("PIN does not match")]
+    Mismatch,
+}
+
+/// Maps each hex digit to a decimal digit.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct DecimalizationTable([u8; 16]);
+
+impl DecimalizationTable {
+    /// The usual table, `0123456789012345`, mapping `A` to `F` to 0 to 5.
+    pub const STANDARD: Self = Self([0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 0, 1, 2, 3, 4, 5]);
+
+    fn decimalize(self, nibble: u8) -> u8 {
+        self.0[usize::from(nibble)]
+    }
+}
+
+impl Default for DecimalizationTable {
+    fn default() -> Self {
+        Self::STANDARD
+    }
+}
+
+impl FromStr for DecimalizationTable {
+    type Err = PinVerificationError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let digits = decimal_digits(s).ok_or(PinVerificationError::InvalidTable)?;
+        digits
+            .try_into()
+            .map(Self)
+            .map_err(|_| PinVerificationError::InvalidTable)
+    }
+}
+
+impl fmt::Debug for DecimalizationTable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("DecimalizationTable")
+            .field(&digits_to_string(&self.0))
+            .finish()
+    }
+}
+
+/// The IBM 3624 natural PIN of `length` digits for `validation_data`.
+///
+/// # Errors
+///
+/// Returns an error if `length` is not a valid PIN length.
+pub fn natural_pin(
+    key: &Key,
+    validation_data: u64,
+    table: DecimalizationTable,
+    length: usize,
+) -> Result<String, PinVerificationError> {
+    if !(MIN_PIN_LENGTH..=MAX_PIN_LENGTH).contains(&length) {
+        return Err(PinError::InvalidPin.into());
+    }
+    Ok(digits_to_string(&natural_digits(
+        key,
+        validation_data,
+        table,
+        length,
+    )))
+}
+
+/// The IBM 3624 offset that turns the natural PIN into `pin`.
+///
+/// # Errors
+///
+/// Returns an error for an invalid PIN.
+pub fn ibm3624_offset(
+    key: &Key,
+    validation_data: u64,
+    table: DecimalizationTable,
+    pin: &str,
+) -> Result<String, PinVerificationError> {
+    let pin = pin_digits(pin)?;
+    let natural = natural_digits(key, validation_data, table, pin.len());
+    let offset = pin
+        .iter()
+        .zip(natural)
+        .map(|(digit, natural)| (digit + 10 - natural) % 10)
+        .collect::<Vec<_>>();
+    Ok(digits_to_string(&offset))
+}
+
+/// Verify `pin` against an IBM 3624 offset, comparing in constant time.
+///
+/// # Errors
+///
+/// Returns [`PinVerificationError::Mismatch`] if the PIN is wrong, and other
+/// errors for an invalid PIN or offset.
+pub fn verify_ibm3624_offset(
+    key: &Key,
+    validation_data: u64,
+    table: DecimalizationTable,
+    pin: &str,
+    offset: &str,
+) -> Result<(), PinVerificationError> {
+    let expected = ibm3624_offset(key, validation_data, table, pin)?;
+    if decimal_digits(offset).is_none() || offset.len() != expected.len() {
+        return Err(PinVerificationError::InvalidOffset);
+    }
+    matches(&expected, offset)
+}
+
+/// The Visa PVV for `pin` with PVK index `pvki`.
+///
+/// # Errors
+///
+/// Returns an error for an invalid PIN, PAN or PVK index.
+pub fn pvv(key: &Key, pan: &str, pvki: u8, pin: &str) -> Result<String, PinVerificationError> {
+    let pin = pin_digits(pin)?;
+    let pan = pan_digits(pan)?;
+    if pvki > 9 {
+        return Err(PinVerificationError::InvalidPvki(pvki));
+    }
+
+    let account = &pan[pan.len() - 12..pan.len() - 1];
+    let tsp = account
+        .iter()
+        .chain(&[pvki])
+        .chain(&pin[..PVV_LENGTH])
+        .fold(0, |block, &digit| block << 4 | u64::from(digit));
+    Ok(extract_digits(key.cipher().encrypt_block(tsp), PVV_LENGTH))
+}
+
+/// Verify `pin` against a Visa PVV, comparing in constant time.
+///
+/// # Errors
+///
+/// Returns [`PinVerificationError::Mismatch`] if the PIN is wrong, and other
+/// errors for an invalid PIN, PAN, PVK index or PVV.
+pub fn verify_pvv(
+    key: &Key,
+    pan: &str,
+    pvki: u8,
+    pin: &str,
+    expected: &str,
+) -> Result<(), PinVerificationError> {
+    if decimal_digits(expected).is_none() || expected.len() != PVV_LENGTH {
+        return Err(PinVerificationError::InvalidPvv);
+    }
+    matches(&pvv(key, pan, pvki, pin)?, expected)
+}
+
+/// The first `count` decimal digits of `block`, topped up from its hex digits
+/// `A` to `F` minus 10, as in Visa PVV and CVV.
+pub(crate) fn extract_digits(block: u64, count: usize) -> String {
+    let nibbles = unpack(&block.to_be_bytes());
+    let digits = nibbles
+        .iter()
+        .filter(|&&nibble| nibble < 10)
+        .chain(nibbles.iter().filter(|&&nibble| nibble >= 10))
+        .map(|nibble| nibble % 10)
+        .take(count)
+        .collect::<Vec<_>>();
+    digits_to_string(&digits)
+}
+
+fn natural_digits(
+    key: &Key,
+    validation_data: u64,
+    table: DecimalizationTable,
+    length: usize,
+) -> Vec<u8> {
+    let encrypted = key.cipher().encrypt_block(validation_data);
+    unpack(&encrypted.to_be_bytes())
+        .into_iter()
+        .take(length)
+        .map(|nibble| table.decimalize(nibble))
+        .collect()
+}
+
+fn matches(computed: &str, expected: &str) -> Result<(), PinVerificationError> {
+    if constant_time_eq(computed.as_bytes(), expected.as_bytes()) {
+        Ok(())
+    } else {
+        Err(PinVerificationError::Mismatch)
+    }
+}
+
+pub(crate) fn decimal_digits(s: &str) -> Option<Vec<u8>> {
+    s.bytes()
+        .map(|byte| byte.is_ascii_digit().then(|| byte - b'0'))
+        .collect()
+}
+
+fn digits_to_string(digits: &[u8]) -> String {
+    digits
+        .iter()
+        .map(|&digit| char::from(b'0' + digit))
+        .collect()
+}
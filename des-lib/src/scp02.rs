@@ -0,0 +1,431 @@
+//! Secure Channel Protocol 02 for smart card management (GP Card
+//! Specification 2.2, appendix E).
+//!
+//! The host sends INITIALIZE UPDATE with a random host challenge. The card
+//! answers with its sequence counter, a card challenge and a card cryptogram.
+//! Both sides derive the session keys from the static keys and the sequence
+//! counter, and the host proves that it holds the keys with the host
+//! cryptogram in EXTERNAL AUTHENTICATE.
+//!
+//! From then on every command carries a C-MAC: a retail MAC (ISO/IEC 9797-1
+//! algorithm 3) over the command with its class byte marked as secure
+//! messaging and Lc counting the MAC. The MAC of the previous command,
+//! encrypted with single DES under the first half of the C-MAC key, is the
+//! ICV of the next one. With [`SecurityLevel::MacAndEncryption`] the command
+//! data is also encrypted under S-ENC after the MAC is computed.
+
+use crate::{
+    Des, blocks_to_bytes, bytes_to_blocks,
+    cmac::constant_time_eq,
+    key::{Key, KeyLength},
+    mac::{self, MacAlgorithm, MacPadding},
+    mode,
+    padding::Padding,
+};
+use thiserror::Error;
+
+/// The secure channel protocol identifier reported by SCP02 cards.
+pub const SCP02: u8 = 0x02;
+
+/// Length of the INITIALIZE UPDATE response data.
+pub const INITIALIZE_UPDATE_RESPONSE_LEN: usize = 28;
+
+/// Length of a C-MAC.
+const MAC_LEN: usize = 8;
+
+/// Class byte bit marking a command as protected by secure messaging.
+const SECURE_MESSAGING: u8 = 0x04;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum Scp02Error {
+    #[error("SCP02 keys must be 16 bytes, got {0} bytes")]
+    KeyLength(usize),
+
+    #[error(
+        "INITIALIZE UPDATE response data must be {INITIALIZE_UPDATE_RESPONSE_LEN} bytes, got {0}"
+    )]
+    ResponseLength(usize),
+
+    #[error("The card uses secure channel protocol {0:#04X}, not SCP02")]
+    UnsupportedProtocol(u8),
+
+    #[error("Card cryptogram does not match: the card holds different keys")]
+    CardCryptogram,
+
+    #[error("Malformed command APDU: {0}")]
+    InvalidCommand(&'static str),
+
+    #[error("Wrapped command data must be at most 255 bytes, got {0}")]
+    CommandTooLong(usize),
+}
+
+/// The static ENC, MAC and DEK keys of a security domain.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StaticKeys {
+    pub enc: Key,
+    pub mac: Key,
+    pub dek: Key,
+}
+
+impl StaticKeys {
+    /// # Errors
+    ///
+    /// Returns [`Scp02Error::KeyLength`] unless every key is a 2TDEA key.
+    pub fn new(enc: Key, mac: Key, dek: Key) -> Result<Self, Scp02Error> {
+        for key in [&enc, &mac, &dek] {
+            check_key(key)?;
+        }
+        Ok(Self { enc, mac, dek })
+    }
+
+    /// Use the same key for all three, as test cards often do.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Scp02Error::KeyLength`] unless `key` is a 2TDEA key.
+    pub fn single(key: &Key) -> Result<Self, Scp02Error> {
+        Self::new(key.clone(), key.clone(), key.clone())
+    }
+}
+
+/// Keys of one secure channel session.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SessionKeys {
+    /// S-ENC, for the cryptograms and command data encryption.
+    pub enc: Key,
+    /// C-MAC, for command MACs.
+    pub c_mac: Key,
+    /// R-MAC, for response MACs.
+    pub r_mac: Key,
+    /// DEK, for sensitive data such as keys sent with PUT KEY.
+    pub dek: Key,
+}
+
+impl SessionKeys {
+    /// Derive the session keys by encrypting a constant and the sequence
+    /// counter, padded with zeros to 16 bytes, under each static key in CBC
+    /// mode.
+    #[must_use]
+    pub fn derive(keys: &StaticKeys, sequence_counter: u16) -> Self {
+        let derive = |key: &Key, constant: u16| {
+            let mut blocks = [
+                u64::from(constant) << 48 | u64::from(sequence_counter) << 32,
+                0,
+            ];
+            mode::cbc_encrypt(&key.cipher(), 0, &mut blocks);
+            Key::double(blocks[0], blocks[1])
+        };
+        Self {
+            enc: derive(&keys.enc, 0x0182),
+            c_mac: derive(&keys.mac, 0x0101),
+            r_mac: derive(&keys.mac, 0x0102),
+            dek: derive(&keys.dek, 0x0181),
+        }
+    }
+}
+
+/// How commands are protected once the channel is open.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SecurityLevel {
+    /// Command MACs only.
+    #[default]
+    Mac,
+    /// Command MACs and command data encryption.
+    MacAndEncryption,
+}
+
+impl SecurityLevel {
+    /// The P1 byte of EXTERNAL AUTHENTICATE.
+    #[must_use]
+    pub const fn p1(self) -> u8 {
+        match self {
+            Self::Mac => 0x01,
+            Self::MacAndEncryption => 0x03,
+        }
+    }
+}
+
+/// The fields of the card's answer to INITIALIZE UPDATE.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InitializeUpdateResponse {
+    pub diversification_data: [u8; 10],
+    pub key_version: u8,
+    pub sequence_counter: u16,
+    pub card_challenge: [u8; 6],
+    pub card_cryptogram: u64,
+}
+
+impl InitializeUpdateResponse {
+    /// Parse the response data, without the status word.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the data has the wrong length or the card does not
+    /// use SCP02.
+    pub fn parse(data: &[u8]) -> Result<Self, Scp02Error> {
+        let data = <[u8; INITIALIZE_UPDATE_RESPONSE_LEN]>::try_from(data)
+            .map_err(|_| Scp02Error::ResponseLength(data.len()))?;
+        if data[11] != SCP02 {
+            return Err(Scp02Error::UnsupportedProtocol(data[11]));
+        }
+        Ok(Self {
+            diversification_data: field(&data, 0),
+            key_version: data[10],
+            sequence_counter: u16::from_be_bytes(field(&data, 12)),
+            card_challenge: field(&data, 14),
+            card_cryptogram: u64::from_be_bytes(field(&data, 20)),
+        })
+    }
+
+    /// Encode the response data, as a card would send it.
+    #[must_use]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        [
+            &self.diversification_data[..],
+            &[self.key_version, SCP02],
+            &self.sequence_counter.to_be_bytes(),
+            &self.card_challenge,
+            &self.card_cryptogram.to_be_bytes(),
+        ]
+        .concat()
+    }
+}
+
+/// The INITIALIZE UPDATE command for the keys with `key_version`, or the
+/// default keys for version 0.
+#[must_use]
+pub fn initialize_update(key_version: u8, host_challenge: u64) -> Vec<u8> {
+    [
+        &[0x80, 0x50, key_version, 0x00, 0x08][..],
+        &host_challenge.to_be_bytes(),
+        &[0x00],
+    ]
+    .concat()
+}
+
+/// The cryptogram with which the card proves that it holds the keys.
+#[must_use]
+pub fn card_cryptogram(
+    keys: &SessionKeys,
+    host_challenge: u64,
+    sequence_counter: u16,
+    card_challenge: [u8; 6],
+) -> u64 {
+    full_mac(
+        &keys.enc,
+        &[
+            &host_challenge.to_be_bytes()[..],
+            &sequence_counter.to_be_bytes(),
+            &card_challenge,
+        ]
+        .concat(),
+    )
+}
+
+/// The cryptogram with which the host proves that it holds the keys.
+#[must_use]
+pub fn host_cryptogram(
+    keys: &SessionKeys,
+    host_challenge: u64,
+    sequence_counter: u16,
+    card_challenge: [u8; 6],
+) -> u64 {
+    full_mac(
+        &keys.enc,
+        &[
+            &sequence_counter.to_be_bytes()[..],
+            &card_challenge,
+            &host_challenge.to_be_bytes(),
+        ]
+        .concat(),
+    )
+}
+
+/// The C-MAC of a command whose header and Lc already mark it as secure
+/// messaging, chained from `icv`.
+#[must_use]
+pub fn c_mac(keys: &SessionKeys, icv: u64, command: &[u8]) -> u64 {
+    let mut data = command.to_vec();
+    Padding::Iso7816.pad(&mut data);
+    let mut blocks = bytes_to_blocks(&data);
+    let [first, second] = halves(&keys.c_mac);
+    let (first, second) = (Des::new(first), Des::new(second));
+    let state = mode::cbc_encrypt(&first, icv, &mut blocks);
+    first.encrypt(second.decrypt(state))
+}
+
+/// The ICV for the command after the one with MAC `c_mac`.
+#[must_use]
+pub fn next_icv(keys: &SessionKeys, c_mac: u64) -> u64 {
+    let [first, _] = halves(&keys.c_mac);
+    Des::new(first).encrypt(c_mac)
+}
+
+/// The host side of an open secure channel.
+#[derive(Debug, Clone)]
+pub struct SecureChannel {
+    keys: SessionKeys,
+    level: SecurityLevel,
+    icv: u64,
+}
+
+impl SecureChannel {
+    /// Check the card's answer to INITIALIZE UPDATE with `host_challenge`,
+    /// and return the channel with the EXTERNAL AUTHENTICATE command that
+    /// opens it on the card.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Scp02Error::CardCryptogram`] if the card holds different
+    /// keys, and the errors of [`InitializeUpdateResponse::parse`].
+    pub fn open(
+        keys: &StaticKeys,
+        host_challenge: u64,
+        response: &[u8],
+        level: SecurityLevel,
+    ) -> Result<(Self, Vec<u8>), Scp02Error> {
+        let response = InitializeUpdateResponse::parse(response)?;
+        let keys = SessionKeys::derive(keys, response.sequence_counter);
+        let expected = card_cryptogram(
+            &keys,
+            host_challenge,
+            response.sequence_counter,
+            response.card_challenge,
+        );
+        if !constant_time_eq(
+            &expected.to_be_bytes(),
+            &response.card_cryptogram.to_be_bytes(),
+        ) {
+            return Err(Scp02Error::CardCryptogram);
+        }
+
+        let cryptogram = host_cryptogram(
+            &keys,
+            host_challenge,
+            response.sequence_counter,
+            response.card_challenge,
+        );
+        let mut channel = Self {
+            keys,
+            level,
+            icv: 0,
+        };
+        let command = [
+            &[0x84, 0x82, level.p1(), 0x00, 0x08][..],
+            &cryptogram.to_be_bytes(),
+        ]
+        .concat();
+        let command = channel.protect(&command, false)?;
+        Ok((channel, command))
+    }
+
+    #[must_use]
+    pub const fn session_keys(&self) -> &SessionKeys {
+        &self.keys
+    }
+
+    #[must_use]
+    pub const fn security_level(&self) -> SecurityLevel {
+        self.level
+    }
+
+    /// Add the C-MAC to a short command APDU, and encrypt its data at
+    /// [`SecurityLevel::MacAndEncryption`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error for a malformed command or one whose wrapped data
+    /// would not fit a short APDU.
+    pub fn wrap(&mut self, command: &[u8]) -> Result<Vec<u8>, Scp02Error> {
+        self.protect(command, self.level == SecurityLevel::MacAndEncryption)
+    }
+
+    fn protect(&mut self, command: &[u8], encrypt: bool) -> Result<Vec<u8>, Scp02Error> {
+        let Command {
+            mut header,
+            data,
+            le,
+        } = Command::parse(command)?;
+        header[0] |= SECURE_MESSAGING;
+        let mac = c_mac(
+            &self.keys,
+            self.icv,
+            &[&header[..], &[lc(data.len())?], data].concat(),
+        );
+        let data = if encrypt && !data.is_empty() {
+            let mut data = data.to_vec();
+            Padding::Iso7816.pad(&mut data);
+            let mut blocks = bytes_to_blocks(&data);
+            mode::cbc_encrypt(&self.keys.enc.cipher(), 0, &mut blocks);
+            blocks_to_bytes(&blocks)
+        } else {
+            data.to_vec()
+        };
+        // The ICV only moves on once the command is sure to be sent.
+        let lc = lc(data.len())?;
+        self.icv = next_icv(&self.keys, mac);
+        Ok([&header[..], &[lc], &data, &mac.to_be_bytes(), le.as_slice()].concat())
+    }
+}
+
+/// A short command APDU.
+struct Command<'a> {
+    header: [u8; 4],
+    data: &'a [u8],
+    le: Option<u8>,
+}
+
+impl<'a> Command<'a> {
+    fn parse(command: &'a [u8]) -> Result<Self, Scp02Error> {
+        let (header, body) = command
+            .split_first_chunk::<4>()
+            .ok_or(Scp02Error::InvalidCommand("shorter than 4 bytes"))?;
+        let (data, le) = match body {
+            [] => (&[][..], None),
+            &[le] => (&[][..], Some(le)),
+            [lc, rest @ ..] => {
+                let lc = usize::from(*lc);
+                match rest.len().checked_sub(lc) {
+                    Some(0) => (rest, None),
+                    Some(1) => (&rest[..lc], Some(rest[lc])),
+                    _ => return Err(Scp02Error::InvalidCommand("Lc does not match the data")),
+                }
+            }
+        };
+        Ok(Self {
+            header: *header,
+            data,
+            le,
+        })
+    }
+}
+
+/// Lc for `len` bytes of data followed by the C-MAC.
+fn lc(len: usize) -> Result<u8, Scp02Error> {
+    u8::try_from(len + MAC_LEN).map_err(|_| Scp02Error::CommandTooLong(len + MAC_LEN))
+}
+
+/// `N` bytes of `data` from `start`.
+fn field<const N: usize>(data: &[u8], start: usize) -> [u8; N] {
+    std::array::from_fn(|i| data[start + i])
+}
+
+/// CBC-MAC under the full 2TDEA key with ISO/IEC 9797-1 padding method 2.
+fn full_mac(key: &Key, data: &[u8]) -> u64 {
+    mac::mac(key, MacAlgorithm::Cbc, MacPadding::Method2, data)
+        .unwrap_or_else(|e| unreachable!("The CBC-MAC accepts any key: {e}"))
+}
+
+fn halves(key: &Key) -> [u64; 2] {
+    let [first, second, ..] = *key.parts() else {
+        unreachable!("Session keys are 2TDEA keys")
+    };
+    [first, second]
+}
+
+const fn check_key(key: &Key) -> Result<(), Scp02Error> {
+    match key.length() {
+        KeyLength::Double => Ok(()),
+        length => Err(Scp02Error::KeyLength(length.bytes())),
+    }
+}
@@ -0,0 +1,406 @@
+//! Authenticated encryption (encrypt-then-MAC) with TDEA.
+//!
+//! Sealed data is TDEA-CBC ciphertext with PKCS#7 padding under a random IV,
+//! followed by a CMAC tag over everything before it:
+//!
+//! ```text
+//! "DESAE" | version (1) | IV (8) | ciphertext | tag (8)
+//! ```
+//!
+//! The encryption and MAC keys are derived from a single master key with the
+//! NIST SP 800-108 counter-mode KDF using CMAC, so neither is ever used for
+//! the other purpose. They are at least double length, so a single-length
+//! master key still seals with TDEA, although its strength remains that of
+//! the 56-bit master key. [`open`] checks the tag before decrypting anything;
+//! [`OpenReader`] streams plaintext as it goes and fails at the end if the tag
+//! does not match, so its output must be discarded on error.
+
+use crate::{
+    BLOCK_SIZE,
+    cmac::Cmac,
+    key::{Key, KeyLength},
+    mode::Mode,
+    padding::Padding,
+    stream::{DecryptReader, READ_CHUNK},
+    tdes::TripleDes,
+};
+use std::io::{self, Read};
+use thiserror::Error;
+
+/// Length of the header in front of the ciphertext.
+pub const HEADER_LEN: usize = MAGIC.len() + 1 + BLOCK_SIZE;
+
+/// Length of the CMAC tag after the ciphertext.
+pub const TAG_LEN: usize = BLOCK_SIZE;
+
+const MAGIC: &[u8; 5] = b"DESAE";
+const VERSION: u8 = 1;
+
+const ENCRYPTION_LABEL: &[u8] = b"des-seal encryption";
+const AUTHENTICATION_LABEL: &[u8] = b"des-seal authentication";
+
+#[derive(Debug, Error)]
+pub enum SealError {
+    #[error("Input is not sealed data")]
+    MissingHeader,
+
+    #[error("Unsupported sealed data version {0}")]
+    UnsupportedVersion(u8),
+
+    #[error("Sealed data is truncated")]
+    Truncated,
+
+    #[error("Authentication failed: the data was modified or the key is wrong")]
+    Authentication,
+
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+impl SealError {
+    /// Recover a `SealError` raised inside a reader or writer.
+    fn from_io(error: io::Error) -> Self {
+        error.downcast::<Self>().unwrap_or_else(Self::Io)
+    }
+
+    fn into_io(self) -> io::Error {
+        match self {
+            Self::Io(error) => error,
+            error => io::Error::new(io::ErrorKind::InvalidData, error),
+        }
+    }
+}
+
+/// Encryption and MAC keys derived from a master key.
+struct Keys {
+    encryption: TripleDes,
+    authentication: TripleDes,
+}
+
+impl Keys {
+    fn derive(master: &Key) -> Self {
+        let cipher = master.cipher();
+        let length = match master.length() {
+            KeyLength::Single | KeyLength::Double => KeyLength::Double,
+            KeyLength::Triple => KeyLength::Triple,
+        };
+        Self {
+            encryption: derive_subkey(&cipher, ENCRYPTION_LABEL, length),
+            authentication: derive_subkey(&cipher, AUTHENTICATION_LABEL, length),
+        }
+    }
+}
+
+/// SP 800-108 KDF in counter mode with CMAC as the PRF and an empty context.
+pub(crate) fn derive_subkey(master: &TripleDes, label: &[u8], length: KeyLength) -> TripleDes {
+    let bits: u32 = match length {
+        KeyLength::Single => 64,
+        KeyLength::Double => 128,
+        KeyLength::Triple => 192,
+    };
+    let bytes = (1..=u8::MAX)
+        .take(length.parts())
+        .flat_map(|counter| {
+            let mut mac = Cmac::new(master);
+            mac.update(&[counter]);
+            mac.update(label);
+            mac.update(&[0]);
+            mac.update(&bits.to_be_bytes());
+            mac.finalize().to_be_bytes()
+        })
+        .collect::<Vec<_>>();
+    Key::from_bytes(&bytes)
+        .map_or_else(
+            |_| unreachable!("Derived key has a valid length"),
+            Key::with_odd_parity,
+        )
+        .cipher()
+}
+
+/// Seal `plaintext` under `key` with a random IV.
+#[cfg(feature = "rand")]
+#[must_use]
+pub fn seal(key: &Key, plaintext: &[u8]) -> Vec<u8> {
+    use std::io::Write;
+
+    SealWriter::new(key, Vec::new())
+        .and_then(|mut writer| {
+            writer.write_all(plaintext)?;
+            writer.finish()
+        })
+        .unwrap_or_else(|_| unreachable!("Writing to a Vec cannot fail"))
+}
+
+/// Check the tag of `sealed` and decrypt it.
+///
+/// # Errors
+///
+/// Returns [`SealError::Authentication`] if the data was modified or `key`
+/// is wrong, and other errors for data that is not sealed.
+pub fn open(key: &Key, sealed: &[u8]) -> Result<Vec<u8>, SealError> {
+    verify(key, sealed)?;
+    let mut plaintext = Vec::with_capacity(sealed.len());
+    OpenReader::new(key, sealed)?
+        .read_to_end(&mut plaintext)
+        .map_err(SealError::from_io)?;
+    Ok(plaintext)
+}
+
+/// Check the tag of sealed data read from `reader` without decrypting it,
+/// for example before streaming a file through [`OpenReader`].
+///
+/// # Errors
+///
+/// Returns [`SealError::Authentication`] if the data was modified or `key`
+/// is wrong, and other errors for data that is not sealed or cannot be read.
+pub fn verify<R: Read>(key: &Key, mut reader: R) -> Result<(), SealError> {
+    let keys = Keys::derive(key);
+    let (header, _) = read_header(&mut reader)?;
+    io::copy(
+        &mut TagReader::new(reader, keys.authentication, &header),
+        &mut io::sink(),
+    )
+    .map_err(SealError::from_io)?;
+    Ok(())
+}
+
+/// Seals everything written to it and forwards the result to `W`.
+///
+/// The tag is only written by [`SealWriter::finish`]; data from a writer that
+/// is dropped instead fails to open.
+#[cfg(feature = "rand")]
+#[derive(Debug)]
+pub struct SealWriter<W: io::Write> {
+    inner: crate::stream::EncryptWriter<TripleDes, TagWriter<W>>,
+}
+
+#[cfg(feature = "rand")]
+impl<W: io::Write> SealWriter<W> {
+    /// Write the header with a random IV to `inner`.
+    ///
+    /// # Errors
+    ///
+    /// Returns any error from writing the header.
+    pub fn new(key: &Key, inner: W) -> io::Result<Self> {
+        use std::io::Write;
+
+        let keys = Keys::derive(key);
+        let iv = rand::random::<u64>();
+        let mut tagged = TagWriter {
+            inner,
+            mac: Cmac::new(keys.authentication),
+        };
+        tagged.write_all(MAGIC)?;
+        tagged.write_all(&[VERSION])?;
+        tagged.write_all(&iv.to_be_bytes())?;
+        Ok(Self {
+            inner: crate::stream::EncryptWriter::new(
+                tagged,
+                keys.encryption,
+                Mode::Cbc { iv },
+                Padding::Pkcs7,
+            ),
+        })
+    }
+
+    /// Pad and encrypt the final block, append the tag, flush, and return
+    /// the inner writer.
+    ///
+    /// # Errors
+    ///
+    /// Returns any error from the inner writer.
+    pub fn finish(self) -> io::Result<W> {
+        let TagWriter { mut inner, mac } = self.inner.finish()?;
+        inner.write_all(&mac.finalize().to_be_bytes())?;
+        inner.flush()?;
+        Ok(inner)
+    }
+}
+
+#[cfg(feature = "rand")]
+impl<W: io::Write> io::Write for SealWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Forwards writes to `W`, feeding them to the CMAC.
+#[cfg(feature = "rand")]
+#[derive(Debug)]
+struct TagWriter<W> {
+    inner: W,
+    mac: Cmac<TripleDes>,
+}
+
+#[cfg(feature = "rand")]
+impl<W: io::Write> io::Write for TagWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.mac.update(&buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Decrypts sealed data read from `R`.
+///
+/// Plaintext is returned before the tag at the end has been read, so it
+/// must not be trusted until the reader has reported EOF. A wrong tag is
+/// reported as an [`io::ErrorKind::InvalidData`] error wrapping
+/// [`SealError::Authentication`], and so is every read after it.
+#[derive(Debug)]
+pub struct OpenReader<R: Read> {
+    inner: DecryptReader<TripleDes, TagReader<R>>,
+}
+
+impl<R: Read> OpenReader<R> {
+    /// Read the header from `inner`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `inner` does not start with a valid header.
+    pub fn new(key: &Key, inner: R) -> Result<Self, SealError> {
+        Self::with_capacity(READ_CHUNK, key, inner)
+    }
+
+    /// Like [`OpenReader::new`], requesting up to `capacity` bytes from
+    /// `inner` at once.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `inner` does not start with a valid header.
+    pub fn with_capacity(capacity: usize, key: &Key, mut inner: R) -> Result<Self, SealError> {
+        let keys = Keys::derive(key);
+        let (header, iv) = read_header(&mut inner)?;
+        Ok(Self {
+            inner: DecryptReader::with_capacity(
+                capacity,
+                TagReader::new(inner, keys.authentication, &header),
+                keys.encryption,
+                Mode::Cbc { iv },
+                Padding::Pkcs7,
+            ),
+        })
+    }
+}
+
+impl<R: Read> Read for OpenReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.inner.read(buf)
+    }
+}
+
+/// Why the tag check failed.
+#[derive(Debug, Clone, Copy)]
+enum TagFailure {
+    Truncated,
+    Mismatch,
+}
+
+impl From<TagFailure> for SealError {
+    fn from(failure: TagFailure) -> Self {
+        match failure {
+            TagFailure::Truncated => Self::Truncated,
+            TagFailure::Mismatch => Self::Authentication,
+        }
+    }
+}
+
+/// Passes the ciphertext through while holding back the trailing tag, and
+/// checks the tag at EOF.
+#[derive(Debug)]
+struct TagReader<R> {
+    inner: R,
+    /// `None` once the tag has been checked.
+    mac: Option<Cmac<TripleDes>>,
+    pending: Vec<u8>,
+    /// Set by a failed check, so that no later read reports a clean EOF.
+    failed: Option<TagFailure>,
+}
+
+impl<R: Read> TagReader<R> {
+    fn new(inner: R, key: TripleDes, header: &[u8]) -> Self {
+        let mut mac = Cmac::new(key);
+        mac.update(header);
+        Self {
+            inner,
+            mac: Some(mac),
+            pending: Vec::new(),
+            failed: None,
+        }
+    }
+
+    fn check_tag(&mut self) -> io::Result<usize> {
+        let Some(mac) = self.mac.take() else {
+            return Ok(0);
+        };
+        let failure = if self.pending.len() < TAG_LEN {
+            TagFailure::Truncated
+        } else if mac.verify(&self.pending).is_err() {
+            TagFailure::Mismatch
+        } else {
+            return Ok(0);
+        };
+        self.failed = Some(failure);
+        Err(SealError::from(failure).into_io())
+    }
+}
+
+impl<R: Read> Read for TagReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if let Some(failure) = self.failed {
+            return Err(SealError::from(failure).into_io());
+        }
+        if self.mac.is_none() || buf.is_empty() {
+            return Ok(0);
+        }
+
+        // Release only bytes that are known not to be part of the tag.
+        while self.pending.len() <= TAG_LEN {
+            let start = self.pending.len();
+            self.pending.resize(start + buf.len().max(READ_CHUNK), 0);
+            let read = self.inner.read(&mut self.pending[start..]);
+            self.pending
+                .truncate(start + read.as_ref().map_or(0, |&count| count));
+            match read {
+                Ok(0) => return self.check_tag(),
+                Ok(_) => {}
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+
+        let count = (self.pending.len() - TAG_LEN).min(buf.len());
+        buf[..count].copy_from_slice(&self.pending[..count]);
+        if let Some(mac) = &mut self.mac {
+            mac.update(&buf[..count]);
+        }
+        self.pending.drain(..count);
+        Ok(count)
+    }
+}
+
+/// Read and check the header, returning it with the IV.
+fn read_header<R: Read>(reader: &mut R) -> Result<([u8; HEADER_LEN], u64), SealError> {
+    let mut header = [0; HEADER_LEN];
+    reader.read_exact(&mut header).map_err(|e| match e.kind() {
+        io::ErrorKind::UnexpectedEof => SealError::Truncated,
+        _ => SealError::Io(e),
+    })?;
+    let [m0, m1, m2, m3, m4, version, i0, i1, i2, i3, i4, i5, i6, i7] = header;
+    if [m0, m1, m2, m3, m4] != *MAGIC {
+        return Err(SealError::MissingHeader);
+    }
+    if version != VERSION {
+        return Err(SealError::UnsupportedVersion(version));
+    }
+    let iv = u64::from_be_bytes([i0, i1, i2, i3, i4, i5, i6, i7]);
+    Ok((header, iv))
+}
@@ -0,0 +1,255 @@
+//! Shamir secret sharing of DES and TDEA keys over GF(256).
+//!
+//! Every key byte is the constant term of its own random polynomial of degree
+//! `threshold - 1`; share `x` holds the polynomials evaluated at `x`. Any
+//! `threshold` shares recover the key, fewer reveal nothing about it. Each
+//! share also carries the key check value of the shared key, so recovering
+//! from mismatched or corrupted shares is detected.
+
+use crate::key::{Key, KeyError};
+use thiserror::Error;
+
+/// Bytes preceding the share data: index, threshold and the 3-byte KCV.
+const HEADER_LEN: usize = 5;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum ShareError {
+    #[error(
+        "Threshold must be between 2 and the number of shares (2..=255), got {threshold} of {shares}"
+    )]
+    InvalidThreshold { threshold: u8, shares: u8 },
+
+    #[error("{needed} shares are required, got {got}")]
+    TooFewShares { needed: u8, got: usize },
+
+    #[error("Share {0} was given more than once")]
+    DuplicateShare(u8),
+
+    #[error("Shares belong to different keys or thresholds")]
+    InconsistentShares,
+
+    #[error("Share is malformed")]
+    Malformed,
+
+    #[error("Recovered key does not match the key check value in the shares")]
+    KcvMismatch,
+
+    #[error(transparent)]
+    Key(#[from] KeyError),
+}
+
+/// One share of a key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Share {
+    index: u8,
+    threshold: u8,
+    kcv: [u8; 3],
+    data: Vec<u8>,
+}
+
+impl Share {
+    /// Position of the share on the polynomials (1-based).
+    #[must_use]
+    pub const fn index(&self) -> u8 {
+        self.index
+    }
+
+    /// Number of shares needed to recover the key.
+    #[must_use]
+    pub const fn threshold(&self) -> u8 {
+        self.threshold
+    }
+
+    /// Key check value of the shared key.
+    #[must_use]
+    pub const fn kcv(&self) -> [u8; 3] {
+        self.kcv
+    }
+
+    /// Serialize as index, threshold, KCV and the share bytes.
+    #[must_use]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(HEADER_LEN + self.data.len());
+        bytes.push(self.index);
+        bytes.push(self.threshold);
+        bytes.extend_from_slice(&self.kcv);
+        bytes.extend_from_slice(&self.data);
+        bytes
+    }
+
+    /// Parse a share produced by [`Share::to_bytes`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ShareError::Malformed`] if the header is invalid or the
+    /// share length does not match a key length.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ShareError> {
+        let Some((&[index, threshold, k0, k1, k2], data)) = bytes.split_first_chunk() else {
+            return Err(ShareError::Malformed);
+        };
+        if index == 0 || threshold < 2 || Key::from_bytes(data).is_err() {
+            return Err(ShareError::Malformed);
+        }
+        Ok(Self {
+            index,
+            threshold,
+            kcv: [k0, k1, k2],
+            data: data.to_vec(),
+        })
+    }
+}
+
+/// Split `key` into `shares` shares, any `threshold` of which recover it.
+///
+/// # Errors
+///
+/// Returns [`ShareError::InvalidThreshold`] unless
+/// `2 <= threshold <= shares`.
+#[cfg(feature = "rand")]
+pub fn split(key: &Key, threshold: u8, shares: u8) -> Result<Vec<Share>, ShareError> {
+    use rand::Rng;
+
+    if threshold < 2 || threshold > shares {
+        return Err(ShareError::InvalidThreshold { threshold, shares });
+    }
+
+    let secret = key.to_bytes();
+    let mut rng = rand::rng();
+    // coefficients[i] holds the polynomial for secret byte i, constant first.
+    let coefficients = secret
+        .iter()
+        .map(|&byte| {
+            let mut polynomial = vec![byte];
+            polynomial.extend((1..threshold).map(|_| rng.random::<u8>()));
+            polynomial
+        })
+        .collect::<Vec<_>>();
+
+    let kcv = key.kcv();
+    let shares = (1..=shares)
+        .map(|index| Share {
+            index,
+            threshold,
+            kcv,
+            data: coefficients
+                .iter()
+                .map(|polynomial| evaluate(polynomial, index))
+                .collect(),
+        })
+        .collect();
+    Ok(shares)
+}
+
+/// Recover the key from at least `threshold` shares.
+///
+/// # Errors
+///
+/// Returns an error if too few, duplicate or inconsistent shares are given,
+/// or if the recovered key does not match the embedded check value.
+pub fn recover(shares: &[Share]) -> Result<Key, ShareError> {
+    let Some(first) = shares.first() else {
+        return Err(ShareError::TooFewShares { needed: 2, got: 0 });
+    };
+    if shares.iter().any(|share| {
+        share.threshold != first.threshold
+            || share.kcv != first.kcv
+            || share.data.len() != first.data.len()
+    }) {
+        return Err(ShareError::InconsistentShares);
+    }
+    if shares.len() < usize::from(first.threshold) {
+        return Err(ShareError::TooFewShares {
+            needed: first.threshold,
+            got: shares.len(),
+        });
+    }
+    for (i, share) in shares.iter().enumerate() {
+        if shares[..i].iter().any(|other| other.index == share.index) {
+            return Err(ShareError::DuplicateShare(share.index));
+        }
+    }
+
+    let secret = (0..first.data.len())
+        .map(|byte| interpolate_at_zero(shares, byte))
+        .collect::<Vec<_>>();
+    let key = Key::from_bytes(&secret)?;
+    if key.kcv() != first.kcv {
+        return Err(ShareError::KcvMismatch);
+    }
+    Ok(key)
+}
+
+/// Lagrange interpolation of byte `byte` of the shares at `x = 0`.
+fn interpolate_at_zero(shares: &[Share], byte: usize) -> u8 {
+    shares.iter().fold(0, |secret, share| {
+        let basis = shares
+            .iter()
+            .filter(|other| other.index != share.index)
+            .fold(1, |basis, other| {
+                mul(basis, div(other.index, other.index ^ share.index))
+            });
+        secret ^ mul(share.data[byte], basis)
+    })
+}
+
+/// Evaluate a polynomial (constant term first) at `x` with Horner's rule.
+#[cfg(feature = "rand")]
+fn evaluate(polynomial: &[u8], x: u8) -> u8 {
+    polynomial
+        .iter()
+        .rev()
+        .fold(0, |result, &coefficient| mul(result, x) ^ coefficient)
+}
+
+/// Multiply in GF(2^8) modulo the AES polynomial `x^8 + x^4 + x^3 + x + 1`,
+/// without data-dependent branches.
+const fn mul(mut a: u8, mut b: u8) -> u8 {
+    let mut product = 0;
+    let mut i = 0;
+    while i < 8 {
+        product ^= a & (b & 1).wrapping_neg();
+        let carry = (a >> 7).wrapping_neg();
+        a = (a << 1) ^ (carry & 0x1B);
+        b >>= 1;
+        i += 1;
+    }
+    product
+}
+
+/// Divide in GF(2^8); `b` must be non-zero.
+const fn div(a: u8, b: u8) -> u8 {
+    // b^254 is the multiplicative inverse of b.
+    let mut inverse = 1;
+    let mut i = 0;
+    while i < 254 {
+        inverse = mul(inverse, b);
+        i += 1;
+    }
+    mul(a, inverse)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn multiplication_matches_fips_197() {
+        // FIPS 197, section 4.2: {57} * {83} = {c1} and {57} * {13} = {fe}.
+        assert_eq!(mul(0x57, 0x83), 0xC1);
+        assert_eq!(mul(0x57, 0x13), 0xFE);
+    }
+
+    #[test]
+    fn division_inverts_multiplication() {
+        for a in 0..=u8::MAX {
+            for b in 1..=u8::MAX {
+                assert_eq!(div(mul(a, b), b), a, "{a:02X} * {b:02X} / {b:02X}");
+            }
+        }
+    }
+
+    #[test]
+    fn evaluate_constant_at_zero() {
+        assert_eq!(evaluate(&[0xAB, 0x12, 0x34], 0), 0xAB);
+    }
+}
@@ -0,0 +1,299 @@
+//! `std::io` adapters that encrypt and decrypt byte streams.
+//!
+//! Data is buffered only up to a block (plus one read chunk), so arbitrarily
+//! large files and sockets can be processed without loading them into memory.
+
+use crate::{
+    BLOCK_SIZE, BlockCipher, blocks_to_bytes, bytes_to_blocks,
+    mode::Mode,
+    padding::{Padding, PaddingError},
+};
+use std::{
+    io::{self, Read, Write},
+    mem,
+};
+
+/// Number of ciphertext bytes requested from the inner reader at once.
+pub(crate) const READ_CHUNK: usize = 8 * 1024;
+
+/// Incremental encryption state shared by the sync and async writers.
+#[derive(Debug)]
+pub(crate) struct Encryptor<C> {
+    cipher: C,
+    mode: Mode,
+    padding: Padding,
+    pending: Vec<u8>,
+}
+
+impl<C: BlockCipher> Encryptor<C> {
+    pub(crate) const fn new(cipher: C, mode: Mode, padding: Padding) -> Self {
+        Self {
+            cipher,
+            mode,
+            padding,
+            pending: Vec::new(),
+        }
+    }
+
+    /// Encrypt every complete block, buffering the trailing partial block.
+    pub(crate) fn update(&mut self, data: &[u8]) -> Vec<u8> {
+        self.pending.extend_from_slice(data);
+        let aligned = self.pending.len() - self.pending.len() % BLOCK_SIZE;
+
+        let mut blocks = bytes_to_blocks(&self.pending[..aligned]);
+        self.pending.drain(..aligned);
+        self.mode.encrypt(&self.cipher, &mut blocks);
+        blocks_to_bytes(&blocks)
+    }
+
+    /// Pad and encrypt the buffered tail.
+    pub(crate) fn finalize(&mut self) -> io::Result<Vec<u8>> {
+        let mut tail = mem::take(&mut self.pending);
+        self.padding.pad(&mut tail);
+        apply_final(&mut self.mode, &self.cipher, &tail, true)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))
+    }
+}
+
+/// Incremental decryption state shared by the sync and async readers.
+#[derive(Debug)]
+pub(crate) struct Decryptor<C> {
+    cipher: C,
+    mode: Mode,
+    padding: Padding,
+    pending: Vec<u8>,
+}
+
+impl<C: BlockCipher> Decryptor<C> {
+    pub(crate) const fn new(cipher: C, mode: Mode, padding: Padding) -> Self {
+        Self {
+            cipher,
+            mode,
+            padding,
+            pending: Vec::new(),
+        }
+    }
+
+    /// Decrypt every complete block except the last one when padding has to
+    /// be stripped at the end of the stream.
+    pub(crate) fn update(&mut self, data: &[u8]) -> Vec<u8> {
+        self.pending.extend_from_slice(data);
+        let held_back = if self.padding == Padding::None {
+            0
+        } else {
+            BLOCK_SIZE
+        };
+        let ready = self.pending.len().saturating_sub(held_back);
+        let ready = ready - ready % BLOCK_SIZE;
+
+        let mut blocks = bytes_to_blocks(&self.pending[..ready]);
+        self.pending.drain(..ready);
+        self.mode.decrypt(&self.cipher, &mut blocks);
+        blocks_to_bytes(&blocks)
+    }
+
+    /// Decrypt the held-back tail and strip the padding.
+    pub(crate) fn finalize(&mut self) -> Result<Vec<u8>, PaddingError> {
+        let tail = mem::take(&mut self.pending);
+        let plaintext = apply_final(&mut self.mode, &self.cipher, &tail, false)?;
+        Ok(self.padding.unpad(&plaintext)?.to_vec())
+    }
+}
+
+/// Encrypts everything written to it and forwards the ciphertext to `W`.
+///
+/// Complete blocks are encrypted as soon as they are written; the trailing
+/// partial block is held back until [`EncryptWriter::finish`], which applies
+/// the padding. [`Write::flush`] only flushes the inner writer, since padding
+/// can be added once per stream. Dropping the writer finishes it, ignoring
+/// errors.
+#[derive(Debug)]
+pub struct EncryptWriter<C: BlockCipher, W: Write> {
+    inner: Option<W>,
+    encryptor: Encryptor<C>,
+    finished: bool,
+}
+
+impl<C: BlockCipher, W: Write> EncryptWriter<C, W> {
+    pub const fn new(inner: W, cipher: C, mode: Mode, padding: Padding) -> Self {
+        Self {
+            inner: Some(inner),
+            encryptor: Encryptor::new(cipher, mode, padding),
+            finished: false,
+        }
+    }
+
+    /// Pad and encrypt the final block, flush, and return the inner writer.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`io::ErrorKind::InvalidInput`] error if the data is not
+    /// block-aligned and neither padding nor a streaming mode is used, or any
+    /// error from the inner writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.write_final()?;
+        self.inner
+            .take()
+            .ok_or_else(|| io::Error::other("writer was already finished"))
+    }
+
+    fn write_final(&mut self) -> io::Result<()> {
+        if self.finished {
+            return Ok(());
+        }
+        self.finished = true;
+
+        let ciphertext = self.encryptor.finalize()?;
+        let inner = self.inner_mut();
+        inner.write_all(&ciphertext)?;
+        inner.flush()
+    }
+
+    const fn inner_mut(&mut self) -> &mut W {
+        self.inner
+            .as_mut()
+            .expect("Inner writer is present until finished")
+    }
+}
+
+impl<C: BlockCipher, W: Write> Write for EncryptWriter<C, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.finished {
+            return Err(io::Error::other("write after the stream was finished"));
+        }
+
+        let ciphertext = self.encryptor.update(buf);
+        self.inner_mut().write_all(&ciphertext)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner_mut().flush()
+    }
+}
+
+impl<C: BlockCipher, W: Write> Drop for EncryptWriter<C, W> {
+    fn drop(&mut self) {
+        if self.inner.is_some() {
+            let _ = self.write_final();
+        }
+    }
+}
+
+/// Decrypts the ciphertext read from `R`.
+///
+/// The last block is held back until the inner reader reports EOF so that
+/// the padding can be verified and removed. Once the padding is rejected,
+/// every later read fails with the same error.
+#[derive(Debug)]
+pub struct DecryptReader<C: BlockCipher, R: Read> {
+    inner: R,
+    decryptor: Decryptor<C>,
+    chunk: Vec<u8>,
+    capacity: usize,
+    output: Vec<u8>,
+    position: usize,
+    eof: bool,
+    failed: Option<PaddingError>,
+}
+
+impl<C: BlockCipher, R: Read> DecryptReader<C, R> {
+    pub const fn new(inner: R, cipher: C, mode: Mode, padding: Padding) -> Self {
+        Self::with_capacity(READ_CHUNK, inner, cipher, mode, padding)
+    }
+
+    /// Create a reader that requests up to `capacity` bytes from `inner` at
+    /// once. Larger reads let bulk modes use more threads.
+    pub const fn with_capacity(
+        capacity: usize,
+        inner: R,
+        cipher: C,
+        mode: Mode,
+        padding: Padding,
+    ) -> Self {
+        Self {
+            inner,
+            decryptor: Decryptor::new(cipher, mode, padding),
+            chunk: Vec::new(),
+            capacity,
+            output: Vec::new(),
+            position: 0,
+            eof: false,
+            failed: None,
+        }
+    }
+
+    /// Return the inner reader.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    fn fill(&mut self) -> io::Result<()> {
+        self.chunk.resize(self.capacity.max(1), 0);
+        let read = self.inner.read(&mut self.chunk)?;
+
+        self.output = if read == 0 {
+            let tail = self
+                .decryptor
+                .finalize()
+                .inspect_err(|&e| self.failed = Some(e))
+                .map_err(invalid_data)?;
+            self.eof = true;
+            tail
+        } else {
+            self.decryptor.update(&self.chunk[..read])
+        };
+        self.position = 0;
+        Ok(())
+    }
+}
+
+impl<C: BlockCipher, R: Read> Read for DecryptReader<C, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if let Some(error) = self.failed {
+            return Err(invalid_data(error));
+        }
+        while self.position == self.output.len() && !self.eof {
+            self.fill()?;
+        }
+
+        let available = &self.output[self.position..];
+        let count = available.len().min(buf.len());
+        buf[..count].copy_from_slice(&available[..count]);
+        self.position += count;
+        Ok(count)
+    }
+}
+
+/// Process the final, possibly partial, run of bytes.
+///
+/// A partial block is only accepted in streaming modes, where it is combined
+/// with a truncated keystream block.
+fn apply_final<C: BlockCipher>(
+    mode: &mut Mode,
+    cipher: &C,
+    data: &[u8],
+    encrypt: bool,
+) -> Result<Vec<u8>, PaddingError> {
+    let remainder = data.len() % BLOCK_SIZE;
+    if remainder != 0 && !mode.is_streaming() {
+        return Err(PaddingError::InvalidLength);
+    }
+
+    let mut padded = data.to_vec();
+    padded.resize(data.len() + (BLOCK_SIZE - remainder) % BLOCK_SIZE, 0);
+    let mut blocks = bytes_to_blocks(&padded);
+    if encrypt {
+        mode.encrypt(cipher, &mut blocks);
+    } else {
+        mode.decrypt(cipher, &mut blocks);
+    }
+
+    let mut bytes = blocks_to_bytes(&blocks);
+    bytes.truncate(data.len());
+    Ok(bytes)
+}
+
+pub(crate) fn invalid_data(error: PaddingError) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, error)
+}
@@ -0,0 +1,98 @@
+use claims::{assert_err_eq, assert_ok, assert_ok_eq};
+use des_lib::{
+    cmac::{Cmac, cmac},
+    mac::MacError,
+    tdes::TripleDes,
+};
+use rstest::rstest;
+
+/// Message prefix shared by the SP 800-38B TDEA examples.
+const MESSAGE: [u8; 32] = [
+    0x6B, 0xC1, 0xBE, 0xE2, 0x2E, 0x40, 0x9F, 0x96, 0xE9, 0x3D, 0x7E, 0x11, 0x73, 0x93, 0x17, 0x2A,
+    0xAE, 0x2D, 0x8A, 0x57, 0x1E, 0x03, 0xAC, 0x9C, 0x9E, 0xB7, 0x6F, 0xAC, 0x45, 0xAF, 0x8E, 0x51,
+];
+
+#[rstest]
+#[case(0, 0xB7A6_88E1_22FF_AF95)]
+#[case(8, 0x8E8F_2931_3628_3797)]
+#[case(20, 0x743D_DBE0_CE2D_C2ED)]
+#[case(32, 0x33E6_B109_2400_EAE5)]
+fn three_key_examples(#[case] len: usize, #[case] expected: u64) {
+    let tdes = TripleDes::new(
+        0x8AA8_3BF8_CBDA_1062,
+        0x0BC1_BF19_FBB6_CD58,
+        0xBC31_3D4A_371C_A8B5,
+    );
+    assert_eq!(cmac(&tdes, &MESSAGE[..len]), expected);
+}
+
+#[rstest]
+#[case(0, 0xBD2E_BF9A_3BA0_0361)]
+#[case(8, 0x4FF2_AB81_3C53_CE83)]
+#[case(20, 0x62DD_1B47_1902_BD4E)]
+#[case(32, 0x31B1_E431_DABC_4EB8)]
+fn two_key_examples(#[case] len: usize, #[case] expected: u64) {
+    let tdes = TripleDes::with_two_keys(0x4CF1_5134_A285_0DD5, 0x8A3D_10BA_8057_0D38);
+    assert_eq!(cmac(&tdes, &MESSAGE[..len]), expected);
+}
+
+fn three_key() -> TripleDes {
+    TripleDes::new(
+        0x8AA8_3BF8_CBDA_1062,
+        0x0BC1_BF19_FBB6_CD58,
+        0xBC31_3D4A_371C_A8B5,
+    )
+}
+
+/// Streaming matches the examples however the message is split.
+#[rstest]
+#[case(0, 0xB7A6_88E1_22FF_AF95)]
+#[case(8, 0x8E8F_2931_3628_3797)]
+#[case(20, 0x743D_DBE0_CE2D_C2ED)]
+#[case(32, 0x33E6_B109_2400_EAE5)]
+fn streaming_examples(
+    #[case] len: usize,
+    #[case] expected: u64,
+    #[values(1, 3, 7, 8, 9, 32)] chunk: usize,
+) {
+    let tdes = three_key();
+    let mut mac = Cmac::new(&tdes);
+    for part in MESSAGE[..len].chunks(chunk) {
+        mac.update(part);
+        mac.update(&[]);
+    }
+    assert_eq!(mac.finalize(), expected);
+}
+
+#[rstest]
+fn truncated_tags(#[values(4, 6, 8)] length: usize) {
+    let tdes = three_key();
+    let expected = 0x33E6_B109_2400_EAE5_u64.to_be_bytes();
+
+    let mut mac = Cmac::new(&tdes);
+    mac.update(&MESSAGE);
+    assert_ok_eq!(
+        mac.clone().finalize_truncated(length),
+        expected[..length].to_vec()
+    );
+    assert_ok!(mac.clone().verify(&expected[..length]));
+
+    let mut wrong = expected;
+    wrong[length - 1] ^= 0x01;
+    assert_err_eq!(mac.verify(&wrong[..length]), MacError::Mismatch);
+}
+
+#[rstest]
+fn rejects_invalid_tag_lengths(#[values(0, 1, 3, 9)] length: usize) {
+    let tdes = three_key();
+    let mut tag = cmac(&tdes, &MESSAGE).to_be_bytes().to_vec();
+    tag.resize(length, 0);
+    assert_err_eq!(
+        Cmac::new(&tdes).verify(&tag),
+        MacError::InvalidLength(length)
+    );
+    assert_err_eq!(
+        Cmac::new(&tdes).finalize_truncated(length),
+        MacError::InvalidLength(length)
+    );
+}
@@ -0,0 +1,68 @@
+use claims::{assert_err_eq, assert_ok, assert_ok_eq};
+use des_lib::{
+    cvv::{CVV2_SERVICE_CODE, CvvError, ICVV_SERVICE_CODE, cvv, verify},
+    key::Key,
+};
+use rstest::rstest;
+
+fn cvk() -> Key {
+    Key::from_parts(&[0x0123_4567_89AB_CDEF, 0xFEDC_BA98_7654_3210]).expect("valid key")
+}
+
+/// The first case is the common published example; the others were checked
+/// with `openssl enc -des-ecb` and `-des-ede`.
+#[rstest]
+#[case("4123456789012345", "8701", "101", "561")]
+#[case("4123456789012345", "8701", CVV2_SERVICE_CODE, "636")]
+#[case("4123456789012345", "8701", ICVV_SERVICE_CODE, "651")]
+#[case("4123456789012345", "8701", "201", "098")]
+#[case("4123456789012345678", "8701", "101", "362")]
+fn card_values(
+    #[case] pan: &str,
+    #[case] expiry: &str,
+    #[case] service_code: &str,
+    #[case] expected: &str,
+) {
+    assert_ok_eq!(cvv(&cvk(), pan, expiry, service_code), expected);
+    assert_ok!(verify(&cvk(), pan, expiry, service_code, expected));
+}
+
+#[test]
+fn verify_rejects_wrong_values() {
+    let pan = "4123456789012345";
+    assert_err_eq!(
+        verify(&cvk(), pan, "8701", "101", "562"),
+        CvvError::Mismatch
+    );
+    assert_err_eq!(
+        verify(&cvk(), pan, "8702", "101", "561"),
+        CvvError::Mismatch
+    );
+    assert_err_eq!(
+        verify(&cvk(), pan, "8701", "101", "5610"),
+        CvvError::InvalidCvv
+    );
+}
+
+#[rstest]
+#[case("412345678901", "87011", "101", CvvError::InvalidExpiry)]
+#[case("41234567890", "8701", "101", CvvError::InvalidPan)]
+#[case("4123 4567 8901 2345", "8701", "101", CvvError::InvalidPan)]
+#[case("4123456789012345", "8701", "1O1", CvvError::InvalidServiceCode)]
+fn rejects_invalid_input(
+    #[case] pan: &str,
+    #[case] expiry: &str,
+    #[case] service_code: &str,
+    #[case] error: CvvError,
+) {
+    assert_err_eq!(cvv(&cvk(), pan, expiry, service_code), error);
+}
+
+#[rstest]
+fn requires_cvk_pair(#[values(1, 3)] parts: usize) {
+    let key = Key::from_parts(&[0x0123_4567_89AB_CDEF; 3][..parts]).expect("valid key");
+    assert_err_eq!(
+        cvv(&key, "4123456789012345", "8701", "101"),
+        CvvError::KeyLength(parts * 8)
+    );
+}
@@ -0,0 +1,190 @@
+use claims::{assert_err_eq, assert_ok_eq};
+use des_lib::{
+    BlockCipher,
+    dukpt::{
+        DukptError, KeyVariant, Ksn, MAX_COUNTER_BITS, Terminal, derive_key, ipek, transaction_key,
+        variant_key,
+    },
+    key::Key,
+    pin::{PinFormat, build},
+};
+use rstest::rstest;
+
+fn bdk() -> Key {
+    Key::from_parts(&[0x0123_4567_89AB_CDEF, 0xFEDC_BA98_7654_3210]).expect("valid key")
+}
+
+fn ksn(counter: u32) -> Ksn {
+    "FFFF9876543210E00000"
+        .parse::<Ksn>()
+        .expect("valid KSN")
+        .with_counter(counter)
+}
+
+#[test]
+fn initial_key() {
+    let ipek = ipek(&bdk(), ksn(0)).expect("2TDEA BDK");
+    assert_eq!(
+        ipek.to_bytes(),
+        0x6AC2_92FA_A131_5B4D_858A_B3A3_D7D5_933A_u128.to_be_bytes()
+    );
+    // The counter does not take part in the IPEK.
+    assert_ok_eq!(des_lib::dukpt::ipek(&bdk(), ksn(7)), ipek);
+}
+
+/// PIN 1234 for PAN 4012345678909 in format 0, from the X9.24-1 test vectors.
+#[rstest]
+#[case(1, 0x1B9C_1845_EB99_3A7A)]
+#[case(2, 0x10A0_1C8D_02C6_9107)]
+#[case(3, 0x18DC_07B9_4797_B466)]
+#[case(4, 0x0BC7_9509_D564_5DF7)]
+#[case(5, 0x5BC0_AF22_AD87_B327)]
+#[case(6, 0xA16D_F70A_E361_58D8)]
+#[case(7, 0x2771_1C16_CB25_7F8E)]
+#[case(8, 0x50E5_5547_A502_7551)]
+fn encrypted_pin_blocks(#[case] counter: u32, #[case] expected: u64) {
+    let key = derive_key(&bdk(), ksn(counter)).expect("valid KSN");
+    let pin_key = variant_key(&key, KeyVariant::PinEncryption).expect("2TDEA key");
+    let block = build(PinFormat::Iso0, "1234", Some("4012345678909"), 0).expect("valid PIN");
+    assert_eq!(pin_key.cipher().encrypt_block(block), expected);
+}
+
+#[test]
+fn terminal_matches_host() {
+    let ipek = ipek(&bdk(), ksn(0)).expect("2TDEA BDK");
+    let mut terminal = Terminal::new(&ipek, ksn(0)).expect("2TDEA IPEK");
+    let mut previous = 0;
+    for _ in 0..2100 {
+        let (ksn, key) = terminal.next_key().expect("keys left");
+        let counter = ksn.counter();
+        assert!(counter > previous);
+        assert!(counter.count_ones() <= MAX_COUNTER_BITS);
+        assert_ok_eq!(transaction_key(&ipek, ksn), key);
+        previous = counter;
+    }
+    // 0x7FF has 11 bits set, so 0x7FE is followed by 0x800.
+    assert!(previous > 0x800);
+}
+
+/// Variant keys for KSN `FFFF9876543210E00001`, whose transaction key is
+/// `042666B49184CFA368DE9628D0397BC9`. The PIN, MAC request and data request
+/// keys are the X9.24-1 test vectors; the response keys were checked with an
+/// independent implementation.
+#[rstest]
+#[case(KeyVariant::PinEncryption, 0x0426_66B4_9184_CF5C_68DE_9628_D039_7B36)]
+#[case(KeyVariant::MacRequest, 0x0426_66B4_9184_30A3_68DE_9628_D039_84C9)]
+#[case(KeyVariant::MacResponse, 0x0426_66B4_6E84_CFA3_68DE_9628_2F39_7BC9)]
+#[case(KeyVariant::DataRequest, 0x448D_3F07_6D83_0403_6A55_A3D7_E005_5A78)]
+#[case(KeyVariant::DataResponse, 0xAD7B_FC8B_06AD_3A08_A560_B410_5CF8_D9E5)]
+fn variant_keys(#[case] variant: KeyVariant, #[case] expected: u128) {
+    let key = derive_key(&bdk(), ksn(1)).expect("valid KSN");
+    assert_eq!(
+        key.to_bytes(),
+        0x0426_66B4_9184_CFA3_68DE_9628_D039_7BC9_u128.to_be_bytes()
+    );
+    let variant = variant_key(&key, variant).expect("2TDEA key");
+    assert_eq!(variant.to_bytes(), expected.to_be_bytes());
+}
+
+/// The data request key is the masked transaction key encrypted with itself,
+/// half by half.
+#[test]
+fn data_key_one_way_step() {
+    let masked =
+        Key::from_parts(&[0x0426_66B4_917B_CFA3, 0x68DE_9628_D0C6_7BC9]).expect("valid key");
+    let cipher = masked.cipher();
+    assert_eq!(
+        cipher.encrypt_block(0x0426_66B4_917B_CFA3),
+        0x448D_3F07_6D83_0403
+    );
+    assert_eq!(
+        cipher.encrypt_block(0x68DE_9628_D0C6_7BC9),
+        0x6A55_A3D7_E005_5A78
+    );
+}
+
+/// Every counter with at most 10 of its 21 bits set is used once:
+/// 2^20 - 1 transactions in all.
+#[test]
+#[ignore = "derives over a million keys; run with --ignored"]
+fn terminal_runs_out_of_keys() {
+    let ipek = ipek(&bdk(), ksn(0)).expect("2TDEA BDK");
+    let mut terminal = Terminal::new(&ipek, ksn(0)).expect("2TDEA IPEK");
+    let mut last = None;
+    let mut count = 0;
+    while let Ok((ksn, key)) = terminal.next_key() {
+        last = Some((ksn, key));
+        count += 1;
+    }
+    assert_eq!(count, (1 << 20) - 1);
+
+    let (ksn, key) = last.expect("keys were issued");
+    assert_eq!(ksn.counter(), 0x1F_F800);
+    assert_ok_eq!(transaction_key(&ipek, ksn), key);
+    for _ in 0..3 {
+        assert_err_eq!(terminal.next_key(), DukptError::Exhausted);
+    }
+}
+
+#[test]
+fn variants_differ() {
+    let key = derive_key(&bdk(), ksn(1)).expect("valid KSN");
+    let variants = [
+        KeyVariant::PinEncryption,
+        KeyVariant::MacRequest,
+        KeyVariant::MacResponse,
+        KeyVariant::DataRequest,
+        KeyVariant::DataResponse,
+    ]
+    .map(|variant| variant_key(&key, variant).expect("2TDEA key"));
+    for (i, a) in variants.iter().enumerate() {
+        assert_ne!(a, &key);
+        for b in &variants[i + 1..] {
+            assert_ne!(a, b);
+        }
+    }
+    // The MAC request variant only flips the second-to-last byte of each half.
+    let mut expected = key.to_bytes();
+    expected[6] ^= 0xFF;
+    expected[14] ^= 0xFF;
+    assert_eq!(variants[1].to_bytes(), expected);
+}
+
+#[test]
+fn ksn_fields() {
+    let ksn: Ksn = "ffff9876543210e00abc".parse().expect("valid KSN");
+    assert_eq!(ksn.counter(), 0xABC);
+    assert_eq!(ksn.to_string(), "FFFF9876543210E00ABC");
+    assert_eq!(
+        ksn.with_counter(0x1F_FFFF).to_string(),
+        "FFFF9876543210FFFFFF"
+    );
+    assert_eq!(ksn.with_counter(0).to_string(), "FFFF9876543210E00000");
+}
+
+#[rstest]
+fn rejects_invalid_ksn(
+    #[values(
+        "",
+        "FFFF9876543210E0000",
+        "FFFF9876543210E000000",
+        "FFFF9876543210E0000G"
+    )]
+    s: &str,
+) {
+    assert_err_eq!(s.parse::<Ksn>(), DukptError::InvalidKsn);
+}
+
+#[test]
+fn rejects_invalid_input() {
+    assert_err_eq!(
+        transaction_key(&bdk(), ksn(0x7FF)),
+        DukptError::InvalidCounter(0x7FF)
+    );
+    let single = Key::single(0x0123_4567_89AB_CDEF);
+    assert_err_eq!(ipek(&single, ksn(0)), DukptError::KeyLength(8));
+    assert_err_eq!(
+        Terminal::new(&single, ksn(0)).map(|_| ()),
+        DukptError::KeyLength(8)
+    );
+}
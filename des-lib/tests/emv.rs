@@ -0,0 +1,127 @@
+use claims::{assert_err_eq, assert_ok, assert_ok_eq};
+use des_lib::{
+    emv::{EmvError, arpc_method1, arpc_method2, arqc, master_key, session_key, verify_arqc},
+    key::Key,
+    mac::MacPadding,
+};
+use rstest::rstest;
+
+fn key(left: u64, right: u64) -> Key {
+    Key::from_parts(&[left, right]).expect("valid key")
+}
+
+fn imk() -> Key {
+    key(0x0123_4567_89AB_CDEF, 0xFEDC_BA98_7654_3210)
+}
+
+/// ICC master key for PAN 5413330089600010, PSN 01.
+fn icc_master_key() -> Key {
+    key(0x438F_4A97_6EC8_0DB3, 0xF4D3_1C0D_CB32_A226)
+}
+
+/// Session key for ATC 001C.
+fn session() -> Key {
+    key(0x3158_B3AD_85AB_20B9, 0xC1D9_A79B_57D3_7F1F)
+}
+
+/// Amount, other amount, country, TVR, currency, date, type, unpredictable
+/// number, AIP, ATC and issuer application data.
+const TRANSACTION_DATA: [u8; 37] = [
+    0x00, 0x00, 0x00, 0x00, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x08, 0x40, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0x08, 0x40, 0x16, 0x05, 0x17, 0x00, 0x12, 0x34, 0x56, 0x78, 0x58, 0x00, 0x00,
+    0x1C, 0x03, 0xA0, 0x00, 0x00,
+];
+
+const ARQC: u64 = 0xCD8D_1200_EF1A_51A1;
+
+/// Expected values were computed with `openssl enc -des-ede` and `-des-ecb`.
+#[rstest]
+#[case("5413330089600010", 1, icc_master_key())]
+#[case(
+    "12345678901234567",
+    45,
+    key(0x946D_C1A8_C78A_6245, 0x19BC_4645_CB07_3737)
+)]
+#[case("541333008960", 0, key(0xD091_0ECE_388A_028F, 0xE064_15E3_0D32_9E91))]
+fn option_a_master_keys(#[case] pan: &str, #[case] psn: u8, #[case] expected: Key) {
+    let master = master_key(&imk(), pan, psn).expect("valid card");
+    assert_eq!(master.parts(), expected.parts());
+    assert!(master.has_odd_parity());
+}
+
+#[test]
+fn common_session_key() {
+    let key = session_key(&icc_master_key(), 0x001C).expect("valid key");
+    assert_eq!(key.parts(), session().parts());
+    assert!(key.has_odd_parity());
+}
+
+#[rstest]
+#[case(MacPadding::Method2, ARQC)]
+#[case(MacPadding::Method1, 0x4C6E_F77D_F15A_71FD)]
+fn arqc_values(#[case] padding: MacPadding, #[case] expected: u64) {
+    assert_ok_eq!(arqc(&session(), padding, &TRANSACTION_DATA), expected);
+    assert_ok!(verify_arqc(
+        &session(),
+        padding,
+        &TRANSACTION_DATA,
+        expected
+    ));
+}
+
+#[test]
+fn verify_arqc_rejects_changed_data() {
+    let mut data = TRANSACTION_DATA;
+    data[5] ^= 1;
+    assert_err_eq!(
+        verify_arqc(&session(), MacPadding::Method2, &data, ARQC),
+        EmvError::Mismatch
+    );
+    assert_err_eq!(
+        verify_arqc(&session(), MacPadding::Method2, &TRANSACTION_DATA, ARQC ^ 1),
+        EmvError::Mismatch
+    );
+}
+
+#[test]
+fn arpc_values() {
+    assert_ok_eq!(
+        arpc_method1(&session(), ARQC, 0x3030),
+        0x1A1F_A9BE_9734_4F20
+    );
+    assert_ok_eq!(
+        arpc_method2(&session(), ARQC, 0x0082_0000, &[]),
+        0x6F43_03AA
+    );
+    assert_ok_eq!(
+        arpc_method2(&session(), ARQC, 0x0082_0000, &[0x01, 0x02]),
+        0x3997_C10B
+    );
+}
+
+#[rstest]
+#[case("541333008960001", 100, EmvError::InvalidPsn(100))]
+#[case("54133300896", 1, EmvError::InvalidPan)]
+#[case("5413 3300 8960 0010", 1, EmvError::InvalidPan)]
+fn rejects_invalid_cards(#[case] pan: &str, #[case] psn: u8, #[case] error: EmvError) {
+    assert_err_eq!(master_key(&imk(), pan, psn), error);
+}
+
+#[test]
+fn rejects_long_proprietary_data() {
+    assert_err_eq!(
+        arpc_method2(&session(), ARQC, 0, &[0; 9]),
+        EmvError::ProprietaryDataLength(9)
+    );
+}
+
+#[rstest]
+fn requires_double_length_keys(#[values(1, 3)] parts: usize) {
+    let key = Key::from_parts(&[0x0123_4567_89AB_CDEF; 3][..parts]).expect("valid key");
+    let length = EmvError::KeyLength(parts * 8);
+    assert_err_eq!(master_key(&key, "5413330089600010", 1), length);
+    assert_err_eq!(session_key(&key, 1), length);
+    assert_err_eq!(arqc(&key, MacPadding::Method2, &TRANSACTION_DATA), length);
+    assert_err_eq!(arpc_method1(&key, ARQC, 0x3030), length);
+    assert_err_eq!(arpc_method2(&key, ARQC, 0, &[]), length);
+}
@@ -0,0 +1,44 @@
+use claims::{assert_matches, assert_ok_eq};
+use des_lib::encoding::{Encoding, EncodingError};
+use rstest::rstest;
+
+#[rstest]
+#[case(b"", "")]
+#[case(&[0x01, 0x23, 0xAB, 0xEF], "0123ABEF")]
+fn hex_roundtrip(#[case] bytes: &[u8], #[case] text: &str) {
+    assert_eq!(Encoding::Hex.encode(bytes), text);
+    assert_ok_eq!(Encoding::Hex.decode(text), bytes);
+}
+
+#[test]
+fn hex_accepts_lowercase() {
+    assert_ok_eq!(Encoding::Hex.decode("abcdef"), [0xAB, 0xCD, 0xEF]);
+}
+
+#[rstest]
+#[case("ABC", EncodingError::OddLength)]
+#[case("0x12", EncodingError::InvalidHex)]
+#[case("12 34", EncodingError::InvalidHex)]
+#[case("+F", EncodingError::InvalidHex)]
+#[case("GG", EncodingError::InvalidHex)]
+fn hex_rejects_malformed_input(#[case] text: &str, #[case] error: EncodingError) {
+    assert_eq!(Encoding::Hex.decode(text), Err(error));
+}
+
+#[cfg(feature = "base64")]
+#[rstest]
+#[case(b"", "")]
+#[case(b"hello", "aGVsbG8=")]
+#[case(&[0xFB, 0xFF], "+/8=")]
+fn base64_roundtrip(#[case] bytes: &[u8], #[case] text: &str) {
+    assert_eq!(Encoding::Base64.encode(bytes), text);
+    assert_ok_eq!(Encoding::Base64.decode(text), bytes);
+}
+
+#[cfg(feature = "base64")]
+#[rstest]
+#[case("aGVsbG8")]
+#[case("aGVs*G8=")]
+fn base64_rejects_malformed_input(#[case] text: &str) {
+    assert_matches!(Encoding::Base64.decode(text), Err(EncodingError::Base64(_)));
+}
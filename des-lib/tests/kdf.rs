@@ -0,0 +1,114 @@
+#![cfg(feature = "kdf")]
+
+use claims::assert_matches;
+use des_lib::{
+    encoding::Encoding,
+    kdf::{KdfError, KdfParams, Prf, derive_key},
+    key::{Key, KeyLength},
+};
+use rstest::rstest;
+
+/// Leading bytes of the RFC 7914 section 11 PBKDF2-HMAC-SHA256 vectors, as
+/// a DES key with odd parity.
+#[rstest]
+#[case(b"passwd", b"salt", 1, 0x55AC_046E_56E3_089F)]
+#[case(b"Password", b"NaCl", 80_000, 0x4DDC_D8F6_0B98_BE21)]
+fn pbkdf2_sha256_vectors(
+    #[case] passphrase: &[u8],
+    #[case] salt: &[u8],
+    #[case] iterations: u32,
+    #[case] expected: u64,
+) {
+    let key = derive_key(
+        Prf::HmacSha256,
+        passphrase,
+        salt,
+        iterations,
+        KeyLength::Single,
+    );
+    assert_eq!(key, Key::single(expected).with_odd_parity());
+}
+
+#[test]
+fn salt_changes_key() {
+    let derive = |salt| derive_key(Prf::HmacSha256, b"secret", salt, 1000, KeyLength::Single);
+    assert_ne!(derive(b"salt-a"), derive(b"salt-b"));
+}
+
+/// RFC 6070 (SHA1) and RFC 7914 (SHA256) outputs with odd parity applied.
+#[rstest]
+#[case(Prf::HmacSha1, 1, KeyLength::Single, "0D61C80E971F0E70")]
+#[case(
+    Prf::HmacSha1,
+    2,
+    KeyLength::Double,
+    "EA6D014CC72C6E8CCD1FD92ACE1C40F1"
+)]
+#[case(
+    Prf::HmacSha256,
+    1,
+    KeyLength::Triple,
+    "130EB6CEFDF8B32C43E6235257C4F837A86449C82CCD3449"
+)]
+fn derive_key_vectors(
+    #[case] prf: Prf,
+    #[case] iterations: u32,
+    #[case] length: KeyLength,
+    #[case] expected: &str,
+) {
+    let key = derive_key(prf, b"password", b"salt", iterations, length);
+    let expected = Encoding::Hex.decode(expected).expect("valid hex");
+
+    assert_eq!(key.length(), length);
+    assert!(key.has_odd_parity());
+    assert_eq!(key, Key::from_bytes(&expected).expect("valid length"));
+}
+
+#[test]
+fn params_roundtrip() {
+    let params = KdfParams::generate(Prf::HmacSha1, KeyLength::Triple, 1000);
+    assert_eq!(params.salt().len(), 16);
+
+    let mut bytes = params.to_bytes();
+    bytes.extend_from_slice(b"ciphertext");
+    let mut reader = bytes.as_slice();
+    let read = KdfParams::read(&mut reader).expect("valid header");
+
+    assert_eq!(read, params);
+    assert_eq!(reader, b"ciphertext");
+    assert_eq!(read.derive(b"secret"), params.derive(b"secret"));
+    assert_ne!(
+        params.derive(b"secret"),
+        KdfParams::generate(Prf::HmacSha1, KeyLength::Triple, 1000).derive(b"secret"),
+        "Random salts produced the same key"
+    );
+}
+
+#[test]
+fn invalid_headers() {
+    let valid = KdfParams::new(Prf::HmacSha256, KeyLength::Single, 1, b"salt").to_bytes();
+    let with = |index: usize, byte: u8| {
+        let mut bytes = valid.clone();
+        bytes[index] = byte;
+        bytes
+    };
+
+    assert_matches!(
+        KdfParams::read(&mut &b"0123456789ABCDEF"[..]),
+        Err(KdfError::MissingHeader)
+    );
+    assert_matches!(
+        KdfParams::read(&mut &with(6, 2)[..]),
+        Err(KdfError::UnsupportedVersion(2))
+    );
+    for (index, byte) in [(7, 0), (8, 4), (12, 0)] {
+        assert_matches!(
+            KdfParams::read(&mut &with(index, byte)[..]),
+            Err(KdfError::InvalidHeader)
+        );
+    }
+    assert_matches!(
+        KdfParams::read(&mut &valid[..valid.len() - 1]),
+        Err(KdfError::InvalidHeader)
+    );
+}
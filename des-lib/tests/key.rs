@@ -0,0 +1,135 @@
+use claims::{assert_err_eq, assert_ok};
+use des_lib::key::{KcvMethod, Key, KeyError, KeyLength, WEAK_KEYS, is_weak_key, set_odd_parity};
+use rstest::rstest;
+
+#[rstest]
+#[case(0x0000_0000_0000_0000, 0x0101_0101_0101_0101)]
+#[case(0xFFFF_FFFF_FFFF_FFFF, 0xFEFE_FEFE_FEFE_FEFE)]
+#[case(0x0123_4567_89AB_CDEF, 0x0123_4567_89AB_CDEF)]
+#[case(0x1334_5779_9BBC_DFF1, 0x1334_5779_9BBC_DFF1)]
+#[case(0x0022_4466_88AA_CCEE, 0x0123_4567_89AB_CDEF)]
+fn odd_parity(#[case] key: u64, #[case] expected: u64) {
+    assert_eq!(set_odd_parity(key), expected);
+}
+
+#[test]
+fn weak_keys_detected_regardless_of_parity() {
+    for key in WEAK_KEYS {
+        assert!(is_weak_key(key), "{key:016X} not detected");
+        assert!(is_weak_key(key & !0x0101_0101_0101_0101));
+    }
+    assert!(!is_weak_key(0x0123_4567_89AB_CDEF));
+}
+
+#[rstest]
+#[case(&[0x0123_4567_89AB_CDEF], [0xD5, 0xD4, 0x4F])]
+#[case(&[0x0123_4567_89AB_CDEF, 0xFEDC_BA98_7654_3210], [0x08, 0xD7, 0xB4])]
+fn key_check_value(#[case] parts: &[u64], #[case] expected: [u8; 3]) {
+    let key = Key::from_parts(parts).expect("valid length");
+    assert_eq!(key.kcv(), expected);
+}
+
+#[rstest]
+#[case(&[0x0123_4567_89AB_CDEF], KcvMethod::ZeroBlock, &[0xD5, 0xD4, 0x4F])]
+#[case(&[0x0123_4567_89AB_CDEF], KcvMethod::Cmac, &[0x03, 0xC8, 0x15, 0x3A, 0xD5])]
+#[case(
+    &[0x0123_4567_89AB_CDEF, 0xFEDC_BA98_7654_3210],
+    KcvMethod::Cmac,
+    &[0x0A, 0x82, 0x45, 0x86, 0x64]
+)]
+fn check_value_methods(#[case] parts: &[u64], #[case] method: KcvMethod, #[case] expected: &[u8]) {
+    let key = Key::from_parts(parts).expect("valid length");
+    assert_eq!(key.check_value(method), expected);
+}
+
+#[rstest]
+#[case(&[0x0101_0101_0101_0101], KeyError::WeakKey(1))]
+#[case(&[0x0123_4567_89AB_CDEF, 0x1F01_1F01_0E01_0E01], KeyError::WeakKey(2))]
+#[case(&[0x0123_4567_89AB_CDEF, 0x0123_4567_89AB_CDEE], KeyError::RepeatedPart(1, 2))]
+#[case(
+    &[0x0123_4567_89AB_CDEF, 0xFEDC_BA98_7654_3210, 0x0123_4567_89AB_CDEF],
+    KeyError::RepeatedPart(1, 3)
+)]
+fn validation_rejects(#[case] parts: &[u64], #[case] error: KeyError) {
+    let key = Key::from_parts(parts).expect("valid length");
+    assert_err_eq!(key.validate(), error);
+}
+
+#[rstest]
+#[case(0)]
+#[case(7)]
+#[case(32)]
+fn invalid_byte_length(#[case] len: usize) {
+    assert_err_eq!(Key::from_bytes(&vec![0; len]), KeyError::InvalidLength(len));
+}
+
+#[rstest]
+fn generated_keys_are_valid(
+    #[values(KeyLength::Single, KeyLength::Double, KeyLength::Triple)] length: KeyLength,
+) {
+    for _ in 0..100 {
+        let key = Key::generate(length);
+        assert_eq!(key.length(), length);
+        assert_eq!(key.to_bytes().len(), length.bytes());
+        assert!(key.has_odd_parity(), "Generated key without odd parity");
+        assert_ok!(key.validate());
+        assert_eq!(Key::from_bytes(&key.to_bytes()), Ok(key));
+    }
+}
+
+#[test]
+fn debug_hides_key_material() {
+    let key = Key::from_parts(&[0x0123_4567_89AB_CDEF]).expect("valid length");
+    let debug = format!("{key:?}");
+    assert!(!debug.contains("0123"), "Key material in {debug}");
+    assert!(debug.contains("D5D44F"), "KCV missing from {debug}");
+}
+
+#[rstest]
+fn split_and_combine(
+    #[values(KeyLength::Single, KeyLength::Double, KeyLength::Triple)] length: KeyLength,
+    #[values(2, 3, 5)] count: usize,
+) {
+    let key = Key::generate(length);
+    let components = key.split(count).expect("valid count");
+
+    assert_eq!(components.len(), count);
+    for component in &components {
+        assert_eq!(component.length(), length);
+        assert!(component.has_odd_parity(), "Component without odd parity");
+        assert_ne!(component, &key, "Component equals the key");
+    }
+    assert_eq!(Key::combine(&components), Ok(key));
+}
+
+#[test]
+fn combine_fixes_parity() {
+    let first = Key::from_parts(&[0x0123_4567_89AB_CDEF]).expect("valid length");
+    let second = Key::from_parts(&[0x0101_0101_0101_0101]).expect("valid length");
+    let combined = Key::combine(&[first, second]).expect("valid components");
+
+    assert!(combined.has_odd_parity());
+    assert_eq!(combined.kcv(), [0xD5, 0xD4, 0x4F]);
+    assert_ok!(combined.verify_check_value(KcvMethod::ZeroBlock, &[0xD5, 0xD4, 0x4F]));
+    assert_err_eq!(
+        combined.verify_check_value(KcvMethod::ZeroBlock, &[0, 0, 0]),
+        KeyError::KcvMismatch
+    );
+}
+
+#[test]
+fn combine_rejects_invalid_components() {
+    let single = Key::from_parts(&[0x0123_4567_89AB_CDEF]).expect("valid length");
+    let double = Key::generate(KeyLength::Double);
+
+    assert_err_eq!(Key::combine(&[]), KeyError::TooFewComponents(0));
+    assert_err_eq!(
+        Key::combine(std::slice::from_ref(&single)),
+        KeyError::TooFewComponents(1)
+    );
+    assert_err_eq!(
+        Key::combine(&[single.clone(), double]),
+        KeyError::MismatchedComponents
+    );
+    assert_err_eq!(single.split(1), KeyError::TooFewComponents(1));
+}
@@ -0,0 +1,308 @@
+#![cfg(feature = "rand")]
+
+use claims::{assert_err_eq, assert_ok, assert_ok_eq};
+use des_lib::{
+    encoding::Encoding,
+    key::Key,
+    keyblock::{
+        Algorithm, Exportability, Header, KeyBlockError, ModeOfUse, OptionalBlock, Usage, Version,
+        inspect, unwrap, wrap, wrap_with,
+    },
+};
+use rand::RngCore;
+use rstest::rstest;
+
+/// Padding of zeros, so that wrapped blocks are reproducible.
+struct Zeros;
+
+impl RngCore for Zeros {
+    fn next_u32(&mut self) -> u32 {
+        0
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        0
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        dest.fill(0);
+    }
+}
+
+fn key(hex: &str) -> Key {
+    Key::from_bytes(&Encoding::Hex.decode(hex).expect("valid hex")).expect("valid key")
+}
+
+const KEY: &str = "F039121BEC83D26B169BDCD5B22AAF8F";
+
+/// Built independently with `openssl mac CMAC` for the version B key
+/// derivation and MAC, and `openssl enc -des-ede-cbc` / `-des-ede3-cbc` for
+/// the encryption, with zero padding.
+#[rstest]
+#[case(
+    "89E88CF7931444F334BD7547FC3F380C",
+    Header::new(Usage::PIN_ENCRYPTION, Algorithm::Tdea, ModeOfUse::Encrypt),
+    "B0080P0TE00E0000F0CA30986E4EB77037AC13356385C3607407F9C7F9E94482CCAAD6BC9A8E3A2A"
+)]
+#[case(
+    "89E88CF7931444F334BD7547FC3F380C",
+    Header {
+        version: Version::C,
+        ..Header::new(Usage::PIN_ENCRYPTION, Algorithm::Tdea, ModeOfUse::Encrypt)
+    },
+    "C0072P0TE00E00008B82F9211C29FE6DD2676D270A2256238D1144D538C390A697789361"
+)]
+#[case(
+    "0123456789ABCDEFFEDCBA987654321089ABCDEF01234567",
+    Header {
+        exportability: Exportability::NonExportable,
+        optional_blocks: vec![OptionalBlock {
+            id: "KS".to_owned(),
+            data: "FFFF9876543210E00000".to_owned(),
+        }],
+        ..Header::new(Usage::DUKPT_INITIAL_KEY, Algorithm::Tdea, ModeOfUse::Derive)
+    },
+    "B0104B1TX00N0100KS18FFFF9876543210E00000C262C10B11342B4F5E7392D021CCF6C2E988389ED45C0288AA5B7DBF3F1D0139"
+)]
+fn known_blocks(#[case] kbpk: &str, #[case] header: Header, #[case] block: &str) {
+    let kbpk = key(kbpk);
+    assert_ok_eq!(wrap_with(&mut Zeros, &kbpk, &header, &key(KEY)), block);
+    assert_ok_eq!(unwrap(&kbpk, block), (header.clone(), key(KEY)));
+    assert_ok_eq!(inspect(block), header);
+}
+
+/// The TDEA examples of ANSI X9 TR-31:2018 annex A, one per version.
+#[rstest]
+#[case(
+    "89E88CF7931444F334BD7547FC3F380C",
+    "A0072P0TE00E0000F5161ED902807AF26F1D62263644BD24192FDB3193C730301CEE8701",
+    Header {
+        version: Version::A,
+        ..Header::new(Usage::PIN_ENCRYPTION, Algorithm::Tdea, ModeOfUse::Encrypt)
+    },
+    "F039121BEC83D26B169BDCD5B22AAF8F"
+)]
+#[case(
+    "DD7515F2BFC17F85CE48F3CA25CB21F6",
+    "B0080P0TE00E000094B420079CC80BA3461F86FE26EFC4A3B8E4FA4C5F5341176EED7B727B8A248E",
+    Header::new(Usage::PIN_ENCRYPTION, Algorithm::Tdea, ModeOfUse::Encrypt),
+    "3F419E1CB7079442AA37474C2EFBF8B8"
+)]
+#[case(
+    "B8ED59E0A279A295E9F5ED7944FD06B9",
+    "C0096B0TX12S0100KS1800604B120F9292800000BFB9B689CB567E66FC3FEE5AD5F52161FC6545B9D60989015D02155C",
+    Header {
+        version: Version::C,
+        key_version: "12".to_owned(),
+        exportability: Exportability::Sensitive,
+        optional_blocks: vec![OptionalBlock {
+            id: "KS".to_owned(),
+            data: "00604B120F9292800000".to_owned(),
+        }],
+        ..Header::new(Usage::BDK, Algorithm::Tdea, ModeOfUse::Derive)
+    },
+    "EDB380DD340BC2620247D445F5B8D678"
+)]
+fn published_blocks(
+    #[case] kbpk: &str,
+    #[case] block: &str,
+    #[case] header: Header,
+    #[case] wrapped: &str,
+) {
+    assert_ok_eq!(unwrap(&key(kbpk), block), (header, key(wrapped)));
+}
+
+#[rstest]
+fn wrap_and_unwrap(
+    #[values(Version::A, Version::B, Version::C)] version: Version,
+    #[values(1, 2, 3)] parts: usize,
+    #[values(0, 1, 2)] blocks: usize,
+) {
+    let kbpk = key("0123456789ABCDEFFEDCBA987654321089ABCDEF01234567");
+    let wrapped = Key::from_parts(
+        &[
+            0x0123_4567_89AB_CDEF,
+            0x1032_5476_98BA_DCFE,
+            0x4567_89AB_CDEF_0123,
+        ][..parts],
+    )
+    .expect("valid key");
+    let header = Header {
+        version,
+        key_version: "c1".to_owned(),
+        optional_blocks: (0..blocks)
+            .map(|i| OptionalBlock {
+                id: format!("T{i}"),
+                data: "x".repeat(i * 3 + 1),
+            })
+            .collect(),
+        ..Header::new(
+            Usage::MAC_ALGORITHM_3,
+            if parts == 1 {
+                Algorithm::Des
+            } else {
+                Algorithm::Tdea
+            },
+            ModeOfUse::Calculate,
+        )
+    };
+    let block = wrap(&kbpk, &header, &wrapped).expect("valid header");
+    assert_eq!(block.len(), block[1..5].parse::<usize>().expect("length"));
+    assert_ok_eq!(unwrap(&kbpk, &block), (header, wrapped));
+}
+
+#[rstest]
+// Key data.
+#[case(20)]
+// MAC.
+#[case(79)]
+fn detects_tampering(#[case] position: usize) {
+    let kbpk = key("89E88CF7931444F334BD7547FC3F380C");
+    let mut block =
+        "B0080P0TE00E0000F0CA30986E4EB77037AC13356385C3607407F9C7F9E94482CCAAD6BC9A8E3A2A"
+            .to_owned();
+    let replacement = if &block[position..=position] == "0" {
+        "1"
+    } else {
+        "0"
+    };
+    block.replace_range(position..=position, replacement);
+    assert_err_eq!(unwrap(&kbpk, &block), KeyBlockError::Authentication);
+}
+
+#[test]
+fn header_is_authenticated() {
+    let kbpk = key("89E88CF7931444F334BD7547FC3F380C");
+    // The same block, relabelled from P0 encrypt-only to D0 both ways.
+    let block = "B0080D0TB00E0000F0CA30986E4EB77037AC13356385C3607407F9C7F9E94482CCAAD6BC9A8E3A2A";
+    assert_ok!(inspect(block));
+    assert_err_eq!(unwrap(&kbpk, block), KeyBlockError::Authentication);
+    assert_err_eq!(
+        unwrap(
+            &key("0123456789ABCDEFFEDCBA9876543210"),
+            &block.replacen("D0TB", "P0TE", 1)
+        ),
+        KeyBlockError::Authentication
+    );
+}
+
+#[rstest]
+#[case("B0080P0TE00E", KeyBlockError::Malformed("too short"))]
+#[case(
+    "D0032P0AE00E0000000000000000000000",
+    KeyBlockError::UnsupportedVersion('D')
+)]
+#[case(
+    "B0081P0TE00E0000F0CA30986E4EB77037AC13356385C3607407F9C7F9E94482CCAAD6BC9A8E3A2A",
+    KeyBlockError::Malformed("length does not match the header")
+)]
+#[case(
+    "B0080P0!E00E0000F0CA30986E4EB77037AC13356385C3607407F9C7F9E94482CCAAD6BC9A8E3A2A",
+    KeyBlockError::InvalidField("algorithm")
+)]
+#[case(
+    "B0080P0TQ00E0000F0CA30986E4EB77037AC13356385C3607407F9C7F9E94482CCAAD6BC9A8E3A2A",
+    KeyBlockError::InvalidField("mode of use")
+)]
+#[case(
+    "B0080P0TE00Q0000F0CA30986E4EB77037AC13356385C3607407F9C7F9E94482CCAAD6BC9A8E3A2A",
+    KeyBlockError::InvalidField("exportability")
+)]
+#[case(
+    "B0080P0TE00E0100F0CA30986E4EB77037AC13356385C3607407F9C7F9E94482CCAAD6BC9A8E3A2A",
+    KeyBlockError::InvalidField("optional block")
+)]
+#[case(
+    "B0080P0TE00E0000F0CA30986E4EB77037AC13356385C3607407F9C7F9E94482CCAAD6BC9A8E3A2Z",
+    KeyBlockError::Malformed("MAC")
+)]
+#[case(
+    "B0072P0TE00E0000F0CA30986E4EB77037AC13356385C3607407F9C7F9E94482CCAAD6BC",
+    KeyBlockError::Malformed("key data length")
+)]
+fn rejects_malformed_blocks(#[case] block: &str, #[case] error: KeyBlockError) {
+    assert_err_eq!(inspect(block), error);
+}
+
+/// Version C blocks with a valid MAC whose header algorithm does not match
+/// the key, built with `openssl enc -des-ede-cbc`.
+#[rstest]
+// TDEA header, single-length key.
+#[case("C0056P0TE00E00003102D4C61A175528FCF67F7CA7FAF639CA87D01B")]
+// DES header, 2TDEA key.
+#[case("C0072P0DE00E0000E4ADED546D2E5A34D268FEF0452F210124C65CF80B11A2F7617EB8BD")]
+fn unwrap_rejects_algorithm_mismatch(#[case] block: &str) {
+    assert_err_eq!(
+        unwrap(&key("89E88CF7931444F334BD7547FC3F380C"), block),
+        KeyBlockError::InvalidField("algorithm")
+    );
+}
+
+#[test]
+fn rejects_invalid_wrapping() {
+    let kbpk = key("89E88CF7931444F334BD7547FC3F380C");
+    let header = Header::new(Usage::PIN_ENCRYPTION, Algorithm::Tdea, ModeOfUse::Encrypt);
+    assert_err_eq!(
+        wrap(&key("0123456789ABCDEF"), &header, &key(KEY)),
+        KeyBlockError::KbpkLength(8)
+    );
+    assert_err_eq!(
+        wrap(
+            &kbpk,
+            &Header {
+                algorithm: Algorithm::Aes,
+                ..header.clone()
+            },
+            &key(KEY)
+        ),
+        KeyBlockError::UnsupportedAlgorithm(Algorithm::Aes)
+    );
+    assert_err_eq!(
+        wrap(&kbpk, &header, &key("0123456789ABCDEF")),
+        KeyBlockError::InvalidField("algorithm")
+    );
+    assert_err_eq!(
+        wrap(
+            &kbpk,
+            &Header {
+                key_version: "0".to_owned(),
+                ..header.clone()
+            },
+            &key(KEY)
+        ),
+        KeyBlockError::InvalidField("key version")
+    );
+    let padding = OptionalBlock {
+        id: OptionalBlock::PADDING.to_owned(),
+        data: String::new(),
+    };
+    assert_err_eq!(
+        wrap(
+            &kbpk,
+            &Header {
+                optional_blocks: vec![padding],
+                ..header
+            },
+            &key(KEY)
+        ),
+        KeyBlockError::InvalidField("optional block")
+    );
+}
+
+#[test]
+fn usage_codes() {
+    assert_ok_eq!("P0".parse::<Usage>(), Usage::PIN_ENCRYPTION);
+    assert_eq!(Usage::PIN_ENCRYPTION.description(), Some("PIN encryption"));
+    assert_eq!(
+        "42".parse::<Usage>().expect("proprietary").description(),
+        None
+    );
+    assert_err_eq!(
+        "P".parse::<Usage>(),
+        KeyBlockError::InvalidField("key usage")
+    );
+    assert_err_eq!(
+        "P-".parse::<Usage>(),
+        KeyBlockError::InvalidField("key usage")
+    );
+}
@@ -0,0 +1,188 @@
+use claims::{assert_err_eq, assert_ok};
+use des_lib::{
+    key::{Key, KeyLength},
+    keystore::{Entry, KeyUsage, Keystore, KeystoreError},
+};
+use rstest::rstest;
+
+const PASSPHRASE: &[u8] = b"correct horse battery staple";
+
+/// Low iteration count to keep the tests fast.
+const ITERATIONS: u32 = 16;
+
+#[rstest]
+fn store_and_load(
+    #[values(KeyLength::Single, KeyLength::Double, KeyLength::Triple)] length: KeyLength,
+) {
+    let (mut keystore, master) = Keystore::create(PASSPHRASE, ITERATIONS);
+    let key = Key::generate(length);
+    assert_ok!(keystore.add(&master, "zmk", &key, KeyUsage::KeyWrapping));
+
+    let entry = keystore.entry("zmk").expect("stored key");
+    assert_eq!(entry.length(), length);
+    assert_eq!(entry.usage(), KeyUsage::KeyWrapping);
+    assert_eq!(entry.kcv(), key.kcv());
+    assert_eq!(keystore.key(&master, "zmk"), Ok(key));
+}
+
+#[test]
+fn roundtrip_through_text() {
+    let (mut keystore, master) = Keystore::create(PASSPHRASE, ITERATIONS);
+    let first = Key::generate(KeyLength::Double);
+    let second = Key::generate(KeyLength::Single);
+    assert_ok!(keystore.add(&master, "first", &first, KeyUsage::General));
+    assert_ok!(keystore.add(&master, "second", &second, KeyUsage::Mac));
+
+    let text = keystore.to_string();
+    assert!(text.starts_with("des-keystore 1\n"), "{text}");
+    let parsed = text.parse::<Keystore>().expect("valid keystore");
+    assert_eq!(parsed, keystore);
+
+    let master = parsed.unlock(PASSPHRASE).expect("correct passphrase");
+    assert_eq!(parsed.key(&master, "first"), Ok(first));
+    assert_eq!(parsed.key(&master, "second"), Ok(second));
+    assert_eq!(
+        parsed.entries().iter().map(Entry::name).collect::<Vec<_>>(),
+        ["first", "second"]
+    );
+}
+
+#[test]
+fn wrong_passphrase() {
+    let (keystore, _) = Keystore::create(PASSPHRASE, ITERATIONS);
+    assert_err_eq!(keystore.unlock(b"wrong"), KeystoreError::WrongPassphrase);
+}
+
+#[test]
+fn tampered_entries_are_rejected() {
+    let (mut keystore, master) = Keystore::create(PASSPHRASE, ITERATIONS);
+    let key = Key::generate(KeyLength::Double);
+    assert_ok!(keystore.add(&master, "data", &key, KeyUsage::Mac));
+
+    // Upgrading the usage must invalidate the tag.
+    let text = keystore.to_string().replace(" mac ", " general ");
+    let tampered = text.parse::<Keystore>().expect("well-formed keystore");
+    assert_err_eq!(
+        tampered.key(&master, "data"),
+        KeystoreError::Tampered("data".into())
+    );
+
+    // So must renaming the entry.
+    let text = keystore.to_string().replace("key data ", "key other ");
+    let tampered = text.parse::<Keystore>().expect("well-formed keystore");
+    assert_err_eq!(
+        tampered.key(&master, "other"),
+        KeystoreError::Tampered("other".into())
+    );
+}
+
+#[test]
+fn removed_and_rolled_back_entries_are_rejected() {
+    let (mut keystore, master) = Keystore::create(PASSPHRASE, ITERATIONS);
+    let old = Key::generate(KeyLength::Double);
+    assert_ok!(keystore.add(&master, "zpk", &old, KeyUsage::PinEncryption));
+    assert_ok!(keystore.add(&master, "mac", &old, KeyUsage::Mac));
+    let before = keystore.to_string();
+
+    let line = |text: &str, name: &str| {
+        text.lines()
+            .find(|line| line.starts_with(&format!("key {name} ")))
+            .expect("stored key")
+            .to_owned()
+    };
+
+    // Deleting an entry line.
+    let text = before.replace(&format!("{}\n", line(&before, "mac")), "");
+    let tampered = text.parse::<Keystore>().expect("well-formed keystore");
+    assert_err_eq!(tampered.unlock(PASSPHRASE), KeystoreError::Modified);
+    assert_err_eq!(tampered.key(&master, "zpk"), KeystoreError::Modified);
+
+    // Replacing a rotated key with its older, individually valid entry.
+    assert_ok!(keystore.remove(&master, "zpk"));
+    let new = Key::generate(KeyLength::Double);
+    assert_ok!(keystore.add(&master, "zpk", &new, KeyUsage::PinEncryption));
+    let after = keystore.to_string();
+    let text = after.replace(&line(&after, "zpk"), &line(&before, "zpk"));
+    let tampered = text.parse::<Keystore>().expect("well-formed keystore");
+    assert_err_eq!(tampered.unlock(PASSPHRASE), KeystoreError::Modified);
+    assert_err_eq!(tampered.key(&master, "zpk"), KeystoreError::Modified);
+
+    let master = keystore.unlock(PASSPHRASE).expect("untouched keystore");
+    assert_eq!(keystore.key(&master, "zpk"), Ok(new));
+}
+
+#[test]
+fn names_and_lookups() {
+    let (mut keystore, master) = Keystore::create(PASSPHRASE, ITERATIONS);
+    let key = Key::generate(KeyLength::Single);
+
+    assert_err_eq!(
+        keystore.add(&master, "", &key, KeyUsage::General),
+        KeystoreError::InvalidName(String::new())
+    );
+    assert_err_eq!(
+        keystore.add(&master, "two words", &key, KeyUsage::General),
+        KeystoreError::InvalidName("two words".into())
+    );
+    assert_ok!(keystore.add(&master, "pek", &key, KeyUsage::Encryption));
+    assert_err_eq!(
+        keystore.add(&master, "pek", &key, KeyUsage::General),
+        KeystoreError::DuplicateName("pek".into())
+    );
+    assert_err_eq!(
+        keystore.key(&master, "missing"),
+        KeystoreError::NotFound("missing".into())
+    );
+
+    let removed = keystore.remove(&master, "pek").expect("stored key");
+    assert_eq!(removed.name(), "pek");
+    assert!(keystore.entries().is_empty());
+}
+
+#[rstest]
+#[case(KeyUsage::General, KeyUsage::Mac, true)]
+#[case(KeyUsage::Encryption, KeyUsage::Encryption, true)]
+#[case(KeyUsage::Encryption, KeyUsage::Mac, false)]
+#[case(KeyUsage::KeyWrapping, KeyUsage::Encryption, false)]
+#[case(KeyUsage::PinEncryption, KeyUsage::Encryption, false)]
+#[case(KeyUsage::CardVerification, KeyUsage::CardVerification, true)]
+#[case(KeyUsage::ApplicationCryptogram, KeyUsage::CardVerification, false)]
+fn usage_permissions(#[case] stored: KeyUsage, #[case] requested: KeyUsage, #[case] allowed: bool) {
+    let (mut keystore, master) = Keystore::create(PASSPHRASE, ITERATIONS);
+    assert_ok!(keystore.add(&master, "key", &Key::generate(KeyLength::Single), stored));
+    let entry = keystore.entry("key").expect("stored key");
+    assert_eq!(entry.check_usage(requested).is_ok(), allowed);
+}
+
+#[rstest]
+#[case("", 1)]
+#[case("des-keystore 2\n", 1)]
+#[case("des-keystore 1\n", 2)]
+#[case("des-keystore 1\nkdf scrypt 16 00\n", 2)]
+#[case(
+    "des-keystore 1\nkdf pbkdf2-sha256 0 00112233445566778899AABBCCDDEEFF\n",
+    2
+)]
+#[case(
+    "des-keystore 1\nkdf pbkdf2-sha256 4294967295 00112233445566778899AABBCCDDEEFF\nverifier 0000000000000000\n",
+    2
+)]
+#[case(
+    "des-keystore 1\nkdf pbkdf2-sha256 16 00112233445566778899AABBCCDDEEFF\n",
+    3
+)]
+#[case(
+    "des-keystore 1\nkdf pbkdf2-sha256 16 00112233445566778899AABBCCDDEEFF\nverifier 0000000000000000\nkey a des general 0 D5D44F 0000000000000000 00 0000000000000000\n",
+    4
+)]
+#[case(
+    "des-keystore 1\nkdf pbkdf2-sha256 16 00112233445566778899AABBCCDDEEFF\nverifier 0000000000000000\n",
+    4
+)]
+#[case(
+    "des-keystore 1\nkdf pbkdf2-sha256 16 00112233445566778899AABBCCDDEEFF\nverifier 0000000000000000\nmac 0000000000000000\nmac 0000000000000000\n",
+    5
+)]
+fn malformed_files(#[case] text: &str, #[case] line: usize) {
+    assert_err_eq!(text.parse::<Keystore>(), KeystoreError::Format(line));
+}
@@ -0,0 +1,140 @@
+use claims::{assert_err_eq, assert_ok};
+use des_lib::{
+    key::Key,
+    mac::{MacAlgorithm, MacError, MacPadding, mac, verify},
+};
+use rstest::rstest;
+
+const MESSAGE: &[u8] = b"Now is the time for all ";
+
+fn key(parts: &[u64]) -> Key {
+    Key::from_parts(parts).expect("valid key")
+}
+
+/// ISO/IEC 9797-1 Annex B examples, with K = 0123456789ABCDEF and
+/// K' = FEDCBA9876543210.
+#[rstest]
+#[case(MacAlgorithm::Cbc, MacPadding::Method1, MESSAGE, 0x70A3_0640_CC76_DD8B)]
+#[case(MacAlgorithm::Cbc, MacPadding::Method2, MESSAGE, 0x10E1_F0F1_0834_1B6D)]
+#[case(MacAlgorithm::Cbc, MacPadding::Method3, MESSAGE, 0x2C58_FB8F_F12A_AEAC)]
+#[case(MacAlgorithm::Cbc, MacPadding::Method1, b"", 0xD5D4_4FF7_2068_3D0D)]
+#[case(
+    MacAlgorithm::Retail,
+    MacPadding::Method1,
+    MESSAGE,
+    0xA1C7_2E74_EA3F_A9B6
+)]
+#[case(
+    MacAlgorithm::Retail,
+    MacPadding::Method2,
+    MESSAGE,
+    0xE908_6230_CA3B_E796
+)]
+#[case(
+    MacAlgorithm::Retail,
+    MacPadding::Method3,
+    MESSAGE,
+    0xAB05_9463_D7A7_D170
+)]
+#[case(MacAlgorithm::Retail, MacPadding::Method1, b"", 0x08D7_B4FB_629D_0885)]
+#[case(
+    MacAlgorithm::Retail,
+    MacPadding::Method3,
+    b"Now is",
+    0x5949_2B91_346A_C2EA
+)]
+fn iso_9797_vectors(
+    #[case] algorithm: MacAlgorithm,
+    #[case] padding: MacPadding,
+    #[case] message: &[u8],
+    #[case] expected: u64,
+) {
+    let key = match algorithm {
+        MacAlgorithm::Cbc => key(&[0x0123_4567_89AB_CDEF]),
+        MacAlgorithm::Retail => key(&[0x0123_4567_89AB_CDEF, 0xFEDC_BA98_7654_3210]),
+    };
+    assert_eq!(mac(&key, algorithm, padding, message), Ok(expected));
+}
+
+#[test]
+fn method1_ignores_trailing_zeros() {
+    let key = key(&[0x0123_4567_89AB_CDEF]);
+    assert_eq!(
+        mac(&key, MacAlgorithm::Cbc, MacPadding::Method1, b"abc"),
+        mac(&key, MacAlgorithm::Cbc, MacPadding::Method1, b"abc\0")
+    );
+    assert_ne!(
+        mac(&key, MacAlgorithm::Cbc, MacPadding::Method2, b"abc"),
+        mac(&key, MacAlgorithm::Cbc, MacPadding::Method2, b"abc\0")
+    );
+}
+
+#[test]
+fn cbc_mac_with_double_key_uses_tdea() {
+    let key = key(&[0x0123_4567_89AB_CDEF, 0xFEDC_BA98_7654_3210]);
+    let single = self::key(&[0x0123_4567_89AB_CDEF]);
+    assert_ne!(
+        mac(&key, MacAlgorithm::Cbc, MacPadding::Method1, MESSAGE),
+        mac(&single, MacAlgorithm::Cbc, MacPadding::Method1, MESSAGE)
+    );
+}
+
+#[rstest]
+fn retail_requires_double_key(
+    #[values(
+        &[0x0123_4567_89AB_CDEF][..],
+        &[0x0123_4567_89AB_CDEF, 0xFEDC_BA98_7654_3210, 0x89AB_CDEF_0123_4567][..]
+    )]
+    parts: &[u64],
+) {
+    assert_err_eq!(
+        mac(
+            &key(parts),
+            MacAlgorithm::Retail,
+            MacPadding::Method1,
+            MESSAGE
+        ),
+        MacError::RetailKeyLength(parts.len() * 8)
+    );
+}
+
+#[rstest]
+fn verify_truncated(#[values(4, 6, 8)] length: usize) {
+    let key = key(&[0x0123_4567_89AB_CDEF, 0xFEDC_BA98_7654_3210]);
+    let full = 0xA1C7_2E74_EA3F_A9B6_u64.to_be_bytes();
+    assert_ok!(verify(
+        &key,
+        MacAlgorithm::Retail,
+        MacPadding::Method1,
+        MESSAGE,
+        &full[..length]
+    ));
+
+    let mut wrong = full;
+    wrong[length - 1] ^= 1;
+    assert_err_eq!(
+        verify(
+            &key,
+            MacAlgorithm::Retail,
+            MacPadding::Method1,
+            MESSAGE,
+            &wrong[..length]
+        ),
+        MacError::Mismatch
+    );
+}
+
+#[rstest]
+fn verify_rejects_invalid_length(#[values(0, 3, 9)] length: usize) {
+    let key = key(&[0x0123_4567_89AB_CDEF]);
+    assert_err_eq!(
+        verify(
+            &key,
+            MacAlgorithm::Cbc,
+            MacPadding::Method1,
+            MESSAGE,
+            &vec![0; length]
+        ),
+        MacError::InvalidLength(length)
+    );
+}
@@ -0,0 +1,126 @@
+use des_lib::{
+    Des,
+    mode::{ctr_apply, ecb_decrypt, ecb_encrypt},
+};
+use rand::random;
+use rstest::rstest;
+
+const TEST_KEY: u64 = 0x1334_5779_9BBC_DFF1;
+const TEST_PLAINTEXT: u64 = 0x0123_4567_89AB_CDEF;
+const TEST_CIPHERTEXT: u64 = 0x85E8_1354_0F0A_B405;
+
+fn random_blocks(len: usize) -> Vec<u64> {
+    (0..len).map(|_| random()).collect()
+}
+
+#[rstest]
+#[case(&[TEST_PLAINTEXT], &[TEST_CIPHERTEXT])]
+#[case(
+    &[0, 1, TEST_PLAINTEXT],
+    &[0x948A_43F9_8A83_4F7E, 0x5D59_D446_0749_5A7A, TEST_CIPHERTEXT]
+)]
+fn ecb_matches_single_block(#[case] plaintext: &[u64], #[case] expected: &[u64]) {
+    let des = Des::new(TEST_KEY);
+    let mut blocks = plaintext.to_vec();
+
+    ecb_encrypt(&des, &mut blocks);
+    assert_eq!(blocks, expected, "ECB encryption mismatch");
+
+    ecb_decrypt(&des, &mut blocks);
+    assert_eq!(blocks, plaintext, "ECB decryption mismatch");
+}
+
+#[test]
+fn ctr_keystream_is_encrypted_counter() {
+    let des = Des::new(TEST_KEY);
+    let mut blocks = [0; 3];
+
+    ctr_apply(&des, 0, &mut blocks);
+
+    assert_eq!(
+        blocks,
+        [
+            0x948A_43F9_8A83_4F7E,
+            0x5D59_D446_0749_5A7A,
+            0x0A48_8BEB_AD8A_16BE
+        ]
+    );
+}
+
+#[test]
+fn ctr_roundtrip() {
+    let des = Des::new(TEST_KEY);
+    let plaintext = random_blocks(100);
+    let mut blocks = plaintext.clone();
+
+    ctr_apply(&des, u64::MAX - 10, &mut blocks);
+    assert_ne!(blocks, plaintext, "CTR did not change the data");
+
+    ctr_apply(&des, u64::MAX - 10, &mut blocks);
+    assert_eq!(blocks, plaintext, "CTR roundtrip failed");
+}
+
+#[cfg(feature = "parallel")]
+mod parallel {
+    use super::*;
+    use des_lib::mode::{
+        PARALLEL_CHUNK_BLOCKS, cbc_decrypt, cbc_encrypt, par_cbc_decrypt, par_ctr_apply,
+        par_ecb_decrypt, par_ecb_encrypt,
+    };
+
+    #[rstest]
+    #[case(0)]
+    #[case(1)]
+    #[case(PARALLEL_CHUNK_BLOCKS)]
+    #[case(PARALLEL_CHUNK_BLOCKS * 3 + 17)]
+    fn ecb_matches_serial(#[case] len: usize) {
+        let des = Des::new(TEST_KEY);
+        let plaintext = random_blocks(len);
+
+        let mut serial = plaintext.clone();
+        let mut parallel = plaintext.clone();
+        ecb_encrypt(&des, &mut serial);
+        par_ecb_encrypt(&des, &mut parallel);
+        assert_eq!(serial, parallel, "Parallel ECB encryption diverged");
+
+        par_ecb_decrypt(&des, &mut parallel);
+        assert_eq!(parallel, plaintext, "Parallel ECB decryption failed");
+    }
+
+    #[rstest]
+    #[case(0, 0)]
+    #[case(1, 42)]
+    #[case(PARALLEL_CHUNK_BLOCKS * 3 + 17, 7)]
+    #[case(PARALLEL_CHUNK_BLOCKS * 2, u64::MAX - 100)]
+    fn ctr_matches_serial(#[case] len: usize, #[case] counter: u64) {
+        let des = Des::new(TEST_KEY);
+        let plaintext = random_blocks(len);
+
+        let mut serial = plaintext.clone();
+        let mut parallel = plaintext;
+        ctr_apply(&des, counter, &mut serial);
+        par_ctr_apply(&des, counter, &mut parallel);
+        assert_eq!(serial, parallel, "Parallel CTR diverged");
+    }
+
+    #[rstest]
+    #[case(0)]
+    #[case(1)]
+    #[case(PARALLEL_CHUNK_BLOCKS + 1)]
+    #[case(PARALLEL_CHUNK_BLOCKS * 3)]
+    fn cbc_decrypt_matches_serial(#[case] len: usize) {
+        let des = Des::new(TEST_KEY);
+        let iv = random();
+        let plaintext = random_blocks(len);
+
+        let mut ciphertext = plaintext.clone();
+        let next_iv = cbc_encrypt(&des, iv, &mut ciphertext);
+
+        let mut serial = ciphertext.clone();
+        let mut parallel = ciphertext;
+        assert_eq!(cbc_decrypt(&des, iv, &mut serial), next_iv);
+        assert_eq!(par_cbc_decrypt(&des, iv, &mut parallel), next_iv);
+        assert_eq!(serial, plaintext, "Serial CBC decryption failed");
+        assert_eq!(parallel, plaintext, "Parallel CBC decryption diverged");
+    }
+}
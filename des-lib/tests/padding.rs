@@ -0,0 +1,35 @@
+use des_lib::padding::{Padding, PaddingError};
+use rstest::rstest;
+
+#[rstest]
+#[case(Padding::Pkcs7, b"", &[8, 8, 8, 8, 8, 8, 8, 8])]
+#[case(Padding::Pkcs7, b"ABCDE", b"ABCDE\x03\x03\x03")]
+#[case(
+    Padding::Pkcs7,
+    b"ABCDEFGH",
+    b"ABCDEFGH\x08\x08\x08\x08\x08\x08\x08\x08"
+)]
+#[case(Padding::Iso7816, b"", &[0x80, 0, 0, 0, 0, 0, 0, 0])]
+#[case(Padding::Iso7816, b"ABCDEFG", b"ABCDEFG\x80")]
+#[case(Padding::Iso7816, b"ABCDEFGH", b"ABCDEFGH\x80\0\0\0\0\0\0\0")]
+#[case(Padding::None, b"ABCDEFGH", b"ABCDEFGH")]
+fn pad_and_unpad(#[case] padding: Padding, #[case] data: &[u8], #[case] padded: &[u8]) {
+    let mut buffer = data.to_vec();
+    padding.pad(&mut buffer);
+
+    assert_eq!(buffer, padded, "Padding mismatch");
+    assert_eq!(padding.unpad(&buffer), Ok(data), "Unpadding mismatch");
+}
+
+#[rstest]
+#[case(Padding::Pkcs7, b"", PaddingError::InvalidLength)]
+#[case(Padding::Pkcs7, b"ABCDEFG", PaddingError::InvalidLength)]
+#[case(Padding::Pkcs7, b"ABCDEFG\x00", PaddingError::InvalidPadding)]
+#[case(Padding::Pkcs7, b"ABCDEFG\x09", PaddingError::InvalidPadding)]
+#[case(Padding::Pkcs7, b"ABCDE\x02\x03\x03", PaddingError::InvalidPadding)]
+#[case(Padding::Iso7816, b"", PaddingError::InvalidLength)]
+#[case(Padding::Iso7816, b"\0\0\0\0\0\0\0\0", PaddingError::InvalidPadding)]
+#[case(Padding::Iso7816, b"ABCDEF\x81\0", PaddingError::InvalidPadding)]
+fn invalid_padding(#[case] padding: Padding, #[case] data: &[u8], #[case] error: PaddingError) {
+    assert_eq!(padding.unpad(data), Err(error));
+}
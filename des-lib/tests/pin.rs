@@ -0,0 +1,130 @@
+use claims::{assert_err_eq, assert_ok_eq};
+use des_lib::{
+    key::Key,
+    pin::{PinError, PinFormat, build, build_format4, decrypt, parse, parse_format4},
+};
+use rstest::rstest;
+
+const PAN: &str = "4111111111111111";
+
+fn key(parts: &[u64]) -> Key {
+    Key::from_parts(parts).expect("valid key")
+}
+
+#[rstest]
+#[case(PinFormat::Iso0, "1234", 0, 0x0412_25EE_EEEE_EEEE)]
+#[case(PinFormat::Iso1, "1234", 0x0123_4567_89AB_CDEF, 0x1412_3467_89AB_CDEF)]
+#[case(PinFormat::Iso2, "1234", 0, 0x2412_34FF_FFFF_FFFF)]
+#[case(PinFormat::Iso3, "1234", 0, 0x3412_25BB_BBBB_BBBB)]
+#[case(PinFormat::Iso3, "1234", u64::MAX, 0x3412_25CC_CCCC_CCCC)]
+#[case(PinFormat::Iso0, "123456789012", 0, 0x0C12_2547_6981_03EE)]
+fn build_blocks(
+    #[case] format: PinFormat,
+    #[case] pin: &str,
+    #[case] fill: u64,
+    #[case] expected: u64,
+) {
+    assert_ok_eq!(build(format, pin, Some(PAN), fill), expected);
+    assert_ok_eq!(parse(expected, Some(PAN)), (format, pin.to_owned()));
+}
+
+#[test]
+fn short_pan_is_padded() {
+    // Account number 12345678901, without the check digit 2.
+    assert_ok_eq!(
+        build(PinFormat::Iso0, "1234", Some("123456789012"), 0),
+        0x0412_34FF_FFFF_FFFF ^ 0x0000_0123_4567_8901
+    );
+}
+
+#[rstest]
+fn random_fill_roundtrip(#[values(PinFormat::Iso1, PinFormat::Iso3)] format: PinFormat) {
+    let fill = rand::random();
+    let block = build(format, "98765", Some(PAN), fill).expect("valid PIN");
+    assert_ok_eq!(parse(block, Some(PAN)), (format, "98765".to_owned()));
+}
+
+#[rstest]
+#[case(PinFormat::Iso0, None, PinError::PanRequired(PinFormat::Iso0))]
+#[case(PinFormat::Iso3, Some("41111111111"), PinError::InvalidPan)]
+#[case(PinFormat::Iso0, Some("4111-1111-1111-1111"), PinError::InvalidPan)]
+fn build_requires_valid_pan(
+    #[case] format: PinFormat,
+    #[case] pan: Option<&str>,
+    #[case] error: PinError,
+) {
+    assert_err_eq!(build(format, "1234", pan, 0), error);
+}
+
+#[rstest]
+fn build_rejects_invalid_pin(#[values("", "123", "1234567890123", "12a4")] pin: &str) {
+    assert_err_eq!(build(PinFormat::Iso2, pin, None, 0), PinError::InvalidPin);
+}
+
+#[rstest]
+// Format 0 with the PAN of another account.
+#[case(0x0412_25EE_EEEE_EEEE, Some("5500000000000004"))]
+// Unknown control field.
+#[case(0x5412_34FF_FFFF_FFFF, None)]
+// PIN length out of range.
+#[case(0x2312_3FFF_FFFF_FFFF, None)]
+// Non-decimal PIN digit.
+#[case(0x2412_3AFF_FFFF_FFFF, None)]
+// Format 3 fill outside A-F.
+#[case(0x3412_2511_1111_1111, Some(PAN))]
+fn parse_rejects_invalid_blocks(#[case] block: u64, #[case] pan: Option<&str>) {
+    assert_err_eq!(parse(block, pan), PinError::InvalidBlock);
+}
+
+/// Encrypted with `openssl enc -des-ecb` and `-des-ede`.
+#[rstest]
+#[case(&[0x0123_4567_89AB_CDEF], 0xC30C_3141_1AA3_D043)]
+#[case(&[0x0123_4567_89AB_CDEF, 0xFEDC_BA98_7654_3210], 0x2A3D_408A_1977_DDE9)]
+fn decrypt_blocks(#[case] parts: &[u64], #[case] encrypted: u64) {
+    assert_ok_eq!(
+        decrypt(&key(parts), encrypted, Some(PAN)),
+        (PinFormat::Iso0, "1234".to_owned())
+    );
+}
+
+#[cfg(feature = "rand")]
+#[rstest]
+fn encrypt_and_translate(
+    #[values(PinFormat::Iso0, PinFormat::Iso1, PinFormat::Iso3)] from: PinFormat,
+    #[values(PinFormat::Iso0, PinFormat::Iso2, PinFormat::Iso3)] to: PinFormat,
+) {
+    use des_lib::pin::{encrypt, translate};
+
+    let terminal = key(&[0x0123_4567_89AB_CDEF, 0xFEDC_BA98_7654_3210]);
+    let zone = key(&[
+        0x89AB_CDEF_0123_4567,
+        0x0123_4567_89AB_CDEF,
+        0xFEDC_BA98_7654_3210,
+    ]);
+    let encrypted = encrypt(&terminal, from, "4321", Some(PAN)).expect("valid PIN");
+    let translated =
+        translate(encrypted, Some(PAN), &terminal, &zone, Some(to)).expect("valid block");
+    assert_ok_eq!(
+        decrypt(&zone, translated, Some(PAN)),
+        (to, "4321".to_owned())
+    );
+
+    let kept = translate(encrypted, Some(PAN), &terminal, &zone, None).expect("valid block");
+    assert_ok_eq!(decrypt(&zone, kept, Some(PAN)), (from, "4321".to_owned()));
+}
+
+#[test]
+fn format4_fields() {
+    let (pin_field, account) =
+        build_format4("1234", "1234567890123456789", 0x0123_4567_89AB_CDEF).expect("valid");
+    assert_eq!(pin_field, 0x4412_34AA_AAAA_AAAA_0123_4567_89AB_CDEF);
+    assert_eq!(account, 0x7123_4567_8901_2345_6789_0000_0000_0000);
+    assert_ok_eq!(parse_format4(pin_field), "1234");
+
+    let (_, account) = build_format4("1234", "432198765432", 0).expect("valid");
+    assert_eq!(account, 0x0432_1987_6543_2000_0000_0000_0000_0000);
+    assert_err_eq!(
+        parse_format4(0x4412_34AA_AAAA_AAAB_0000_0000_0000_0000),
+        PinError::InvalidBlock
+    );
+}
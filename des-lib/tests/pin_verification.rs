@@ -0,0 +1,155 @@
+use claims::{assert_err_eq, assert_ok, assert_ok_eq};
+use des_lib::{
+    BlockCipher, Des,
+    key::Key,
+    pin::PinError,
+    pin_verification::{
+        DecimalizationTable, PinVerificationError, ibm3624_offset, natural_pin, pvv,
+        verify_ibm3624_offset, verify_pvv,
+    },
+};
+use rstest::rstest;
+
+const PAN: &str = "4111111111111111";
+
+fn key(parts: &[u64]) -> Key {
+    Key::from_parts(parts).expect("valid key")
+}
+
+fn pvk() -> Key {
+    key(&[0x0123_4567_89AB_CDEF, 0xFEDC_BA98_7654_3210])
+}
+
+/// The encrypted validation data was checked with `openssl enc -des-ecb` and
+/// `-des-ede`: `F0157C5DA1787BED` and `69D9405C8462F410`.
+#[rstest]
+#[case(&[0x0123_4567_89AB_CDEF], DecimalizationTable::STANDARD, "5015", "6229")]
+#[case(&[0x0123_4567_89AB_CDEF], "9876543210123456".parse().expect("valid table"), "6984", "5350")]
+#[case(&[0x0123_4567_89AB_CDEF, 0xFEDC_BA98_7654_3210], DecimalizationTable::STANDARD, "6939", "5305")]
+fn ibm3624(
+    #[case] parts: &[u64],
+    #[case] table: DecimalizationTable,
+    #[case] natural: &str,
+    #[case] offset: &str,
+) {
+    let key = key(parts);
+    let data = 0x4111_1111_1111_1111;
+    assert_ok_eq!(natural_pin(&key, data, table, 4), natural);
+    assert_ok_eq!(ibm3624_offset(&key, data, table, "1234"), offset);
+    assert_ok!(verify_ibm3624_offset(&key, data, table, "1234", offset));
+    assert_ok_eq!(ibm3624_offset(&key, data, table, natural), "0000");
+    assert_err_eq!(
+        verify_ibm3624_offset(&key, data, table, "1235", offset),
+        PinVerificationError::Mismatch
+    );
+}
+
+#[test]
+fn ibm3624_longer_pins() {
+    let key = key(&[0x0123_4567_89AB_CDEF]);
+    let data = 0x4111_1111_1111_1111;
+    let table = DecimalizationTable::default();
+    assert_ok_eq!(natural_pin(&key, data, table, 6), "501572");
+    assert_ok_eq!(ibm3624_offset(&key, data, table, "123456"), "622984");
+    assert_err_eq!(
+        verify_ibm3624_offset(&key, data, table, "123456", "6229"),
+        PinVerificationError::InvalidOffset
+    );
+    assert_err_eq!(
+        natural_pin(&key, data, table, 13),
+        PinVerificationError::Pin(PinError::InvalidPin)
+    );
+}
+
+/// The worked example of Bond and Zieliński, "Decimalisation table attacks
+/// for PIN cracking" (University of Cambridge UCAM-CL-TR-560, 2003): the
+/// encrypted validation data `3F7C220100CA8AB3` decimalizes to the natural
+/// PIN 3572, and offset 4344 gives the customer PIN 7816. The paper leaves
+/// out the key, so the validation data is chosen to encrypt to its value.
+#[test]
+fn ibm3624_published_example() {
+    let key = key(&[0x0123_4567_89AB_CDEF]);
+    let data = Des::new(0x0123_4567_89AB_CDEF).decrypt_block(0x3F7C_2201_00CA_8AB3);
+    let table = DecimalizationTable::STANDARD;
+    assert_ok_eq!(natural_pin(&key, data, table, 4), "3572");
+    assert_ok_eq!(ibm3624_offset(&key, data, table, "7816"), "4344");
+    assert_ok!(verify_ibm3624_offset(&key, data, table, "7816", "4344"));
+}
+
+/// No published IBM 3624 example that gives its key has been found, so these
+/// take the encryption step from published DES known answers instead: the
+/// FIPS 81 ECB example (`4E6F772069732074` to `3FA40E8A984D4815`) and the
+/// first NIST SP 800-17 variable plaintext entry (`8000000000000000` to
+/// `95F8A5E5DD31D900` under `0101010101010101`). The natural PIN is the first
+/// digits of that block through the standard table.
+#[rstest]
+#[case(0x0123_4567_89AB_CDEF, 0x4E6F_7720_6973_2074, "3504", "8730")]
+#[case(0x0101_0101_0101_0101, 0x8000_0000_0000_0000, "9558", "2786")]
+fn ibm3624_from_des_known_answers(
+    #[case] pvk: u64,
+    #[case] data: u64,
+    #[case] natural: &str,
+    #[case] offset: &str,
+) {
+    let key = key(&[pvk]);
+    let table = DecimalizationTable::STANDARD;
+    assert_ok_eq!(natural_pin(&key, data, table, 4), natural);
+    assert_ok_eq!(ibm3624_offset(&key, data, table, "1234"), offset);
+    assert_ok!(verify_ibm3624_offset(&key, data, table, "1234", offset));
+}
+
+#[rstest]
+fn rejects_invalid_tables(
+    #[values("", "012345678901234", "0123456789A12345", "01234567890123456")] table: &str,
+) {
+    assert_err_eq!(
+        table.parse::<DecimalizationTable>(),
+        PinVerificationError::InvalidTable
+    );
+}
+
+/// Encrypted parameters, checked with `openssl enc -des-ede`:
+/// `946B41C3A8F83E68`, `DC6FCCFFBCDB9DCD` and `DF1BBCAAAFBBEDFF`. The last two
+/// have fewer than four decimal digits, so the PVV is topped up from `A`-`F`.
+#[rstest]
+#[case("1234", "9464")]
+#[case("123456", "9464")]
+#[case("1412", "6932")]
+#[case("5502", "1351")]
+fn visa_pvv(#[case] pin: &str, #[case] expected: &str) {
+    assert_ok_eq!(pvv(&pvk(), PAN, 1, pin), expected);
+    assert_ok!(verify_pvv(&pvk(), PAN, 1, pin, expected));
+}
+
+/// As for IBM 3624, no published PVV example that gives its key has been
+/// found. These choose the PAN, PVK index and PIN so that the transformed
+/// security parameters are the plaintext of a NIST SP 800-17 known answer:
+/// `8000000000000000` to `95F8A5E5DD31D900` under `0101010101010101`, and
+/// `0000000000000000` to `95A8D72813DAA94D` under `8001010101010101`.
+#[rstest]
+#[case(0x0101_0101_0101_0101, "4000800000000007", "9585")]
+#[case(0x8001_0101_0101_0101, "4000000000000002", "9587")]
+fn pvv_from_des_known_answers(#[case] pvk: u64, #[case] pan: &str, #[case] expected: &str) {
+    assert_ok_eq!(pvv(&key(&[pvk]), pan, 0, "0000"), expected);
+    assert_ok!(verify_pvv(&key(&[pvk]), pan, 0, "0000", expected));
+}
+
+#[test]
+fn pvv_rejects_wrong_pin_and_invalid_input() {
+    assert_err_eq!(
+        verify_pvv(&pvk(), PAN, 1, "1235", "9464"),
+        PinVerificationError::Mismatch
+    );
+    assert_err_eq!(
+        verify_pvv(&pvk(), PAN, 1, "1234", "946"),
+        PinVerificationError::InvalidPvv
+    );
+    assert_err_eq!(
+        pvv(&pvk(), PAN, 10, "1234"),
+        PinVerificationError::InvalidPvki(10)
+    );
+    assert_err_eq!(
+        pvv(&pvk(), "41111111111", 1, "1234"),
+        PinVerificationError::Pin(PinError::InvalidPan)
+    );
+}
@@ -0,0 +1,401 @@
+//! No published SCP02 exchange is used here. Expected values were instead
+//! computed independently with `openssl enc -des-ede-cbc`, `-des-cbc` and
+//! `-des-ecb`, using the usual test card keys
+//! `404142434445464748494A4B4C4D4E4F`. The simulated card checks C-MACs with
+//! its own retail MAC rather than the one under test.
+
+use claims::{assert_err_eq, assert_ok};
+use des_lib::{
+    BlockCipher, Des, bytes_to_blocks,
+    encoding::Encoding,
+    key::Key,
+    mode,
+    padding::Padding,
+    scp02::{
+        InitializeUpdateResponse, Scp02Error, SecureChannel, SecurityLevel, SessionKeys,
+        StaticKeys, c_mac, card_cryptogram, host_cryptogram, initialize_update, next_icv,
+    },
+};
+use rstest::rstest;
+
+const SEQUENCE_COUNTER: u16 = 0x000E;
+const HOST_CHALLENGE: u64 = 0x1122_3344_5566_7788;
+const CARD_CHALLENGE: [u8; 6] = [0xA1, 0xB2, 0xC3, 0xD4, 0xE5, 0xF6];
+
+fn bytes(hex: &str) -> Vec<u8> {
+    Encoding::Hex.decode(hex).expect("valid hex")
+}
+
+fn static_keys() -> StaticKeys {
+    let key = Key::from_parts(&[0x4041_4243_4445_4647, 0x4849_4A4B_4C4D_4E4F]).expect("valid key");
+    StaticKeys::single(&key).expect("2TDEA key")
+}
+
+fn session_keys() -> SessionKeys {
+    SessionKeys::derive(&static_keys(), SEQUENCE_COUNTER)
+}
+
+/// A card holding the test keys, answering INITIALIZE UPDATE and checking
+/// every later command the way a security domain does.
+struct SimulatedCard {
+    keys: StaticKeys,
+    sequence_counter: u16,
+    card_challenge: [u8; 6],
+    host_challenge: u64,
+    session: Option<(SessionKeys, SecurityLevel, u64)>,
+    /// Commands received over the secure channel, unwrapped.
+    received: Vec<Vec<u8>>,
+}
+
+impl SimulatedCard {
+    fn new() -> Self {
+        Self {
+            keys: static_keys(),
+            sequence_counter: SEQUENCE_COUNTER,
+            card_challenge: CARD_CHALLENGE,
+            host_challenge: 0,
+            session: None,
+            received: Vec::new(),
+        }
+    }
+
+    /// Process a command APDU and return the response with its status word.
+    fn transmit(&mut self, command: &[u8]) -> Vec<u8> {
+        match command[..2] {
+            [0x80, 0x50] => {
+                self.host_challenge = u64::from_be_bytes(command[5..13].try_into().expect("8"));
+                let keys = SessionKeys::derive(&self.keys, self.sequence_counter);
+                let response = InitializeUpdateResponse {
+                    diversification_data: [0; 10],
+                    key_version: 0xFF,
+                    sequence_counter: self.sequence_counter,
+                    card_challenge: self.card_challenge,
+                    card_cryptogram: card_cryptogram(
+                        &keys,
+                        self.host_challenge,
+                        self.sequence_counter,
+                        self.card_challenge,
+                    ),
+                };
+                [response.to_bytes(), vec![0x90, 0x00]].concat()
+            }
+            [0x84, 0x82] => {
+                let keys = SessionKeys::derive(&self.keys, self.sequence_counter);
+                let expected = host_cryptogram(
+                    &keys,
+                    self.host_challenge,
+                    self.sequence_counter,
+                    self.card_challenge,
+                );
+                let level = match command[2] {
+                    0x01 => SecurityLevel::Mac,
+                    _ => SecurityLevel::MacAndEncryption,
+                };
+                self.session = Some((keys, level, 0));
+                match self.unwrap(command, false) {
+                    Some(data) if data == expected.to_be_bytes() => vec![0x90, 0x00],
+                    _ => {
+                        self.session = None;
+                        vec![0x63, 0x00]
+                    }
+                }
+            }
+            _ => {
+                let encrypted =
+                    matches!(self.session, Some((_, SecurityLevel::MacAndEncryption, _)));
+                match self.unwrap(command, encrypted) {
+                    Some(data) => {
+                        let mut plain = command[..4].to_vec();
+                        plain[0] &= !0x04;
+                        if !data.is_empty() {
+                            plain.push(u8::try_from(data.len()).expect("short APDU"));
+                            plain.extend(data);
+                        }
+                        self.received.push(plain);
+                        vec![0x90, 0x00]
+                    }
+                    None => vec![0x69, 0x82],
+                }
+            }
+        }
+    }
+
+    /// Check the C-MAC of a secure messaging command and return its plain
+    /// data, advancing the ICV.
+    fn unwrap(&mut self, command: &[u8], encrypted: bool) -> Option<Vec<u8>> {
+        let (keys, _, icv) = self.session.as_mut()?;
+        let lc = usize::from(command[4]);
+        let (data, mac) = command[5..5 + lc].split_at(lc - 8);
+        let data = if encrypted && !data.is_empty() {
+            let mut blocks = bytes_to_blocks(data);
+            mode::cbc_decrypt(&keys.enc.cipher(), 0, &mut blocks);
+            let padded = des_lib::blocks_to_bytes(&blocks);
+            Padding::Iso7816.unpad(&padded).ok()?.to_vec()
+        } else {
+            data.to_vec()
+        };
+
+        let header = [&command[..4], &[u8::try_from(data.len() + 8).ok()?]].concat();
+        let [left, right] = *keys.c_mac.parts() else {
+            unreachable!("C-MAC keys are 2TDEA")
+        };
+        let expected = retail_mac(left, right, *icv, &[header, data.clone()].concat());
+        if mac != expected.to_be_bytes() {
+            return None;
+        }
+        // The ICV of the next command is the C-MAC encrypted under the left key.
+        *icv = Des::new(left).encrypt_block(expected);
+        Some(data)
+    }
+}
+
+/// ISO/IEC 9797-1 algorithm 3 with method 2 padding, chained from `icv`:
+/// single DES CBC under `left`, then the last block decrypted under `right`
+/// and encrypted under `left` again.
+fn retail_mac(left: u64, right: u64, icv: u64, message: &[u8]) -> u64 {
+    let mut data = message.to_vec();
+    Padding::Iso7816.pad(&mut data);
+    let des = Des::new(left);
+    let state = bytes_to_blocks(&data)
+        .into_iter()
+        .fold(icv, |state, block| des.encrypt_block(state ^ block));
+    des.encrypt_block(Des::new(right).decrypt_block(state))
+}
+
+/// Run INITIALIZE UPDATE and EXTERNAL AUTHENTICATE against the card.
+fn open(card: &mut SimulatedCard, level: SecurityLevel) -> SecureChannel {
+    let response = card.transmit(&initialize_update(0, HOST_CHALLENGE));
+    let (data, status) = response.split_at(response.len() - 2);
+    assert_eq!(status, [0x90, 0x00]);
+    let (channel, external_authenticate) =
+        SecureChannel::open(&static_keys(), HOST_CHALLENGE, data, level).expect("card is genuine");
+    assert_eq!(card.transmit(&external_authenticate), [0x90, 0x00]);
+    channel
+}
+
+#[test]
+fn session_key_derivation() {
+    let keys = session_keys();
+    assert_eq!(
+        keys.c_mac.to_bytes(),
+        bytes("31BF91D27AC657C742B6854C61BCE32E")
+    );
+    assert_eq!(
+        keys.r_mac.to_bytes(),
+        bytes("1F1060E36314378E25403422A717C686")
+    );
+    assert_eq!(
+        keys.enc.to_bytes(),
+        bytes("83E96BDC8C5AF9CDFA926DC92935CF6A")
+    );
+    assert_eq!(
+        keys.dek.to_bytes(),
+        bytes("E31DDA6ECFBD56B2CC81141A2A7EAB0C")
+    );
+}
+
+#[test]
+fn cryptograms() {
+    let keys = session_keys();
+    assert_eq!(
+        card_cryptogram(&keys, HOST_CHALLENGE, SEQUENCE_COUNTER, CARD_CHALLENGE),
+        0x6AEB_8ADB_4229_9E0C
+    );
+    assert_eq!(
+        host_cryptogram(&keys, HOST_CHALLENGE, SEQUENCE_COUNTER, CARD_CHALLENGE),
+        0x8207_6D7F_ACED_BF9B
+    );
+}
+
+#[test]
+fn initialize_update_exchange() {
+    assert_eq!(
+        initialize_update(0, HOST_CHALLENGE),
+        bytes("8050000008112233445566778800")
+    );
+    let data = bytes("00000000000000000000FF02000EA1B2C3D4E5F66AEB8ADB42299E0C");
+    let response = InitializeUpdateResponse::parse(&data).expect("valid response");
+    assert_eq!(response.key_version, 0xFF);
+    assert_eq!(response.sequence_counter, SEQUENCE_COUNTER);
+    assert_eq!(response.card_challenge, CARD_CHALLENGE);
+    assert_eq!(response.card_cryptogram, 0x6AEB_8ADB_4229_9E0C);
+    assert_eq!(response.to_bytes(), data);
+}
+
+/// The C-MACs of EXTERNAL AUTHENTICATE and the SELECT that follows it.
+#[test]
+fn c_mac_chain() {
+    let keys = session_keys();
+    let external_authenticate = c_mac(&keys, 0, &bytes("848201001082076D7FACEDBF9B"));
+    assert_eq!(external_authenticate, 0x2E29_EF5B_8464_7C8B);
+    let icv = next_icv(&keys, external_authenticate);
+    assert_eq!(icv, 0xEAA3_1D16_068F_31E2);
+    assert_eq!(
+        c_mac(&keys, icv, &bytes("84A4040010A000000151000000")),
+        0x3BE6_8BE8_227B_4229
+    );
+}
+
+#[rstest]
+#[case(
+    SecurityLevel::Mac,
+    "848201001082076D7FACEDBF9B2E29EF5B84647C8B",
+    &[
+        ("80A4040008A00000015100000000", "84A4040010A0000001510000003BE68BE8227B422900"),
+        ("80F00000", "84F00000083D7BC60CA7960315"),
+    ]
+)]
+#[case(
+    SecurityLevel::MacAndEncryption,
+    "848203001082076D7FACEDBF9BFC586EB27B37C90F",
+    &[
+        ("80F24000024F0000", "84F24000106D8780C0376A12352184D397380AB93900"),
+        ("80CA006600", "84CA0066081E15B51CC2607EEC00"),
+    ]
+)]
+fn wrapped_commands(
+    #[case] level: SecurityLevel,
+    #[case] external_authenticate: &str,
+    #[case] commands: &[(&str, &str)],
+) {
+    let data = bytes("00000000000000000000FF02000EA1B2C3D4E5F66AEB8ADB42299E0C");
+    let (mut channel, command) =
+        SecureChannel::open(&static_keys(), HOST_CHALLENGE, &data, level).expect("valid card");
+    assert_eq!(command, bytes(external_authenticate));
+    assert_eq!(channel.security_level(), level);
+    for (command, wrapped) in commands {
+        assert_eq!(
+            channel.wrap(&bytes(command)).expect("valid command"),
+            bytes(wrapped)
+        );
+    }
+}
+
+#[rstest]
+fn simulated_card(
+    #[values(SecurityLevel::Mac, SecurityLevel::MacAndEncryption)] level: SecurityLevel,
+) {
+    let mut card = SimulatedCard::new();
+    let mut channel = open(&mut card, level);
+    let commands = [
+        "00A4040008A00000015100000000",
+        "80F24000024F0000",
+        "80E60C00",
+    ];
+    for command in commands {
+        let wrapped = channel.wrap(&bytes(command)).expect("valid command");
+        assert_eq!(card.transmit(&wrapped), [0x90, 0x00]);
+    }
+    assert_eq!(
+        card.received,
+        [
+            bytes("00A4040008A000000151000000"),
+            bytes("80F24000024F00"),
+            bytes("80E60C00"),
+        ]
+    );
+}
+
+#[test]
+fn card_rejects_tampered_commands() {
+    let mut card = SimulatedCard::new();
+    let mut channel = open(&mut card, SecurityLevel::Mac);
+    let mut wrapped = channel
+        .wrap(&bytes("80F24000024F0000"))
+        .expect("valid command");
+    wrapped[6] ^= 0x01;
+    assert_eq!(card.transmit(&wrapped), [0x69, 0x82]);
+}
+
+#[test]
+fn card_rejects_replayed_commands() {
+    let mut card = SimulatedCard::new();
+    let mut channel = open(&mut card, SecurityLevel::Mac);
+    let wrapped = channel
+        .wrap(&bytes("80F24000024F0000"))
+        .expect("valid command");
+    assert_eq!(card.transmit(&wrapped), [0x90, 0x00]);
+    assert_eq!(card.transmit(&wrapped), [0x69, 0x82]);
+}
+
+#[test]
+fn open_rejects_wrong_keys() {
+    let mut card = SimulatedCard::new();
+    card.keys = StaticKeys::single(
+        &Key::from_parts(&[0x0123_4567_89AB_CDEF, 0xFEDC_BA98_7654_3210]).expect("valid key"),
+    )
+    .expect("2TDEA key");
+    let response = card.transmit(&initialize_update(0, HOST_CHALLENGE));
+    assert_err_eq!(
+        SecureChannel::open(
+            &static_keys(),
+            HOST_CHALLENGE,
+            &response[..response.len() - 2],
+            SecurityLevel::Mac
+        ),
+        Scp02Error::CardCryptogram
+    );
+}
+
+#[rstest]
+#[case(
+    "00000000000000000000FF02000EA1B2C3D4E5F66AEB8ADB42299E",
+    Scp02Error::ResponseLength(27)
+)]
+#[case(
+    "00000000000000000000FF02000EA1B2C3D4E5F66AEB8ADB42299E0C9000",
+    Scp02Error::ResponseLength(30)
+)]
+#[case(
+    "00000000000000000000FF01000EA1B2C3D4E5F66AEB8ADB42299E0C",
+    Scp02Error::UnsupportedProtocol(0x01)
+)]
+fn rejects_invalid_responses(#[case] data: &str, #[case] error: Scp02Error) {
+    assert_err_eq!(InitializeUpdateResponse::parse(&bytes(data)), error);
+}
+
+#[rstest]
+#[case("80CA00", "shorter than 4 bytes")]
+#[case("80F24000034F00", "Lc does not match the data")]
+#[case("80F24000014F0000", "Lc does not match the data")]
+fn rejects_invalid_commands(#[case] command: &str, #[case] reason: &'static str) {
+    let mut channel = open(&mut SimulatedCard::new(), SecurityLevel::Mac);
+    assert_err_eq!(
+        channel.wrap(&bytes(command)),
+        Scp02Error::InvalidCommand(reason)
+    );
+}
+
+/// Encryption pads the data to the next block, and the C-MAC takes 8 more
+/// bytes of a short APDU.
+#[test]
+fn rejects_commands_too_long_to_wrap() {
+    let load = |length: u8| {
+        [
+            &[0x80, 0xE8, 0x00, 0x00, length][..],
+            &vec![0; length.into()],
+        ]
+        .concat()
+    };
+    let mut channel = open(&mut SimulatedCard::new(), SecurityLevel::MacAndEncryption);
+    assert_err_eq!(channel.wrap(&load(0xF0)), Scp02Error::CommandTooLong(256));
+    assert_ok!(channel.wrap(&load(0xEF)));
+}
+
+#[test]
+fn rejected_commands_keep_the_channel_in_step() {
+    let mut card = SimulatedCard::new();
+    let mut channel = open(&mut card, SecurityLevel::MacAndEncryption);
+    let too_long = [&[0x80, 0xE8, 0x00, 0x00, 0xF0][..], &[0; 0xF0]].concat();
+    assert!(channel.wrap(&too_long).is_err());
+    let wrapped = channel
+        .wrap(&bytes("80F24000024F0000"))
+        .expect("valid command");
+    assert_eq!(card.transmit(&wrapped), [0x90, 0x00]);
+}
+
+#[rstest]
+fn requires_double_length_keys(#[values(1, 3)] parts: usize) {
+    let key = Key::from_parts(&[0x4041_4243_4445_4647; 3][..parts]).expect("valid key");
+    assert_err_eq!(StaticKeys::single(&key), Scp02Error::KeyLength(parts * 8));
+}
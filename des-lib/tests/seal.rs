@@ -0,0 +1,194 @@
+#![cfg(feature = "rand")]
+
+use claims::{assert_matches, assert_ok, assert_ok_eq};
+use des_lib::{
+    cmac::cmac,
+    key::Key,
+    mode::Mode,
+    padding::Padding,
+    seal::{HEADER_LEN, OpenReader, SealError, SealWriter, TAG_LEN, open, seal, verify},
+    stream::DecryptReader,
+};
+use rstest::rstest;
+use std::io::{Read, Write};
+
+fn key(parts: usize) -> Key {
+    Key::from_parts(
+        &[
+            0x0123_4567_89AB_CDEF,
+            0xFEDC_BA98_7654_3210,
+            0x89AB_CDEF_0123_4567,
+        ][..parts],
+    )
+    .expect("valid key")
+}
+
+#[rstest]
+fn seal_and_open(#[values(1, 2, 3)] parts: usize, #[values(0, 1, 8, 20, 4096)] len: usize) {
+    let key = key(parts);
+    let plaintext = (0..=u8::MAX).cycle().take(len).collect::<Vec<_>>();
+    let sealed = seal(&key, &plaintext);
+
+    let padded = len + 8 - len % 8;
+    assert_eq!(sealed.len(), HEADER_LEN + padded + TAG_LEN);
+    assert!(sealed.starts_with(b"DESAE\x01"));
+    assert_ok_eq!(open(&key, &sealed), plaintext);
+}
+
+/// Decrypt `sealed` with an encryption key derived here, independently of
+/// the library, as SP 800-108 counter-mode CMAC output of `bits` bits.
+fn decrypt_with_derived_key(master: &Key, bits: u32, sealed: &[u8]) -> Vec<u8> {
+    let parts = (1..=bits / 64)
+        .map(|counter| {
+            let mut input = vec![u8::try_from(counter).expect("small counter")];
+            input.extend_from_slice(b"des-seal encryption\0");
+            input.extend_from_slice(&bits.to_be_bytes());
+            cmac(&master.cipher(), &input)
+        })
+        .collect::<Vec<_>>();
+    let subkey = Key::from_parts(&parts)
+        .expect("valid key")
+        .with_odd_parity();
+
+    let iv = u64::from_be_bytes(sealed[6..HEADER_LEN].try_into().expect("IV"));
+    let ciphertext = &sealed[HEADER_LEN..sealed.len() - TAG_LEN];
+    let mut plaintext = Vec::new();
+    DecryptReader::new(
+        ciphertext,
+        subkey.cipher(),
+        Mode::Cbc { iv },
+        Padding::Pkcs7,
+    )
+    .read_to_end(&mut plaintext)
+    .expect("valid ciphertext");
+    plaintext
+}
+
+#[rstest]
+#[case(1, 128)]
+#[case(2, 128)]
+#[case(3, 192)]
+fn subkeys_are_at_least_double_length(#[case] parts: usize, #[case] bits: u32) {
+    let key = key(parts);
+    let sealed = seal(&key, b"single DES is not enough");
+
+    assert_eq!(
+        decrypt_with_derived_key(&key, bits, &sealed),
+        b"single DES is not enough"
+    );
+}
+
+#[test]
+fn iv_is_random() {
+    let key = key(2);
+    assert_ne!(seal(&key, b"same message"), seal(&key, b"same message"));
+}
+
+#[rstest]
+fn streams_in_pieces(#[values(1, 7, 64)] piece: usize) {
+    let key = key(3);
+    let plaintext = vec![0x5A; 1000];
+
+    let mut writer = SealWriter::new(&key, Vec::new()).expect("header written");
+    for chunk in plaintext.chunks(piece) {
+        writer.write_all(chunk).expect("write to Vec");
+    }
+    let sealed = writer.finish().expect("finish");
+    assert_ok!(verify(&key, sealed.as_slice()));
+
+    let mut reader = OpenReader::new(&key, sealed.as_slice()).expect("valid header");
+    let mut opened = Vec::new();
+    let mut buffer = vec![0; piece];
+    loop {
+        let count = reader.read(&mut buffer).expect("authentic data");
+        if count == 0 {
+            break;
+        }
+        opened.extend_from_slice(&buffer[..count]);
+    }
+    assert_eq!(opened, plaintext);
+}
+
+#[rstest]
+fn detects_tampering(#[values(6, HEADER_LEN, HEADER_LEN + 9, HEADER_LEN + 24)] position: usize) {
+    let key = key(2);
+    let mut sealed = seal(&key, b"Transfer 100 to account 12345");
+    sealed[position] ^= 0x01;
+
+    assert_matches!(open(&key, &sealed), Err(SealError::Authentication));
+    assert_matches!(
+        verify(&key, sealed.as_slice()),
+        Err(SealError::Authentication)
+    );
+
+    let mut reader = OpenReader::new(&key, sealed.as_slice()).expect("valid header");
+    let error = reader
+        .read_to_end(&mut Vec::new())
+        .expect_err("tampered data");
+    assert_matches!(
+        error.get_ref().and_then(|e| e.downcast_ref::<SealError>()),
+        Some(SealError::Authentication)
+    );
+}
+
+#[test]
+fn authentication_failure_is_sticky() {
+    let key = key(2);
+    let mut sealed = seal(&key, b"attack at dawn!! secret tail");
+    let last = sealed.len() - 1;
+    sealed[last] ^= 0x01;
+
+    let mut reader = OpenReader::with_capacity(8, &key, sealed.as_slice()).expect("valid header");
+    let mut buffer = [0; 8];
+    let error = loop {
+        match reader.read(&mut buffer) {
+            Ok(0) => panic!("Tampered data reached a clean EOF"),
+            Ok(_) => {}
+            Err(error) => break error,
+        }
+    };
+    assert_matches!(
+        error.get_ref().and_then(|e| e.downcast_ref::<SealError>()),
+        Some(SealError::Authentication)
+    );
+    for _ in 0..3 {
+        let error = reader.read(&mut buffer).expect_err("tampered data");
+        assert_matches!(
+            error.get_ref().and_then(|e| e.downcast_ref::<SealError>()),
+            Some(SealError::Authentication)
+        );
+    }
+}
+
+#[test]
+fn detects_wrong_key() {
+    let sealed = seal(&key(2), b"secret");
+    assert_matches!(open(&key(3), &sealed), Err(SealError::Authentication));
+}
+
+#[test]
+fn rejects_truncation() {
+    let key = key(2);
+    let sealed = seal(&key, b"sixteen byte msg");
+    assert_matches!(
+        open(&key, &sealed[..sealed.len() - TAG_LEN]),
+        Err(SealError::Authentication)
+    );
+    assert_matches!(
+        open(&key, &sealed[..HEADER_LEN + 4]),
+        Err(SealError::Truncated)
+    );
+    assert_matches!(
+        open(&key, &sealed[..HEADER_LEN - 1]),
+        Err(SealError::Truncated)
+    );
+}
+
+#[test]
+fn rejects_other_data() {
+    let key = key(2);
+    let mut sealed = seal(&key, b"data");
+    sealed[5] = 2;
+    assert_matches!(open(&key, &sealed), Err(SealError::UnsupportedVersion(2)));
+    assert_matches!(open(&key, &[0; 32]), Err(SealError::MissingHeader));
+}
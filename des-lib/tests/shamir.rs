@@ -0,0 +1,99 @@
+use claims::assert_err_eq;
+use des_lib::{
+    key::{Key, KeyLength},
+    shamir::{Share, ShareError, recover, split},
+};
+use rstest::rstest;
+
+#[rstest]
+fn split_and_recover(
+    #[values(KeyLength::Single, KeyLength::Double, KeyLength::Triple)] length: KeyLength,
+    #[values((2, 2), (2, 3), (3, 5))] (threshold, count): (u8, u8),
+) {
+    let key = Key::generate(length);
+    let shares = split(&key, threshold, count).expect("valid threshold");
+
+    assert_eq!(shares.len(), usize::from(count));
+    for share in &shares {
+        assert_eq!(share.threshold(), threshold);
+        assert_eq!(share.kcv(), key.kcv());
+    }
+    // Any `threshold` shares, in any order, recover the key.
+    for window in shares.windows(usize::from(threshold)) {
+        assert_eq!(recover(window), Ok(key.clone()));
+        let reversed = window.iter().rev().cloned().collect::<Vec<_>>();
+        assert_eq!(recover(&reversed), Ok(key.clone()));
+    }
+    assert_eq!(recover(&shares), Ok(key));
+}
+
+#[test]
+fn share_bytes_roundtrip() {
+    let key = Key::generate(KeyLength::Double);
+    let shares = split(&key, 2, 3).expect("valid threshold");
+
+    for share in shares {
+        let bytes = share.to_bytes();
+        assert_eq!(bytes.len(), 5 + 16);
+        assert_eq!(bytes[0], share.index());
+        assert_eq!(Share::from_bytes(&bytes), Ok(share));
+    }
+}
+
+#[rstest]
+#[case(1, 3)]
+#[case(4, 3)]
+#[case(0, 0)]
+fn split_rejects_invalid_threshold(#[case] threshold: u8, #[case] shares: u8) {
+    let key = Key::generate(KeyLength::Single);
+    assert_err_eq!(
+        split(&key, threshold, shares),
+        ShareError::InvalidThreshold { threshold, shares }
+    );
+}
+
+#[test]
+fn recover_rejects_invalid_shares() {
+    let key = Key::generate(KeyLength::Double);
+    let shares = split(&key, 3, 4).expect("valid threshold");
+    let other = split(&Key::generate(KeyLength::Double), 3, 4).expect("valid threshold");
+
+    assert_err_eq!(recover(&[]), ShareError::TooFewShares { needed: 2, got: 0 });
+    assert_err_eq!(
+        recover(&shares[..2]),
+        ShareError::TooFewShares { needed: 3, got: 2 }
+    );
+    assert_err_eq!(
+        recover(&[shares[0].clone(), shares[1].clone(), shares[0].clone()]),
+        ShareError::DuplicateShare(shares[0].index())
+    );
+    assert_err_eq!(
+        recover(&[shares[0].clone(), shares[1].clone(), other[2].clone()]),
+        ShareError::InconsistentShares
+    );
+}
+
+#[test]
+fn recover_detects_corrupted_share() {
+    let key = Key::generate(KeyLength::Triple);
+    let shares = split(&key, 2, 2).expect("valid threshold");
+
+    let mut bytes = shares[1].to_bytes();
+    *bytes.last_mut().expect("share data") ^= 0x40;
+    let corrupted = Share::from_bytes(&bytes).expect("well-formed share");
+
+    assert_err_eq!(
+        recover(&[shares[0].clone(), corrupted]),
+        ShareError::KcvMismatch
+    );
+}
+
+#[rstest]
+#[case(&[])]
+#[case(&[1, 2, 0xD5, 0xD4, 0x4F])]
+#[case(&[0, 2, 0xD5, 0xD4, 0x4F, 1, 2, 3, 4, 5, 6, 7, 8])]
+#[case(&[1, 1, 0xD5, 0xD4, 0x4F, 1, 2, 3, 4, 5, 6, 7, 8])]
+#[case(&[1, 2, 0xD5, 0xD4, 0x4F, 1, 2, 3, 4, 5, 6, 7])]
+fn malformed_shares(#[case] bytes: &[u8]) {
+    assert_err_eq!(Share::from_bytes(bytes), ShareError::Malformed);
+}
@@ -0,0 +1,155 @@
+use des_lib::{
+    Des,
+    encoding::Encoding,
+    mode::Mode,
+    padding::Padding,
+    stream::{DecryptReader, EncryptWriter},
+};
+use rand::random;
+use rstest::rstest;
+use std::io::{self, Read, Write};
+
+const FIPS_KEY: u64 = 0x0123_4567_89AB_CDEF;
+const FIPS_IV: u64 = 0x1234_5678_90AB_CDEF;
+const FIPS_PLAINTEXT: &[u8] = b"Now is the time for all ";
+
+fn encrypt(data: &[u8], mode: Mode, padding: Padding, piece: usize) -> io::Result<Vec<u8>> {
+    let mut writer = EncryptWriter::new(Vec::new(), Des::new(FIPS_KEY), mode, padding);
+    for chunk in data.chunks(piece.max(1)) {
+        writer.write_all(chunk)?;
+    }
+    writer.finish()
+}
+
+fn decrypt(data: &[u8], mode: Mode, padding: Padding) -> io::Result<Vec<u8>> {
+    let mut reader = DecryptReader::new(data, Des::new(FIPS_KEY), mode, padding);
+    let mut plaintext = Vec::new();
+    reader.read_to_end(&mut plaintext)?;
+    Ok(plaintext)
+}
+
+#[rstest]
+#[case(Mode::Ecb, "3FA40E8A984D48156A271787AB8883F9893D51EC4B563B53")]
+#[case(
+    Mode::Cbc { iv: FIPS_IV },
+    "E5C7CDDE872BF27C43E934008C389C0F683788499A7C05F6"
+)]
+fn fips81_vectors(#[case] mode: Mode, #[case] expected: &str) {
+    let ciphertext = encrypt(FIPS_PLAINTEXT, mode, Padding::None, 5).expect("aligned input");
+
+    assert_eq!(Encoding::Hex.encode(&ciphertext), expected);
+    assert_eq!(
+        decrypt(&ciphertext, mode, Padding::None).expect("valid ciphertext"),
+        FIPS_PLAINTEXT
+    );
+}
+
+#[test]
+fn cbc_pkcs7_matches_openssl() {
+    let ciphertext = encrypt(
+        b"Now is the time",
+        Mode::Cbc { iv: FIPS_IV },
+        Padding::Pkcs7,
+        3,
+    )
+    .expect("padded input");
+
+    assert_eq!(
+        Encoding::Hex.encode(&ciphertext),
+        "E5C7CDDE872BF27CC031B490FEB4D7EF"
+    );
+}
+
+#[rstest]
+fn roundtrip(
+    #[values(Mode::Ecb, Mode::Cbc { iv: random() }, Mode::Ctr { counter: random() })] mode: Mode,
+    #[values(Padding::Pkcs7, Padding::Iso7816)] padding: Padding,
+    #[values(0, 1, 7, 8, 9, 100, 20_000)] len: usize,
+    #[values(1, 8, 4096)] piece: usize,
+) {
+    let plaintext = (0..len).map(|_| random()).collect::<Vec<u8>>();
+
+    let ciphertext = encrypt(&plaintext, mode, padding, piece).expect("padded input");
+    assert_eq!(
+        ciphertext.len(),
+        (len / 8 + 1) * 8,
+        "Unexpected padded length"
+    );
+
+    let decrypted = decrypt(&ciphertext, mode, padding).expect("valid ciphertext");
+    assert_eq!(decrypted, plaintext, "Stream roundtrip failed");
+}
+
+#[rstest]
+#[case(0)]
+#[case(5)]
+#[case(8)]
+#[case(8195)]
+fn ctr_accepts_partial_block_without_padding(#[case] len: usize) {
+    let mode = Mode::Ctr { counter: 7 };
+    let plaintext = (0..len).map(|_| random()).collect::<Vec<u8>>();
+
+    let ciphertext = encrypt(&plaintext, mode, Padding::None, 3).expect("CTR input");
+    assert_eq!(ciphertext.len(), len, "CTR must not expand the data");
+
+    let decrypted = decrypt(&ciphertext, mode, Padding::None).expect("CTR ciphertext");
+    assert_eq!(decrypted, plaintext);
+}
+
+#[rstest]
+#[case(Mode::Ecb)]
+#[case(Mode::Cbc { iv: 0 })]
+fn block_modes_reject_unaligned_data_without_padding(#[case] mode: Mode) {
+    let error = encrypt(b"odd", mode, Padding::None, 8).expect_err("unaligned input");
+    assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+
+    let error = decrypt(b"0123456789", mode, Padding::None).expect_err("unaligned input");
+    assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+}
+
+#[test]
+fn tampered_padding_is_rejected() {
+    let mode = Mode::Cbc { iv: FIPS_IV };
+    let mut ciphertext = encrypt(b"Now is the time", mode, Padding::Pkcs7, 8).expect("input");
+    let last = ciphertext.len() - 1;
+    ciphertext[last] ^= 0x01;
+
+    let error = decrypt(&ciphertext, mode, Padding::Pkcs7).expect_err("bad padding");
+    assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+}
+
+#[test]
+fn padding_error_is_sticky() {
+    let ciphertext =
+        encrypt(b"Now is the tim\0\0", Mode::Ecb, Padding::None, 8).expect("aligned input");
+    let mut reader = DecryptReader::new(
+        ciphertext.as_slice(),
+        Des::new(FIPS_KEY),
+        Mode::Ecb,
+        Padding::Pkcs7,
+    );
+
+    let mut buffer = [0; 8];
+    assert_eq!(reader.read(&mut buffer).expect("first block"), 8);
+    assert_eq!(&buffer, b"Now is t");
+    for _ in 0..3 {
+        let error = reader.read(&mut buffer).expect_err("bad padding");
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+}
+
+#[test]
+fn dropping_writer_finishes_stream() {
+    let mut sink = Vec::new();
+    {
+        let mut writer =
+            EncryptWriter::new(&mut sink, Des::new(FIPS_KEY), Mode::Ecb, Padding::Pkcs7);
+        writer.write_all(b"abc").expect("write");
+    }
+
+    assert_eq!(sink.len(), 8, "Final padded block was not written on drop");
+    assert_eq!(
+        decrypt(&sink, Mode::Ecb, Padding::Pkcs7).expect("valid ciphertext"),
+        b"abc"
+    );
+}
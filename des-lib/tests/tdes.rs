@@ -0,0 +1,52 @@
+use des_lib::{BlockCipher, Des, tdes::TripleDes};
+use rand::random;
+use rstest::rstest;
+
+#[test]
+fn sp800_67_example() {
+    let tdes = TripleDes::new(
+        0x0123_4567_89AB_CDEF,
+        0x2345_6789_ABCD_EF01,
+        0x4567_89AB_CDEF_0123,
+    );
+    // "The qufck brown fox jump"
+    let plaintext = [
+        0x5468_6520_7175_6663,
+        0x6B20_6272_6F77_6E20,
+        0x666F_7820_6A75_6D70,
+    ];
+    let expected = [
+        0xA826_FD8C_E53B_855F,
+        0xCCE2_1C81_1225_6FE6,
+        0x68D5_C05D_D9B6_B900,
+    ];
+
+    let mut blocks = plaintext;
+    tdes.encrypt_blocks(&mut blocks);
+    assert_eq!(blocks, expected, "Bulk encryption mismatch");
+    tdes.decrypt_blocks(&mut blocks);
+    assert_eq!(blocks, plaintext, "Bulk decryption mismatch");
+
+    for (&block, &ciphertext) in plaintext.iter().zip(&expected) {
+        assert_eq!(tdes.encrypt(block), ciphertext);
+        assert_eq!(tdes.decrypt(ciphertext), block);
+    }
+}
+
+#[rstest]
+#[case(0x0000_0000_0000_0000, 0x08D7_B4FB_629D_0885)]
+#[case(0x4142_4344_4546_4748, 0x5EDF_AC24_A0CD_C238)]
+fn two_key_matches_openssl(#[case] plaintext: u64, #[case] expected: u64) {
+    let tdes = TripleDes::with_two_keys(0x0123_4567_89AB_CDEF, 0xFEDC_BA98_7654_3210);
+    assert_eq!(tdes.encrypt(plaintext), expected);
+}
+
+#[test]
+fn repeated_key_is_single_des() {
+    let key = random();
+    let block = random();
+    assert_eq!(
+        TripleDes::new(key, key, key).encrypt(block),
+        Des::new(key).encrypt(block)
+    );
+}
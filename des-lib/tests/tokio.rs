@@ -0,0 +1,215 @@
+#![cfg(feature = "tokio")]
+
+use bytes::{Bytes, BytesMut};
+use des_lib::{
+    Des,
+    async_io::{AsyncDecryptReader, AsyncEncryptWriter},
+    codec::CbcFrameCodec,
+    mode::Mode,
+    padding::Padding,
+    stream::EncryptWriter,
+};
+use futures::{SinkExt, StreamExt};
+use rand::random;
+use rstest::rstest;
+use std::io::{self, Write};
+use tokio::io::{AsyncReadExt, AsyncWriteExt, duplex};
+use tokio_util::codec::{Decoder, Encoder, Framed, FramedRead, FramedWrite};
+
+const TEST_KEY: u64 = 0x1334_5779_9BBC_DFF1;
+
+#[rstest]
+#[tokio::test]
+async fn async_stream_roundtrip(
+    #[values(Mode::Ecb, Mode::Cbc { iv: random() }, Mode::Ctr { counter: random() })] mode: Mode,
+    #[values(0, 7, 8, 20_000)] len: usize,
+) {
+    let plaintext = (0..len).map(|_| random()).collect::<Vec<u8>>();
+    let (client, server) = duplex(64);
+
+    let expected = plaintext.clone();
+    let writer = tokio::spawn(async move {
+        let mut writer = AsyncEncryptWriter::new(client, Des::new(TEST_KEY), mode, Padding::Pkcs7);
+        for chunk in plaintext.chunks(13) {
+            writer.write_all(chunk).await?;
+        }
+        writer.shutdown().await
+    });
+
+    let mut reader = AsyncDecryptReader::new(server, Des::new(TEST_KEY), mode, Padding::Pkcs7);
+    let mut decrypted = Vec::new();
+    reader
+        .read_to_end(&mut decrypted)
+        .await
+        .expect("valid ciphertext");
+
+    writer.await.expect("writer task").expect("writer result");
+    assert_eq!(decrypted, expected, "Async stream roundtrip failed");
+}
+
+#[tokio::test]
+async fn async_padding_error_is_sticky() {
+    let mut writer = EncryptWriter::new(Vec::new(), Des::new(TEST_KEY), Mode::Ecb, Padding::None);
+    writer.write_all(b"Now is the tim\0\0").expect("write");
+    let ciphertext = writer.finish().expect("aligned input");
+    let mut reader = AsyncDecryptReader::new(
+        ciphertext.as_slice(),
+        Des::new(TEST_KEY),
+        Mode::Ecb,
+        Padding::Pkcs7,
+    );
+
+    let mut buffer = [0; 8];
+    assert_eq!(reader.read(&mut buffer).await.expect("first block"), 8);
+    assert_eq!(&buffer, b"Now is t");
+    for _ in 0..3 {
+        let error = reader.read(&mut buffer).await.expect_err("bad padding");
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+}
+
+#[tokio::test]
+async fn framed_roundtrip_over_duplex() {
+    let messages = [
+        Bytes::from_static(b""),
+        Bytes::from_static(b"PING"),
+        Bytes::from_static(b"exactly8"),
+        Bytes::from((0..5000).map(|_| random()).collect::<Vec<u8>>()),
+    ];
+    let (client, server) = duplex(256);
+
+    let outgoing = messages.clone();
+    let sender = tokio::spawn(async move {
+        let mut sink = FramedWrite::new(client, CbcFrameCodec::new(Des::new(TEST_KEY)));
+        for message in outgoing {
+            sink.send(message).await?;
+        }
+        Ok::<_, io::Error>(())
+    });
+
+    let mut frames = FramedRead::new(server, CbcFrameCodec::new(Des::new(TEST_KEY)));
+    for expected in &messages {
+        let frame = frames
+            .next()
+            .await
+            .expect("frame available")
+            .expect("valid frame");
+        assert_eq!(frame, expected, "Decoded frame mismatch");
+    }
+
+    sender.await.expect("sender task").expect("sender result");
+    assert!(frames.next().await.is_none(), "Unexpected trailing frame");
+}
+
+#[tokio::test]
+async fn framed_request_response() {
+    let (client, server) = duplex(128);
+
+    let controller = tokio::spawn(async move {
+        let mut framed = Framed::new(server, CbcFrameCodec::new(Des::new(TEST_KEY)));
+        while let Some(request) = framed.next().await {
+            let mut reply = b"ACK ".to_vec();
+            reply.extend_from_slice(&request?);
+            framed.send(Bytes::from(reply)).await?;
+        }
+        Ok::<_, io::Error>(())
+    });
+
+    let mut framed = Framed::new(client, CbcFrameCodec::new(Des::new(TEST_KEY)));
+    for request in ["STATUS", "OPEN 3"] {
+        framed
+            .send(Bytes::from(request))
+            .await
+            .expect("send request");
+        let reply = framed
+            .next()
+            .await
+            .expect("reply available")
+            .expect("valid reply");
+        assert_eq!(reply, format!("ACK {request}").as_bytes());
+    }
+
+    drop(framed);
+    controller
+        .await
+        .expect("controller task")
+        .expect("controller result");
+}
+
+#[test]
+fn frames_use_fresh_ivs() {
+    let mut codec = CbcFrameCodec::new(Des::new(TEST_KEY));
+    let mut first = BytesMut::new();
+    let mut second = BytesMut::new();
+
+    codec
+        .encode(Bytes::from_static(b"same payload"), &mut first)
+        .expect("encode");
+    codec
+        .encode(Bytes::from_static(b"same payload"), &mut second)
+        .expect("encode");
+
+    assert_eq!(first.len(), 4 + 8 + 16, "Unexpected frame layout");
+    assert_eq!(first[..4], [0, 0, 0, 24], "Unexpected length prefix");
+    assert_ne!(first, second, "Identical frames leak equal payloads");
+}
+
+#[test]
+fn decoder_waits_for_complete_frame() {
+    let mut codec = CbcFrameCodec::new(Des::new(TEST_KEY));
+    let mut encoded = BytesMut::new();
+    codec
+        .encode(Bytes::from_static(b"partial delivery"), &mut encoded)
+        .expect("encode");
+
+    let mut src = BytesMut::new();
+    for &byte in &encoded[..encoded.len() - 1] {
+        src.extend_from_slice(&[byte]);
+        assert!(codec.decode(&mut src).expect("incomplete frame").is_none());
+    }
+    src.extend_from_slice(&encoded[encoded.len() - 1..]);
+
+    let frame = codec.decode(&mut src).expect("complete frame");
+    assert_eq!(frame.as_deref(), Some(&b"partial delivery"[..]));
+    assert!(src.is_empty(), "Frame bytes were not consumed");
+}
+
+#[rstest]
+#[case(&[0, 0, 0, 8], "shorter than IV and one block")]
+#[case(&[0, 0, 0, 17], "not block aligned")]
+#[case(&[0x7F, 0, 0, 0], "over the frame limit")]
+fn decoder_rejects_invalid_lengths(#[case] prefix: &[u8], #[case] reason: &str) {
+    let mut codec = CbcFrameCodec::new(Des::new(TEST_KEY));
+    let mut src = BytesMut::from(prefix);
+
+    let error = codec.decode(&mut src).expect_err(reason);
+    assert_eq!(error.kind(), io::ErrorKind::InvalidData, "{reason}");
+}
+
+#[test]
+fn decoder_rejects_wrong_key() {
+    let mut encoded = BytesMut::new();
+    CbcFrameCodec::new(Des::new(TEST_KEY))
+        .encode(Bytes::from_static(b"secret"), &mut encoded)
+        .expect("encode");
+
+    let result = CbcFrameCodec::new(Des::new(!TEST_KEY)).decode(&mut encoded);
+    assert!(
+        !matches!(result, Ok(Some(ref frame)) if frame == &b"secret"[..]),
+        "Frame decrypted under the wrong key"
+    );
+}
+
+#[test]
+fn encoder_enforces_frame_limit() {
+    let mut codec = CbcFrameCodec::new(Des::new(TEST_KEY)).max_frame_length(32);
+    let mut dst = BytesMut::new();
+
+    codec
+        .encode(Bytes::from(vec![0; 23]), &mut dst)
+        .expect("fits the limit");
+    let error = codec
+        .encode(Bytes::from(vec![0; 24]), &mut dst)
+        .expect_err("exceeds the limit");
+    assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+}
@@ -1,10 +1,23 @@
-use clap::{Parser, Subcommand, ValueEnum};
+use clap::{ArgGroup, Parser, Subcommand, ValueEnum};
+use des_lib::{
+    emv::MAX_PSN,
+    encoding::{Encoding, EncodingError},
+    kdf::{DEFAULT_ITERATIONS, Prf, derive_key},
+    key::{KcvMethod, Key, KeyError, KeyLength},
+    keyblock::{Exportability, ModeOfUse, OptionalBlock, Usage, Version},
+    keystore::{KeyUsage, MAX_ITERATIONS},
+    mac::{MIN_MAC_LENGTH, MacAlgorithm, MacPadding},
+    mode::Mode,
+    padding::Padding,
+    pin::PinFormat,
+    shamir::{Share, ShareError},
+};
 use std::{
     fmt::{Display, LowerHex, UpperHex},
-    fs::read_to_string,
-    num::IntErrorKind,
+    fs::{read, read_to_string},
+    num::{IntErrorKind, NonZeroU32, NonZeroUsize},
     path::PathBuf,
-    str::FromStr,
+    str::{self, Utf8Error},
 };
 use thiserror::Error;
 
@@ -25,41 +38,1145 @@ pub enum ValueError {
     #[error("Invalid number format: {0}")]
     InvalidFormat(String),
 
+    #[error("Invalid number format: {0}")]
+    Encoding(#[from] EncodingError),
+
     #[error("Invalid byte string: must be exactly 8 ASCII characters")]
     InvalidByteString,
 
     #[error("String-to-u64 conversion error: {0}")]
     ConversionError(String),
+
+    #[error("'{0}' is not a 64-bit key; use --kdf to derive a key from a passphrase")]
+    Passphrase(String),
+
+    #[error(transparent)]
+    Key(#[from] KeyError),
+
+    #[error(transparent)]
+    Share(#[from] ShareError),
+
+    #[error("Invalid {0}: {1}")]
+    Argument(String, Box<Self>),
 }
 
+const KDF_ITERATIONS: NonZeroU32 =
+    NonZeroU32::new(DEFAULT_ITERATIONS).expect("Default iteration count is non-zero");
+
 #[derive(Debug, Clone, Parser)]
 #[command(version, about, long_about = None)]
 pub struct Args {
     #[command(subcommand)]
     pub operation: Operation,
 
-    /// Key used to encrypt/decrypt data (64-bit number, string, or path to file)
-    #[arg(short = 'k', long, value_parser = Value::from_str, required = true)]
-    pub key: Value,
+    /// Number of worker threads used for bulk encryption (defaults to one per core)
+    #[arg(short = 't', long, global = true)]
+    pub threads: Option<NonZeroUsize>,
+
+    /// Report how KEY and TEXT were interpreted on stderr
+    #[arg(short = 'v', long, global = true)]
+    pub verbose: bool,
+
+    #[command(flatten)]
+    pub keystore: KeystoreArgs,
+}
+
+/// Location and passphrase of the keystore used by `--key-id`.
+#[derive(Debug, Clone, clap::Args)]
+pub struct KeystoreArgs {
+    /// Keystore file holding named keys
+    #[arg(
+        id = "keystore",
+        long = "keystore",
+        value_name = "PATH",
+        env = "DES_KEYSTORE",
+        default_value = "des.keystore",
+        global = true
+    )]
+    pub path: PathBuf,
+
+    /// Passphrase protecting the keystore
+    #[arg(
+        id = "keystore_passphrase",
+        long = "keystore-passphrase",
+        value_name = "PASSPHRASE",
+        env = "DES_KEYSTORE_PASSPHRASE",
+        hide_env_values = true,
+        global = true
+    )]
+    pub passphrase: Option<String>,
+}
+
+/// Key, data and mode shared by encryption and decryption.
+#[derive(Debug, Clone, clap::Args)]
+#[command(group(ArgGroup::new("derivation").args(["kdf", "passphrase"]).multiple(true)))]
+pub struct CipherArgs {
+    /// Key used to encrypt/decrypt data (64-bit number, string, 16- or 24-byte
+    /// hex or base64 key, or path to file)
+    #[arg(short = 'k', long, required_unless_present_any = ["key_id", "passphrase"])]
+    pub key: Option<String>,
+
+    /// Name of a key in the keystore to use instead of KEY
+    #[arg(long, value_name = "NAME", conflicts_with_all = ["key", "kdf"])]
+    pub key_id: Option<String>,
+
+    /// How to interpret KEY (detected automatically by default)
+    #[arg(long, value_enum, default_value_t)]
+    pub key_format: InputFormat,
+
+    /// Derive the key from KEY used as a passphrase of any length, or select
+    /// the function used with --passphrase
+    #[arg(long, value_enum, conflicts_with = "key_format")]
+    pub kdf: Option<KeyDerivation>,
+
+    /// Derive the key from a passphrase, prompting for it if no value is
+    /// given; the salt and iteration count are stored with the ciphertext
+    #[arg(
+        long,
+        value_name = "PASSPHRASE",
+        num_args = 0..=1,
+        require_equals = true,
+        conflicts_with_all = ["key", "key_id", "salt"]
+    )]
+    // `Some(None)` is the flag without a value, which prompts for it.
+    #[allow(clippy::option_option)]
+    pub passphrase: Option<Option<String>>,
+
+    /// Length of the key derived with --passphrase when encrypting [default: des]
+    #[arg(
+        long,
+        value_enum,
+        requires = "passphrase",
+        conflicts_with_all = ["key", "key_id"]
+    )]
+    pub key_length: Option<KeyLengthArg>,
+
+    /// Salt for the key derivation function, required with --kdf and KEY
+    #[arg(
+        long,
+        requires = "kdf",
+        value_parser = clap::builder::NonEmptyStringValueParser::new()
+    )]
+    pub salt: Option<String>,
+
+    /// Iteration count for the key derivation function
+    #[arg(long, default_value_t = KDF_ITERATIONS, requires = "derivation")]
+    pub iterations: NonZeroU32,
 
     /// The text to encrypt/decrypt data (64-bit number, string, or path to file)
-    #[arg(value_name = "TEXT", value_parser = Value::from_str, required = true)]
-    pub text: Value,
+    #[arg(
+        value_name = "TEXT",
+        required_unless_present = "input",
+        conflicts_with = "input"
+    )]
+    pub text: Option<String>,
+
+    /// How to interpret TEXT (detected automatically by default)
+    #[arg(long, value_enum, default_value_t)]
+    pub input_format: InputFormat,
+
+    /// Stream binary data from a file instead of TEXT (`-` for stdin)
+    #[arg(short = 'i', long, value_name = "PATH")]
+    pub input: Option<PathBuf>,
+
+    /// Write the streamed result to a file (`-` or omitted for stdout)
+    #[arg(short = 'o', long, value_name = "PATH", requires = "input")]
+    pub output: Option<PathBuf>,
+
+    /// Encrypt without authentication in this block cipher mode. Streamed
+    /// data is otherwise sealed with TDEA-CBC and a CMAC tag under keys
+    /// derived from KEY; TEXT defaults to ECB
+    #[arg(short = 'm', long, value_enum)]
+    pub mode: Option<CipherMode>,
+
+    /// IV (CBC) or initial counter block (CTR) used when streaming
+    #[arg(long, required_if_eq_any = [("mode", "cbc"), ("mode", "ctr")])]
+    pub iv: Option<String>,
+
+    /// Padding applied to the final block of unauthenticated data
+    /// [default: pkcs7]
+    #[arg(short = 'p', long, value_enum)]
+    pub padding: Option<PaddingMode>,
+
+    /// Report progress on stderr (automatic for large files on a terminal)
+    #[arg(long)]
+    pub progress: bool,
+
+    /// Byte order used to pack 8-byte strings into 64-bit values and back
+    /// (multi-block data and streams are always processed in input order)
+    #[arg(long, value_enum, default_value_t)]
+    pub byte_order: ByteOrder,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum KeyDerivation {
+    /// PBKDF2 with HMAC-SHA1 (RFC 8018), for older systems
+    Pbkdf2Sha1,
+    /// PBKDF2 with HMAC-SHA256 (RFC 8018)
+    #[default]
+    Pbkdf2Sha256,
+}
+
+impl KeyDerivation {
+    /// Derive a single DES key from `passphrase`.
+    #[must_use]
+    pub fn derive(self, passphrase: &str, salt: &str, iterations: NonZeroU32) -> Key {
+        derive_key(
+            self.into(),
+            passphrase.as_bytes(),
+            salt.as_bytes(),
+            iterations.get(),
+            KeyLength::Single,
+        )
+    }
+}
+
+impl From<KeyDerivation> for Prf {
+    fn from(kdf: KeyDerivation) -> Self {
+        match kdf {
+            KeyDerivation::Pbkdf2Sha1 => Self::HmacSha1,
+            KeyDerivation::Pbkdf2Sha256 => Self::HmacSha256,
+        }
+    }
+}
+
+impl From<Prf> for KeyDerivation {
+    fn from(prf: Prf) -> Self {
+        match prf {
+            Prf::HmacSha1 => Self::Pbkdf2Sha1,
+            Prf::HmacSha256 => Self::Pbkdf2Sha256,
+        }
+    }
+}
+
+impl Display for KeyDerivation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Pbkdf2Sha1 => f.write_str("PBKDF2-HMAC-SHA1"),
+            Self::Pbkdf2Sha256 => f.write_str("PBKDF2-HMAC-SHA256"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum ByteOrder {
+    /// First byte is the most significant (FIPS 46-3, OpenSSL)
+    #[default]
+    Big,
+    /// First byte is the least significant
+    Little,
+}
+
+impl ByteOrder {
+    /// Combine 8 bytes into a 64-bit value.
+    #[must_use]
+    pub const fn pack(self, bytes: [u8; 8]) -> u64 {
+        match self {
+            Self::Big => u64::from_be_bytes(bytes),
+            Self::Little => u64::from_le_bytes(bytes),
+        }
+    }
+
+    /// Split a 64-bit value into 8 bytes.
+    #[must_use]
+    pub const fn unpack(self, value: u64) -> [u8; 8] {
+        match self {
+            Self::Big => value.to_be_bytes(),
+            Self::Little => value.to_le_bytes(),
+        }
+    }
 }
 
-#[derive(Debug, Clone, Subcommand, Default)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum InputFormat {
+    /// Guess from the value (decimal, existing file, 0x/0b prefix, 8 ASCII characters)
+    #[default]
+    Auto,
+    /// Hexadecimal digits, with an optional 0x prefix
+    Hex,
+    /// Base64 encoding of 8 bytes
+    Base64,
+    /// Exactly 8 ASCII characters
+    Ascii,
+    /// UTF-8 text of any length, padded to whole blocks (TEXT only)
+    Text,
+    /// Unsigned decimal number
+    Decimal,
+    /// Binary digits, with an optional 0b prefix
+    Binary,
+    /// Path to a text file whose contents are detected automatically
+    File,
+    /// Path to a binary file holding exactly 8 bytes
+    Raw,
+}
+
+impl Display for InputFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::Auto => "auto",
+            Self::Hex => "hex",
+            Self::Base64 => "base64",
+            Self::Ascii => "ASCII",
+            Self::Text => "UTF-8 text",
+            Self::Decimal => "decimal",
+            Self::Binary => "binary",
+            Self::File => "file",
+            Self::Raw => "raw bytes",
+        };
+        f.write_str(name)
+    }
+}
+
+/// How a [`Value`] was read from the command line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Interpretation {
+    /// Encoding of the value itself.
+    pub format: InputFormat,
+    /// File the value was read from, if any.
+    pub file: Option<PathBuf>,
+}
+
+impl Interpretation {
+    const fn direct(format: InputFormat) -> Self {
+        Self { format, file: None }
+    }
+}
+
+impl Display for Interpretation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.file {
+            Some(path) => write!(f, "{} read from '{}'", self.format, path.display()),
+            None => write!(f, "{}", self.format),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Subcommand)]
 pub enum Operation {
     /// Encrypt data
-    #[default]
-    Encrypt,
+    Encrypt(CipherArgs),
     /// Decrypt data
     Decrypt {
+        #[command(flatten)]
+        cipher: CipherArgs,
+
         /// Output format for decrypted data
         #[arg(short = 'f', long, value_enum)]
         output_format: Option<OutputFormat>,
+
+        /// How invalid UTF-8 is handled by the text output format
+        #[arg(long, value_enum, default_value_t)]
+        utf8: Utf8Mode,
+    },
+    /// Generate a random key, printed with its key check value
+    Keygen(KeygenArgs),
+    /// Manage named keys in an encrypted keystore
+    Keystore {
+        #[command(subcommand)]
+        command: KeystoreCommand,
+    },
+    /// Inspect and manage DES and TDEA keys
+    Key {
+        #[command(subcommand)]
+        command: KeyCommand,
+    },
+    /// Generate or verify an ISO/IEC 9797-1 MAC (ANSI X9.9 or X9.19)
+    Mac(MacArgs),
+    /// Build, encrypt and translate ISO 9564 PIN blocks
+    Pin {
+        #[command(subcommand)]
+        command: PinCommand,
+    },
+    /// Generate or verify card verification values (CVV, CVC, CVV2, iCVV)
+    Cvv {
+        #[command(subcommand)]
+        command: CvvCommand,
+    },
+    /// Wrap, unwrap and inspect TR-31 key blocks
+    Keyblock {
+        #[command(subcommand)]
+        command: KeyblockCommand,
+    },
+    /// Compute and check EMV application cryptograms
+    Emv {
+        #[command(subcommand)]
+        command: EmvCommand,
     },
 }
 
+#[derive(Debug, Clone, Subcommand)]
+pub enum KeyCommand {
+    /// Print the key check value of a key
+    Kcv {
+        #[command(flatten)]
+        key: KeyArgs,
+
+        /// How the check value is computed
+        #[arg(short = 'm', long, value_enum, default_value_t)]
+        method: KcvMethodArg,
+
+        /// Encoding of the check value
+        #[arg(short = 'f', long, value_enum, default_value_t)]
+        format: EncodingArg,
+    },
+    /// Split a key into XOR components for separate custodians
+    Split {
+        #[command(flatten)]
+        key: KeyArgs,
+
+        /// Number of components
+        #[arg(
+            short = 'n',
+            long,
+            default_value_t = 2,
+            value_parser = clap::value_parser!(u8).range(2..)
+        )]
+        components: u8,
+
+        /// Encoding of the components and check values
+        #[arg(short = 'f', long, value_enum, default_value_t)]
+        format: EncodingArg,
+    },
+    /// Combine XOR components into a key
+    Combine {
+        /// Key component as hex or base64, or a path to a file holding it
+        #[arg(
+            short = 'c',
+            long = "component",
+            value_name = "COMPONENT",
+            required = true
+        )]
+        components: Vec<String>,
+
+        /// Expected check value of the combined key (3 bytes zero-block or 5 bytes CMAC, hex)
+        #[arg(long)]
+        kcv: Option<String>,
+
+        /// Encoding of the key and check values
+        #[arg(short = 'f', long, value_enum, default_value_t)]
+        format: EncodingArg,
+    },
+    /// Split a key into Shamir shares, any THRESHOLD of which recover it
+    Share {
+        #[command(flatten)]
+        key: KeyArgs,
+
+        /// Number of shares needed to recover the key
+        #[arg(
+            short = 'm',
+            long,
+            value_parser = clap::value_parser!(u8).range(2..)
+        )]
+        threshold: u8,
+
+        /// Number of shares to create
+        #[arg(
+            short = 'n',
+            long,
+            value_parser = clap::value_parser!(u8).range(2..)
+        )]
+        shares: u8,
+
+        /// Encoding of the shares and check value
+        #[arg(short = 'f', long, value_enum, default_value_t)]
+        format: EncodingArg,
+    },
+    /// Recover a key from Shamir shares
+    Recover {
+        /// Share as hex or base64, or a path to a file holding it
+        #[arg(short = 's', long = "share", value_name = "SHARE", required = true)]
+        shares: Vec<String>,
+
+        /// Encoding of the key and check value
+        #[arg(short = 'f', long, value_enum, default_value_t)]
+        format: EncodingArg,
+    },
+}
+
+#[derive(Debug, Clone, Subcommand)]
+pub enum KeystoreCommand {
+    /// Create an empty keystore protected by the keystore passphrase
+    Init {
+        /// PBKDF2 iteration count for the master key
+        #[arg(
+            long,
+            default_value_t = KDF_ITERATIONS.get(),
+            value_parser = clap::value_parser!(u32).range(1..=i64::from(MAX_ITERATIONS))
+        )]
+        iterations: u32,
+    },
+    /// Store a key under a name
+    Add {
+        /// Name used to refer to the key with --key-id
+        name: String,
+
+        /// Key of 8, 16 or 24 bytes as hex or base64, or a path to a file holding it
+        #[arg(
+            short = 'k',
+            long,
+            required_unless_present = "generate",
+            conflicts_with = "generate"
+        )]
+        key: Option<String>,
+
+        /// How to interpret KEY: hex, base64, file or raw (detected automatically by default)
+        #[arg(long, value_enum, default_value_t)]
+        key_format: InputFormat,
+
+        /// Generate a random key of this length instead of storing KEY
+        #[arg(short = 'g', long, value_enum)]
+        generate: Option<KeyLengthArg>,
+
+        /// What the key may be used for
+        #[arg(short = 'u', long, value_enum, default_value_t)]
+        usage: KeyUsageArg,
+    },
+    /// List the stored keys and their metadata
+    List,
+    /// Delete a key
+    Remove {
+        /// Name of the key
+        name: String,
+    },
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum KeyUsageArg {
+    /// Any purpose
+    #[default]
+    General,
+    /// Data encryption and decryption
+    Encryption,
+    /// Message authentication codes
+    Mac,
+    /// Encryption of other keys
+    KeyWrapping,
+    /// Encryption of PIN blocks
+    PinEncryption,
+    /// Card verification values (CVV and CVC)
+    CardVerification,
+    /// EMV issuer master keys for application cryptograms
+    ApplicationCryptogram,
+}
+
+impl From<KeyUsageArg> for KeyUsage {
+    fn from(usage: KeyUsageArg) -> Self {
+        match usage {
+            KeyUsageArg::General => Self::General,
+            KeyUsageArg::Encryption => Self::Encryption,
+            KeyUsageArg::Mac => Self::Mac,
+            KeyUsageArg::KeyWrapping => Self::KeyWrapping,
+            KeyUsageArg::PinEncryption => Self::PinEncryption,
+            KeyUsageArg::CardVerification => Self::CardVerification,
+            KeyUsageArg::ApplicationCryptogram => Self::ApplicationCryptogram,
+        }
+    }
+}
+
+/// A DES, 2TDEA or 3TDEA key given on the command line or by name.
+#[derive(Debug, Clone, clap::Args)]
+pub struct KeyArgs {
+    /// Key of 8, 16 or 24 bytes as hex or base64, or a path to a file holding it
+    #[arg(short = 'k', long, required_unless_present = "key_id")]
+    pub key: Option<String>,
+
+    /// Name of a key in the keystore to use instead of KEY
+    #[arg(long, value_name = "NAME", conflicts_with = "key")]
+    pub key_id: Option<String>,
+
+    /// How to interpret KEY: hex, base64, file or raw (detected automatically by default)
+    #[arg(long, value_enum, default_value_t)]
+    pub key_format: InputFormat,
+}
+
+/// Parse a DES or TDEA key given as hex, base64 or a file.
+///
+/// # Errors
+///
+/// Returns an error if `s` cannot be decoded or has an invalid length.
+pub fn load_key(s: &str, format: InputFormat) -> Result<Key, ValueError> {
+    let bytes = parse_key_bytes(s, format)?;
+    Ok(Key::from_bytes(&bytes)?)
+}
+
+/// Parse a Shamir share given as hex, base64 or a file.
+///
+/// # Errors
+///
+/// Returns an error if `s` cannot be decoded or is not a valid share.
+pub fn load_share(s: &str) -> Result<Share, ValueError> {
+    let bytes = parse_key_bytes(s, InputFormat::Auto)?;
+    Ok(Share::from_bytes(&bytes)?)
+}
+
+/// Parse a key check value given in hex, returning the method implied by
+/// its length.
+///
+/// # Errors
+///
+/// Returns an error for invalid hex or a length other than 3 or 5 bytes.
+pub fn parse_kcv(s: &str) -> Result<(KcvMethod, Vec<u8>), ValueError> {
+    let bytes = decode(s, Encoding::Hex)?;
+    let method = [KcvMethod::ZeroBlock, KcvMethod::Cmac]
+        .into_iter()
+        .find(|method| method.length() == bytes.len())
+        .ok_or_else(|| {
+            ValueError::InvalidFormat(format!(
+                "KCV must be 3 (zero-block) or 5 (CMAC) bytes, got {}",
+                bytes.len()
+            ))
+        })?;
+    Ok((method, bytes))
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum KcvMethodArg {
+    /// First 3 bytes of the encrypted zero block
+    #[default]
+    ZeroBlock,
+    /// First 5 bytes of the CMAC of the zero block
+    Cmac,
+}
+
+impl From<KcvMethodArg> for KcvMethod {
+    fn from(method: KcvMethodArg) -> Self {
+        match method {
+            KcvMethodArg::ZeroBlock => Self::ZeroBlock,
+            KcvMethodArg::Cmac => Self::Cmac,
+        }
+    }
+}
+
+/// Key, message and algorithm of `des mac`.
+#[derive(Debug, Clone, clap::Args)]
+pub struct MacArgs {
+    #[command(flatten)]
+    pub key: KeyArgs,
+
+    /// MAC algorithm
+    #[arg(short = 'a', long, value_enum, default_value_t)]
+    pub algorithm: MacAlgorithmArg,
+
+    /// ISO/IEC 9797-1 padding method
+    #[arg(short = 'p', long, value_enum, default_value_t)]
+    pub padding: MacPaddingArg,
+
+    /// Number of leftmost MAC bytes to print
+    #[arg(
+        short = 'l',
+        long,
+        default_value_t = 8,
+        value_parser = clap::value_parser!(u8).range(MIN_MAC_LENGTH as i64..=8)
+    )]
+    pub length: u8,
+
+    /// Verify this MAC (hex, 4 to 8 bytes) instead of printing one
+    #[arg(long, value_name = "MAC", conflicts_with = "length")]
+    pub verify: Option<String>,
+
+    /// The message (64-bit number, hex or base64 data, or a string)
+    #[arg(
+        value_name = "TEXT",
+        required_unless_present = "input",
+        conflicts_with = "input"
+    )]
+    pub text: Option<String>,
+
+    /// How to interpret TEXT (detected automatically by default)
+    #[arg(long, value_enum, default_value_t)]
+    pub input_format: InputFormat,
+
+    /// Read the message from a file instead of TEXT (`-` for stdin)
+    #[arg(short = 'i', long, value_name = "PATH")]
+    pub input: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum MacAlgorithmArg {
+    /// MAC algorithm 1: CBC-MAC (ANSI X9.9; TDEA for longer keys)
+    #[default]
+    Cbc,
+    /// MAC algorithm 3: retail MAC with a 16-byte key (ANSI X9.19)
+    Retail,
+}
+
+impl From<MacAlgorithmArg> for MacAlgorithm {
+    fn from(algorithm: MacAlgorithmArg) -> Self {
+        match algorithm {
+            MacAlgorithmArg::Cbc => Self::Cbc,
+            MacAlgorithmArg::Retail => Self::Retail,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum MacPaddingArg {
+    /// Method 1: zero bytes
+    #[default]
+    #[value(name = "1")]
+    Method1,
+    /// Method 2: 0x80, then zero bytes
+    #[value(name = "2")]
+    Method2,
+    /// Method 3: length block, then zero bytes
+    #[value(name = "3")]
+    Method3,
+}
+
+impl From<MacPaddingArg> for MacPadding {
+    fn from(padding: MacPaddingArg) -> Self {
+        match padding {
+            MacPaddingArg::Method1 => Self::Method1,
+            MacPaddingArg::Method2 => Self::Method2,
+            MacPaddingArg::Method3 => Self::Method3,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Subcommand)]
+pub enum PinCommand {
+    /// Build a plaintext PIN block
+    Build {
+        /// PIN block format
+        #[arg(short = 'f', long, value_enum, default_value_t)]
+        format: PinFormatArg,
+
+        /// Primary account number (required by formats 0, 3 and 4)
+        #[arg(long)]
+        pan: Option<String>,
+
+        /// PIN of 4 to 12 digits
+        pin: String,
+    },
+    /// Parse a plaintext PIN block (16 hex digits, or 32 for a format 4 PIN
+    /// field)
+    Parse {
+        /// Primary account number (required by formats 0 and 3)
+        #[arg(long)]
+        pan: Option<String>,
+
+        /// PIN block in hex
+        block: String,
+    },
+    /// Encrypt a PIN as a PIN block under a PIN encryption key
+    Encrypt {
+        #[command(flatten)]
+        key: KeyArgs,
+
+        /// PIN block format
+        #[arg(short = 'f', long, value_enum, default_value_t)]
+        format: PinFormatArg,
+
+        /// Primary account number (required by formats 0 and 3)
+        #[arg(long)]
+        pan: Option<String>,
+
+        /// PIN of 4 to 12 digits
+        pin: String,
+    },
+    /// Decrypt a PIN block and print the PIN
+    Decrypt {
+        #[command(flatten)]
+        key: KeyArgs,
+
+        /// Primary account number (required by formats 0 and 3)
+        #[arg(long)]
+        pan: Option<String>,
+
+        /// Encrypted PIN block in hex
+        block: String,
+    },
+    /// Re-encrypt a PIN block under another key, optionally changing its
+    /// format
+    Translate {
+        #[command(flatten)]
+        key: KeyArgs,
+
+        /// Key to re-encrypt the PIN block under, as hex or base64, or a
+        /// path to a file holding it
+        #[arg(long, value_name = "KEY", required_unless_present = "to_key_id")]
+        to_key: Option<String>,
+
+        /// Name of a key in the keystore to use instead of --to-key
+        #[arg(long, value_name = "NAME", conflicts_with = "to_key")]
+        to_key_id: Option<String>,
+
+        /// How to interpret --to-key
+        #[arg(long, value_enum, default_value_t)]
+        to_key_format: InputFormat,
+
+        /// Format of the translated PIN block [default: unchanged]
+        #[arg(short = 'f', long, value_enum)]
+        format: Option<PinFormatArg>,
+
+        /// Primary account number (required by formats 0 and 3)
+        #[arg(long)]
+        pan: Option<String>,
+
+        /// Encrypted PIN block in hex
+        block: String,
+    },
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum PinFormatArg {
+    /// ISO format 0 (ANSI X9.8): PIN combined with the PAN
+    #[default]
+    #[value(name = "0")]
+    Iso0,
+    /// ISO format 1: PIN and transaction field
+    #[value(name = "1")]
+    Iso1,
+    /// ISO format 2: PIN only, between chip card and terminal
+    #[value(name = "2")]
+    Iso2,
+    /// ISO format 3: PIN with random fill, combined with the PAN
+    #[value(name = "3")]
+    Iso3,
+    /// ISO format 4: plaintext PIN and PAN fields of the AES format (build only)
+    #[value(name = "4")]
+    Iso4,
+}
+
+impl PinFormatArg {
+    /// The 64-bit format, or `None` for format 4.
+    #[must_use]
+    pub const fn format(self) -> Option<PinFormat> {
+        match self {
+            Self::Iso0 => Some(PinFormat::Iso0),
+            Self::Iso1 => Some(PinFormat::Iso1),
+            Self::Iso2 => Some(PinFormat::Iso2),
+            Self::Iso3 => Some(PinFormat::Iso3),
+            Self::Iso4 => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Subcommand)]
+pub enum CvvCommand {
+    /// Compute the CVV of a card
+    Generate(CardArgs),
+    /// Check a CVV against a card
+    Verify {
+        #[command(flatten)]
+        card: CardArgs,
+
+        /// CVV to check
+        cvv: String,
+    },
+}
+
+#[derive(Debug, Clone, clap::Args)]
+pub struct CardArgs {
+    #[command(flatten)]
+    pub key: KeyArgs,
+
+    /// Primary account number
+    #[arg(long)]
+    pub pan: String,
+
+    /// Expiry date as YYMM
+    #[arg(short = 'e', long, value_name = "YYMM")]
+    pub expiry: String,
+
+    /// Compute a CVV2 or iCVV instead of the magnetic stripe CVV
+    #[arg(long = "type", value_enum)]
+    pub kind: Option<CvvKind>,
+
+    /// Service code from the magnetic stripe, for the CVV
+    #[arg(
+        short = 's',
+        long,
+        required_unless_present = "kind",
+        conflicts_with = "kind"
+    )]
+    pub service_code: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum CvvKind {
+    /// CVV2 or CVC2 printed on the card (service code 000)
+    Cvv2,
+    /// iCVV on the chip (service code 999)
+    Icvv,
+}
+
+#[derive(Debug, Clone, Subcommand)]
+pub enum EmvCommand {
+    /// Compute or verify the ARQC of a test card, and optionally the ARPC
+    Arqc(ArqcArgs),
+}
+
+/// Card, transaction and response of `des emv arqc`.
+#[derive(Debug, Clone, clap::Args)]
+pub struct ArqcArgs {
+    /// Issuer master key for application cryptograms
+    #[command(flatten)]
+    pub key: KeyArgs,
+
+    /// Primary account number
+    #[arg(long)]
+    pub pan: String,
+
+    /// PAN sequence number
+    #[arg(
+        long,
+        default_value_t = 0,
+        value_parser = clap::value_parser!(u8).range(0..=i64::from(MAX_PSN))
+    )]
+    pub psn: u8,
+
+    /// Application transaction counter (4 hex digits)
+    #[arg(long)]
+    pub atc: String,
+
+    /// ISO/IEC 9797-1 padding method; Visa CVN 10 uses method 1
+    #[arg(short = 'p', long, value_enum, default_value = "2")]
+    pub padding: MacPaddingArg,
+
+    /// Verify this ARQC (16 hex digits) instead of printing one
+    #[arg(long, value_name = "ARQC")]
+    pub verify: Option<String>,
+
+    /// Authorization response code (4 hex digits) for an ARPC with method 1
+    #[arg(long)]
+    pub arc: Option<String>,
+
+    /// Card status update (8 hex digits) for an ARPC with method 2
+    #[arg(long, conflicts_with = "arc")]
+    pub csu: Option<String>,
+
+    /// Proprietary authentication data (up to 8 bytes in hex) for ARPC
+    /// method 2
+    #[arg(long, value_name = "HEX", requires = "csu")]
+    pub proprietary_data: Option<String>,
+
+    /// Transaction data covered by the ARQC, in hex
+    pub data: String,
+}
+
+#[derive(Debug, Clone, Subcommand)]
+pub enum KeyblockCommand {
+    /// Wrap a key in a key block under a key block protection key
+    Wrap(WrapArgs),
+    /// Check a key block and print its header and key
+    Unwrap {
+        #[command(flatten)]
+        key: KeyArgs,
+
+        /// Key block
+        block: String,
+    },
+    /// Print the header of a key block without checking it
+    Inspect {
+        /// Key block
+        block: String,
+    },
+}
+
+/// Keys and header fields of `des keyblock wrap`.
+#[derive(Debug, Clone, clap::Args)]
+pub struct WrapArgs {
+    #[command(flatten)]
+    pub key: KeyArgs,
+
+    /// Key to wrap, as hex or base64, or a path to a file holding it
+    #[arg(long, value_name = "KEY", required_unless_present = "wrapped_key_id")]
+    pub wrapped_key: Option<String>,
+
+    /// Name of a key in the keystore to wrap instead of --wrapped-key
+    #[arg(long, value_name = "NAME", conflicts_with = "wrapped_key")]
+    pub wrapped_key_id: Option<String>,
+
+    /// How to interpret --wrapped-key
+    #[arg(long, value_enum, default_value_t)]
+    pub wrapped_key_format: InputFormat,
+
+    /// Key block version
+    #[arg(long, value_enum, default_value_t, ignore_case = true)]
+    pub block_version: VersionArg,
+
+    /// Key usage, such as P0 for PIN encryption or K0 for key encryption
+    #[arg(short = 'u', long)]
+    pub usage: Usage,
+
+    /// Mode of use
+    #[arg(short = 'm', long, value_enum, default_value_t, ignore_case = true)]
+    pub mode_of_use: ModeOfUseArg,
+
+    /// Key version number, or 00 if key versions are not used
+    #[arg(long, default_value = "00")]
+    pub key_version: String,
+
+    /// Whether the key may be exported again
+    #[arg(short = 'x', long, value_enum, default_value_t, ignore_case = true)]
+    pub exportability: ExportabilityArg,
+
+    /// Optional block as ID=DATA, such as KS=FFFF9876543210E00000; may be
+    /// repeated
+    #[arg(long = "optional-block", value_name = "ID=DATA", value_parser = parse_optional_block)]
+    pub optional_blocks: Vec<OptionalBlock>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum VersionArg {
+    /// TDEA key variant binding (deprecated)
+    A,
+    /// TDEA key derivation binding
+    #[default]
+    B,
+    /// TDEA key variant binding
+    C,
+}
+
+impl From<VersionArg> for Version {
+    fn from(version: VersionArg) -> Self {
+        match version {
+            VersionArg::A => Self::A,
+            VersionArg::B => Self::B,
+            VersionArg::C => Self::C,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum ModeOfUseArg {
+    /// Encrypt and decrypt, or wrap and unwrap
+    #[value(name = "B")]
+    Both,
+    /// Generate and verify
+    #[value(name = "C")]
+    Calculate,
+    /// Decrypt or unwrap only
+    #[value(name = "D")]
+    Decrypt,
+    /// Encrypt or wrap only
+    #[value(name = "E")]
+    Encrypt,
+    /// Generate only
+    #[value(name = "G")]
+    Generate,
+    /// No special restrictions
+    #[default]
+    #[value(name = "N")]
+    NoRestrictions,
+    /// Signature only
+    #[value(name = "S")]
+    Sign,
+    /// Sign and decrypt
+    #[value(name = "T")]
+    SignAndDecrypt,
+    /// Verify only
+    #[value(name = "V")]
+    Verify,
+    /// Derive other keys
+    #[value(name = "X")]
+    Derive,
+    /// Create key variants
+    #[value(name = "Y")]
+    Variant,
+}
+
+impl From<ModeOfUseArg> for ModeOfUse {
+    fn from(mode: ModeOfUseArg) -> Self {
+        match mode {
+            ModeOfUseArg::Both => Self::Both,
+            ModeOfUseArg::Calculate => Self::Calculate,
+            ModeOfUseArg::Decrypt => Self::Decrypt,
+            ModeOfUseArg::Encrypt => Self::Encrypt,
+            ModeOfUseArg::Generate => Self::Generate,
+            ModeOfUseArg::NoRestrictions => Self::NoRestrictions,
+            ModeOfUseArg::Sign => Self::Sign,
+            ModeOfUseArg::SignAndDecrypt => Self::SignAndDecrypt,
+            ModeOfUseArg::Verify => Self::Verify,
+            ModeOfUseArg::Derive => Self::Derive,
+            ModeOfUseArg::Variant => Self::Variant,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum ExportabilityArg {
+    /// Exportable under a trusted key
+    #[default]
+    #[value(name = "E")]
+    Exportable,
+    /// Not exportable
+    #[value(name = "N")]
+    NonExportable,
+    /// Sensitive
+    #[value(name = "S")]
+    Sensitive,
+}
+
+impl From<ExportabilityArg> for Exportability {
+    fn from(exportability: ExportabilityArg) -> Self {
+        match exportability {
+            ExportabilityArg::Exportable => Self::Exportable,
+            ExportabilityArg::NonExportable => Self::NonExportable,
+            ExportabilityArg::Sensitive => Self::Sensitive,
+        }
+    }
+}
+
+/// Parse an optional key block header block given as `ID=DATA`.
+///
+/// # Errors
+///
+/// Returns an error if `s` has no `=`.
+pub fn parse_optional_block(s: &str) -> Result<OptionalBlock, ValueError> {
+    let (id, data) = s
+        .split_once('=')
+        .ok_or_else(|| ValueError::InvalidFormat(format!("'{s}' is not ID=DATA")))?;
+    Ok(OptionalBlock {
+        id: id.to_owned(),
+        data: data.to_owned(),
+    })
+}
+
+#[derive(Debug, Clone, clap::Args)]
+pub struct KeygenArgs {
+    /// Key length
+    #[arg(short = 'l', long, value_enum, default_value_t)]
+    pub length: KeyLengthArg,
+
+    /// Encoding of the key and key check value
+    #[arg(short = 'f', long, value_enum, default_value_t)]
+    pub format: EncodingArg,
+
+    /// Also generate a random 64-bit IV
+    #[arg(long)]
+    pub iv: bool,
+
+    /// Write the key to a new file readable only by its owner instead of
+    /// printing it
+    #[arg(short = 'o', long, value_name = "PATH")]
+    pub output: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum KeyLengthArg {
+    /// Single DES (8 bytes)
+    #[default]
+    Des,
+    /// Two-key Triple DES (16 bytes)
+    #[value(name = "2tdea")]
+    Tdea2,
+    /// Three-key Triple DES (24 bytes)
+    #[value(name = "3tdea")]
+    Tdea3,
+}
+
+impl From<KeyLengthArg> for KeyLength {
+    fn from(length: KeyLengthArg) -> Self {
+        match length {
+            KeyLengthArg::Des => Self::Single,
+            KeyLengthArg::Tdea2 => Self::Double,
+            KeyLengthArg::Tdea3 => Self::Triple,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Default, ValueEnum)]
 pub enum OutputFormat {
     /// Binary output
@@ -71,10 +1188,311 @@ pub enum OutputFormat {
     /// Hexadecimal output
     #[default]
     Hex,
+    /// Base64 output
+    Base64,
     /// Text output (ASCII)
     Text,
 }
 
+#[derive(Debug, Clone, Copy, Default, ValueEnum)]
+pub enum CipherMode {
+    /// Electronic codebook
+    #[default]
+    Ecb,
+    /// Cipher block chaining (requires --iv)
+    Cbc,
+    /// Counter mode (requires --iv)
+    Ctr,
+}
+
+impl CipherMode {
+    /// Combine the mode with its IV or initial counter.
+    #[must_use]
+    pub fn with_iv(self, iv: Option<Value>) -> Mode {
+        let iv = iv.map(Value::as_64).unwrap_or_default();
+        match self {
+            Self::Ecb => Mode::Ecb,
+            Self::Cbc => Mode::Cbc { iv },
+            Self::Ctr => Mode::Ctr { counter: iv },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, ValueEnum)]
+pub enum PaddingMode {
+    /// PKCS#7 padding
+    #[default]
+    Pkcs7,
+    /// ISO/IEC 7816-4 padding (0x80 followed by zeros)
+    Iso7816,
+    /// No padding (input must be block-aligned unless using CTR)
+    None,
+}
+
+impl From<PaddingMode> for Padding {
+    fn from(padding: PaddingMode) -> Self {
+        match padding {
+            PaddingMode::Pkcs7 => Self::Pkcs7,
+            PaddingMode::Iso7816 => Self::Iso7816,
+            PaddingMode::None => Self::None,
+        }
+    }
+}
+
+impl OutputFormat {
+    /// Renders a 64-bit block in this format, laying out the bytes of the
+    /// base64 and text formats in `order`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if text output is not valid UTF-8 in strict mode.
+    pub fn format(
+        &self,
+        value: u64,
+        order: ByteOrder,
+        utf8: Utf8Mode,
+    ) -> Result<String, Utf8Error> {
+        match self {
+            Self::Binary => Ok(format!("{value:064b}")),
+            Self::Octal => Ok(format!("{value:022o}")),
+            Self::Decimal => Ok(value.to_string()),
+            Self::Hex => Ok(format!("{value:016X}")),
+            Self::Base64 => Ok(Encoding::Base64.encode(&order.unpack(value))),
+            Self::Text => utf8.decode(&order.unpack(value)),
+        }
+    }
+
+    /// Whether this format can render data longer than a single block.
+    #[must_use]
+    pub const fn supports_data(&self) -> bool {
+        matches!(self, Self::Hex | Self::Base64 | Self::Text)
+    }
+
+    /// Renders arbitrary bytes in this format. Formats without
+    /// [`supports_data`](Self::supports_data) fall back to hex.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if text output is not valid UTF-8 in strict mode.
+    pub fn format_bytes(&self, bytes: &[u8], utf8: Utf8Mode) -> Result<String, Utf8Error> {
+        match self {
+            Self::Base64 => Ok(Encoding::Base64.encode(bytes)),
+            Self::Text => utf8.decode(bytes),
+            Self::Hex | Self::Binary | Self::Octal | Self::Decimal => {
+                Ok(Encoding::Hex.encode(bytes))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Utf8Mode {
+    /// Replace invalid sequences with U+FFFD
+    #[default]
+    Lossy,
+    /// Fail on invalid sequences
+    Strict,
+}
+
+impl Utf8Mode {
+    /// Decode `bytes` as UTF-8.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error for invalid UTF-8 in strict mode.
+    pub fn decode(self, bytes: &[u8]) -> Result<String, Utf8Error> {
+        match self {
+            Self::Lossy => Ok(String::from_utf8_lossy(bytes).into_owned()),
+            Self::Strict => str::from_utf8(bytes).map(str::to_owned),
+        }
+    }
+}
+
+/// Text encoding of binary data.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum EncodingArg {
+    /// Uppercase hexadecimal
+    #[default]
+    Hex,
+    /// Standard base64 with padding
+    Base64,
+}
+
+impl From<EncodingArg> for Encoding {
+    fn from(encoding: EncodingArg) -> Self {
+        match encoding {
+            EncodingArg::Hex => Self::Hex,
+            EncodingArg::Base64 => Self::Base64,
+        }
+    }
+}
+
+impl From<Encoding> for OutputFormat {
+    fn from(encoding: Encoding) -> Self {
+        match encoding {
+            Encoding::Hex => Self::Hex,
+            Encoding::Base64 => Self::Base64,
+        }
+    }
+}
+
+/// TEXT given on the command line: a single 64-bit block, or hex/base64 data
+/// of any length.
+#[derive(Debug, Clone)]
+pub enum Text {
+    Block(Value),
+    Data { bytes: Vec<u8>, encoding: Encoding },
+}
+
+impl Text {
+    /// Parse `s` as `format`. Hex with more than 16 digits or with spaces or
+    /// colons between bytes, base64 that does not decode to exactly 8 bytes,
+    /// and UTF-8 text is read as data; everything else as a single [`Value`].
+    /// Automatic detection falls back to text for strings that are not
+    /// valid values.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `s` is not valid in the requested format, or if a
+    /// file cannot be read.
+    pub fn parse(
+        s: &str,
+        format: InputFormat,
+        order: ByteOrder,
+    ) -> Result<(Self, Interpretation), ValueError> {
+        let trimmed = s.trim();
+        let data = |bytes, encoding, format| {
+            Ok((
+                Self::Data { bytes, encoding },
+                Interpretation::direct(format),
+            ))
+        };
+
+        match format {
+            InputFormat::Hex if is_hex_data(trimmed) => {
+                data(decode(trimmed, Encoding::Hex)?, Encoding::Hex, format)
+            }
+            InputFormat::Auto if is_prefixed_hex_data(trimmed) || is_separated_hex(trimmed) => {
+                data(
+                    decode(trimmed, Encoding::Hex)?,
+                    Encoding::Hex,
+                    InputFormat::Hex,
+                )
+            }
+            InputFormat::Base64 => {
+                let bytes = decode(trimmed, Encoding::Base64)?;
+                if bytes.len() == 8 {
+                    Value::parse(trimmed, format, order)
+                        .map(|(value, how)| (Self::Block(value), how))
+                } else {
+                    data(bytes, Encoding::Base64, format)
+                }
+            }
+            InputFormat::Text => data(s.as_bytes().to_vec(), Encoding::Hex, format),
+            InputFormat::Auto => match Value::parse(s, format, order) {
+                Ok((value, how)) => Ok((Self::Block(value), how)),
+                Err(_) if !s.is_empty() && !looks_numeric(trimmed) => {
+                    data(s.as_bytes().to_vec(), Encoding::Hex, InputFormat::Text)
+                }
+                Err(e) => Err(e),
+            },
+            _ => Value::parse(s, format, order).map(|(value, how)| (Self::Block(value), how)),
+        }
+    }
+
+    /// Parse ciphertext given to `decrypt`. Automatic detection reads whole
+    /// bytes of at least 16 bare hex digits as hex, even if they are also a
+    /// decimal number, so that the output of `encrypt` can be passed back as
+    /// is; anything else is parsed like [`Text::parse`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `s` is not valid in the requested format, or if a
+    /// file cannot be read.
+    pub fn parse_ciphertext(
+        s: &str,
+        format: InputFormat,
+        order: ByteOrder,
+    ) -> Result<(Self, Interpretation), ValueError> {
+        match format {
+            InputFormat::Auto if is_bare_hex(s.trim()) => Self::parse(s, InputFormat::Hex, order),
+            _ => Self::parse(s, format, order),
+        }
+    }
+
+    /// The bytes of TEXT, with a single block unpacked in `order`.
+    #[must_use]
+    pub fn into_data(self, order: ByteOrder) -> (Vec<u8>, Encoding) {
+        match self {
+            Self::Block(value) => (order.unpack(value.as_64()).to_vec(), Encoding::Hex),
+            Self::Data { bytes, encoding } => (bytes, encoding),
+        }
+    }
+}
+
+/// Whether `s` was meant as a number, so that a typo is reported instead of
+/// being encrypted as text.
+fn looks_numeric(s: &str) -> bool {
+    ["0x", "0X", "0b", "0B"]
+        .iter()
+        .any(|prefix| s.starts_with(prefix))
+        || s.chars().all(|ch| ch.is_ascii_digit() || ch == '-')
+}
+
+/// Strip an optional 0x prefix.
+fn hex_digits(s: &str) -> &str {
+    s.strip_prefix("0x")
+        .or_else(|| s.strip_prefix("0X"))
+        .unwrap_or(s)
+}
+
+const fn is_hex_separator(ch: char) -> bool {
+    matches!(ch, ' ' | ':')
+}
+
+/// Whether hex input spans more than a single 64-bit value.
+fn is_hex_data(s: &str) -> bool {
+    let digits = hex_digits(s);
+    digits.contains(is_hex_separator) || digits.len() > 16
+}
+
+/// `0x`-prefixed hex longer than one block.
+fn is_prefixed_hex_data(s: &str) -> bool {
+    hex_digits(s).len() < s.len() && is_hex_data(s)
+}
+
+/// At least one block of whole hex bytes without a prefix, as `encrypt`
+/// prints ciphertext.
+fn is_bare_hex(s: &str) -> bool {
+    s.len() >= 16 && s.len().is_multiple_of(2) && s.chars().all(|ch| ch.is_ascii_hexdigit())
+}
+
+/// Byte pairs separated by spaces or colons, as in `01:23:45` or `AB CD`.
+fn is_separated_hex(s: &str) -> bool {
+    s.contains(is_hex_separator)
+        && s.split(is_hex_separator)
+            .filter(|pair| !pair.is_empty())
+            .all(|pair| pair.len() == 2 && pair.chars().all(|ch| ch.is_ascii_hexdigit()))
+}
+
+/// Decode bytes given on the command line, such as a MAC or a PIN block.
+/// Hex may carry a 0x prefix and spaces or colons between bytes.
+///
+/// # Errors
+///
+/// Returns an error if `s` is empty or not valid in `encoding`.
+pub fn decode(s: &str, encoding: Encoding) -> Result<Vec<u8>, ValueError> {
+    let s = s.trim();
+    let bytes = match encoding {
+        Encoding::Hex => encoding.decode(&hex_digits(s).replace(is_hex_separator, ""))?,
+        Encoding::Base64 => encoding.decode(s)?,
+    };
+    if bytes.is_empty() {
+        return Err(ValueError::EmptyString);
+    }
+    Ok(bytes)
+}
+
 #[derive(Debug, Clone, Copy)]
 pub struct Value(u64);
 
@@ -98,28 +1516,140 @@ impl From<u64> for Value {
     }
 }
 
-impl FromStr for Value {
-    type Err = ValueError;
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        if let Ok(num) = s.parse::<u64>() {
-            return Ok(Self(num));
+impl Value {
+    /// Parse `s` as `format`, reporting which interpretation was used.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `s` is not valid in the requested format, or if a
+    /// file cannot be read.
+    pub fn parse(
+        s: &str,
+        format: InputFormat,
+        order: ByteOrder,
+    ) -> Result<(Self, Interpretation), ValueError> {
+        let direct = |value| Ok((Self(value), Interpretation::direct(format)));
+        match format {
+            InputFormat::Auto => {
+                if let Ok(num) = s.parse::<u64>() {
+                    return Ok((Self(num), Interpretation::direct(InputFormat::Decimal)));
+                }
+                let path = PathBuf::from(s);
+                if path.is_file() {
+                    return Self::parse(s, InputFormat::File, order);
+                }
+                let (value, format) = parse_string_to_u64(s, order)?;
+                Ok((Self(value), Interpretation::direct(format)))
+            }
+            InputFormat::Hex => direct(parse_hex(s.trim())?),
+            InputFormat::Base64 => direct(parse_base64(s.trim(), order)?),
+            InputFormat::Ascii => direct(ascii_string_to_u64(s, order)?),
+            InputFormat::Text => Err(ValueError::InvalidFormat(
+                "Text of any length is only accepted as TEXT; use --kdf for passphrase keys".into(),
+            )),
+            InputFormat::Decimal => direct(parse_decimal(s.trim())?),
+            InputFormat::Binary => direct(parse_binary(s.trim())?),
+            InputFormat::File => {
+                let path = PathBuf::from(s);
+                let contents = read_to_string(&path).map_err(|_| file_error(path.clone()))?;
+                let (value, format) = parse_string_to_u64(&contents, order)?;
+                Ok((
+                    Self(value),
+                    Interpretation {
+                        format,
+                        file: Some(path),
+                    },
+                ))
+            }
+            InputFormat::Raw => {
+                let path = PathBuf::from(s);
+                let contents = read(&path).map_err(|_| file_error(path.clone()))?;
+                let bytes = <[u8; 8]>::try_from(contents.as_slice()).map_err(|_| {
+                    ValueError::InvalidFormat(format!(
+                        "Raw file must hold exactly 8 bytes, found {}",
+                        contents.len()
+                    ))
+                })?;
+                Ok((
+                    Self(order.pack(bytes)),
+                    Interpretation {
+                        format,
+                        file: Some(path),
+                    },
+                ))
+            }
         }
+    }
+}
 
-        let path = PathBuf::from(s);
-        if path.exists() && path.is_file() {
-            if let Ok(contents) = read_to_string(&path) {
-                let value = parse_string_to_u64(&contents)?;
-                return Ok(Self(value));
-            }
-            return Err(ValueError::FileReadingError(path));
+/// Decode a multi-length key given as hex, base64 or a file.
+fn parse_key_bytes(s: &str, format: InputFormat) -> Result<Vec<u8>, ValueError> {
+    let trimmed = s.trim();
+    match format {
+        InputFormat::Hex => decode(trimmed, Encoding::Hex),
+        InputFormat::Base64 => decode(trimmed, Encoding::Base64),
+        InputFormat::Raw => read(s).map_err(|_| file_error(PathBuf::from(s))),
+        InputFormat::File => {
+            let contents = read_to_string(s).map_err(|_| file_error(PathBuf::from(s)))?;
+            decode_key_text(contents.trim())
+        }
+        InputFormat::Auto if PathBuf::from(s).is_file() => parse_key_bytes(s, InputFormat::File),
+        InputFormat::Auto => decode_key_text(trimmed),
+        InputFormat::Ascii | InputFormat::Text | InputFormat::Decimal | InputFormat::Binary => {
+            Err(ValueError::InvalidFormat(format!(
+                "{format} keys are not supported here; use hex, base64, file or raw"
+            )))
+        }
+    }
+}
+
+/// Hex if the text only holds hex digits (and separators), base64 otherwise.
+fn decode_key_text(s: &str) -> Result<Vec<u8>, ValueError> {
+    let is_hex = hex_digits(s)
+        .chars()
+        .all(|ch| ch.is_ascii_hexdigit() || is_hex_separator(ch));
+    decode(
+        s,
+        if is_hex {
+            Encoding::Hex
+        } else {
+            Encoding::Base64
+        },
+    )
+}
+
+/// Parse KEY like [`Value::parse`], pointing passphrases at `--kdf`.
+///
+/// # Errors
+///
+/// Returns an error if `s` is not a valid key in the requested format.
+pub fn parse_key(
+    s: &str,
+    format: InputFormat,
+    order: ByteOrder,
+) -> Result<(Value, Interpretation), ValueError> {
+    Value::parse(s, format, order).map_err(|e| match e {
+        ValueError::InvalidFormat(_)
+        | ValueError::InvalidByteString
+        | ValueError::ConversionError(_)
+            if format == InputFormat::Auto && !looks_numeric(s.trim()) =>
+        {
+            ValueError::Passphrase(s.into())
         }
+        e => e,
+    })
+}
 
-        let value = parse_string_to_u64(&s)?;
-        Ok(Self(value))
+fn file_error(path: PathBuf) -> ValueError {
+    if path.exists() {
+        ValueError::FileReadingError(path)
+    } else {
+        ValueError::MissingFile(path)
     }
 }
 
-fn parse_string_to_u64(s: &str) -> Result<u64, ValueError> {
+/// Guess the format of `s` from its prefix and length.
+fn parse_string_to_u64(s: &str, order: ByteOrder) -> Result<(u64, InputFormat), ValueError> {
     let trimmed = s.trim();
 
     if trimmed.is_empty() {
@@ -128,36 +1658,68 @@ fn parse_string_to_u64(s: &str) -> Result<u64, ValueError> {
 
     // Hexadecimal with 0x/0X prefix
     if trimmed.starts_with("0x") || trimmed.starts_with("0X") {
-        let hex_str = &trimmed[2..].trim_start_matches('0');
-        if hex_str.is_empty() {
-            return Ok(0); // 0x000 ->
-        }
-        return u64::from_str_radix(hex_str, 16)
-            .map_err(|e| ValueError::InvalidFormat(format!("Hex parsing failed: {e}")));
+        return Ok((parse_hex(trimmed)?, InputFormat::Hex));
     }
 
     // Binary with 0b/0B prefix
     if trimmed.starts_with("0b") || trimmed.starts_with("0B") {
-        let bin_str = &trimmed[2..].trim_start_matches('0');
-        if bin_str.is_empty() {
-            return Ok(0); // 0b000 -> 0
-        }
-        if !bin_str.chars().all(|ch| ch == '0' || ch == '1') {
-            return Err(ValueError::InvalidFormat(
-                "Binary string contains invalid characters".into(),
-            ));
-        }
-        return u64::from_str_radix(bin_str, 2)
-            .map_err(|e| ValueError::InvalidFormat(format!("Binary parsing failed: {e}")));
+        return Ok((parse_binary(trimmed)?, InputFormat::Binary));
     }
 
     // 8-character ASCII string conversion to u64
     if trimmed.len() == 8 {
-        return ascii_string_to_u64(trimmed);
+        return Ok((ascii_string_to_u64(trimmed, order)?, InputFormat::Ascii));
+    }
+
+    Ok((parse_decimal(trimmed)?, InputFormat::Decimal))
+}
+
+fn parse_hex(s: &str) -> Result<u64, ValueError> {
+    let digits = hex_digits(s);
+    if digits.is_empty() || !digits.chars().all(|ch| ch.is_ascii_hexdigit()) {
+        return Err(ValueError::InvalidFormat(
+            "Hex string contains invalid characters".into(),
+        ));
+    }
+    let significant = digits.trim_start_matches('0');
+    if significant.is_empty() {
+        return Ok(0); // 0x000 -> 0
+    }
+    u64::from_str_radix(significant, 16)
+        .map_err(|e| ValueError::InvalidFormat(format!("Hex parsing failed: {e}")))
+}
+
+fn parse_binary(s: &str) -> Result<u64, ValueError> {
+    let digits = s
+        .strip_prefix("0b")
+        .or_else(|| s.strip_prefix("0B"))
+        .unwrap_or(s);
+    if digits.is_empty() || !digits.chars().all(|ch| ch == '0' || ch == '1') {
+        return Err(ValueError::InvalidFormat(
+            "Binary string contains invalid characters".into(),
+        ));
+    }
+    let significant = digits.trim_start_matches('0');
+    if significant.is_empty() {
+        return Ok(0); // 0b000 -> 0
     }
+    u64::from_str_radix(significant, 2)
+        .map_err(|e| ValueError::InvalidFormat(format!("Binary parsing failed: {e}")))
+}
+
+fn parse_base64(s: &str, order: ByteOrder) -> Result<u64, ValueError> {
+    let bytes = decode(s, Encoding::Base64)?;
+    let bytes = <[u8; 8]>::try_from(bytes.as_slice()).map_err(|_| {
+        ValueError::InvalidFormat(format!(
+            "Base64 value must decode to 8 bytes, found {}",
+            bytes.len()
+        ))
+    })?;
+    Ok(order.pack(bytes))
+}
 
-    // Regular decimal parsing
-    trimmed.parse::<u64>().map_err(|e| {
+fn parse_decimal(s: &str) -> Result<u64, ValueError> {
+    s.parse::<u64>().map_err(|e| {
         ValueError::InvalidFormat(match e.kind() {
             IntErrorKind::InvalidDigit => "contains invalid digits".into(),
             IntErrorKind::PosOverflow => "number too large for u64".into(),
@@ -169,7 +1731,7 @@ fn parse_string_to_u64(s: &str) -> Result<u64, ValueError> {
     })
 }
 
-fn ascii_string_to_u64(s: &str) -> Result<u64, ValueError> {
+fn ascii_string_to_u64(s: &str, order: ByteOrder) -> Result<u64, ValueError> {
     if s.len() != 8 {
         return Err(ValueError::InvalidByteString);
     }
@@ -186,7 +1748,7 @@ fn ascii_string_to_u64(s: &str) -> Result<u64, ValueError> {
         bytes[idx] = byte;
     }
 
-    Ok(u64::from_le_bytes(bytes))
+    Ok(order.pack(bytes))
 }
 
 impl Display for Value {
@@ -0,0 +1,39 @@
+use crate::{
+    CliError,
+    args::{CardArgs, CvvCommand, CvvKind, KeystoreArgs},
+    key,
+};
+use des_lib::{
+    cvv::{self, CVV2_SERVICE_CODE, ICVV_SERVICE_CODE},
+    keystore::KeyUsage,
+};
+
+/// Run a `des cvv` subcommand.
+pub fn run(command: &CvvCommand, keystore: &KeystoreArgs) -> Result<(), CliError> {
+    match command {
+        CvvCommand::Generate(card) => {
+            let key = key::load(&card.key, keystore, Some(KeyUsage::CardVerification))?;
+            println!(
+                "{}",
+                cvv::cvv(&key, &card.pan, &card.expiry, service_code(card))?
+            );
+        }
+        CvvCommand::Verify { card, cvv } => {
+            let key = key::load(&card.key, keystore, Some(KeyUsage::CardVerification))?;
+            cvv::verify(&key, &card.pan, &card.expiry, service_code(card), cvv)?;
+            println!("CVV verified");
+        }
+    }
+    Ok(())
+}
+
+fn service_code(card: &CardArgs) -> &str {
+    match card.kind {
+        None => card
+            .service_code
+            .as_deref()
+            .expect("--service-code is required without --type"),
+        Some(CvvKind::Cvv2) => CVV2_SERVICE_CODE,
+        Some(CvvKind::Icvv) => ICVV_SERVICE_CODE,
+    }
+}
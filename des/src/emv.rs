@@ -0,0 +1,65 @@
+use crate::{
+    CliError,
+    args::{ArqcArgs, EmvCommand, KeystoreArgs, ValueError, decode},
+    key,
+};
+use des_lib::{emv, encoding::Encoding, keystore::KeyUsage};
+
+/// Run a `des emv` subcommand.
+pub fn run(command: &EmvCommand, keystore: &KeystoreArgs) -> Result<(), CliError> {
+    match command {
+        EmvCommand::Arqc(args) => arqc(args, keystore),
+    }
+}
+
+fn arqc(args: &ArqcArgs, keystore: &KeystoreArgs) -> Result<(), CliError> {
+    let imk = key::load(&args.key, keystore, Some(KeyUsage::ApplicationCryptogram))?;
+    let atc = u16::from_be_bytes(fixed("ATC", &args.atc)?);
+    let data = decode(&args.data, Encoding::Hex)
+        .map_err(|e| ValueError::Argument("transaction data".into(), Box::new(e)))?;
+
+    let session_key = emv::session_key(&emv::master_key(&imk, &args.pan, args.psn)?, atc)?;
+    let padding = args.padding.into();
+    let request = if let Some(expected) = &args.verify {
+        let expected = u64::from_be_bytes(fixed("ARQC", expected)?);
+        emv::verify_arqc(&session_key, padding, &data, expected)?;
+        println!("ARQC verified");
+        expected
+    } else {
+        let computed = emv::arqc(&session_key, padding, &data)?;
+        println!("ARQC: {}", Encoding::Hex.encode(&computed.to_be_bytes()));
+        computed
+    };
+
+    if let Some(response_code) = &args.arc {
+        let response_code = u16::from_be_bytes(fixed("ARC", response_code)?);
+        let response = emv::arpc_method1(&session_key, request, response_code)?;
+        println!("ARPC: {}", Encoding::Hex.encode(&response.to_be_bytes()));
+    } else if let Some(csu) = &args.csu {
+        let csu = u32::from_be_bytes(fixed("CSU", csu)?);
+        let proprietary_data = args
+            .proprietary_data
+            .as_deref()
+            .map(|data| decode(data, Encoding::Hex))
+            .transpose()
+            .map_err(|e| ValueError::Argument("proprietary data".into(), Box::new(e)))?
+            .unwrap_or_default();
+        let response = emv::arpc_method2(&session_key, request, csu, &proprietary_data)?;
+        println!("ARPC: {}", Encoding::Hex.encode(&response.to_be_bytes()));
+    }
+    Ok(())
+}
+
+/// Parse a field of exactly `N` bytes given in hex.
+fn fixed<const N: usize>(name: &str, value: &str) -> Result<[u8; N], CliError> {
+    let invalid = |e| ValueError::Argument(name.into(), Box::new(e));
+    let bytes = decode(value, Encoding::Hex).map_err(invalid)?;
+    <[u8; N]>::try_from(bytes.as_slice())
+        .map_err(|_| {
+            invalid(ValueError::InvalidFormat(format!(
+                "expected {} hex digits",
+                2 * N
+            )))
+        })
+        .map_err(Into::into)
+}
@@ -0,0 +1,132 @@
+use crate::{
+    CliError,
+    args::{
+        InputFormat, KeyArgs, KeyCommand, KeystoreArgs, ValueError, load_key, load_share, parse_kcv,
+    },
+    keystore,
+};
+use des_lib::{
+    encoding::Encoding,
+    key::{KcvMethod, Key},
+    keystore::KeyUsage,
+    shamir,
+};
+
+/// Run a `des key` subcommand.
+pub fn run(command: &KeyCommand, keystore: &KeystoreArgs) -> Result<(), CliError> {
+    match command {
+        KeyCommand::Kcv {
+            key,
+            method,
+            format,
+        } => {
+            let format = Encoding::from(*format);
+            let key = load(key, keystore, None)?;
+            println!(
+                "{}",
+                format.encode(&key.check_value(KcvMethod::from(*method)))
+            );
+        }
+        KeyCommand::Split {
+            key,
+            components,
+            format,
+        } => {
+            let format = Encoding::from(*format);
+            let key = load(key, keystore, None)?;
+            let components = key.split(usize::from(*components))?;
+            for (index, component) in components.iter().enumerate() {
+                println!(
+                    "Component {}: {}  KCV: {}",
+                    index + 1,
+                    format.encode(&component.to_bytes()),
+                    format.encode(&component.kcv())
+                );
+            }
+            println!("KCV: {}", format.encode(&key.kcv()));
+        }
+        KeyCommand::Combine {
+            components,
+            kcv,
+            format,
+        } => {
+            let format = Encoding::from(*format);
+            let components = components
+                .iter()
+                .enumerate()
+                .map(|(index, component)| {
+                    load_key(component, InputFormat::Auto).map_err(|e| {
+                        ValueError::Argument(format!("component {}", index + 1), Box::new(e))
+                    })
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            for (index, component) in components.iter().enumerate() {
+                println!(
+                    "Component {} KCV: {}",
+                    index + 1,
+                    format.encode(&component.kcv())
+                );
+            }
+
+            let key = Key::combine(&components)?;
+            if let Some(kcv) = kcv {
+                let (method, expected) = parse_kcv(kcv)?;
+                key.verify_check_value(method, &expected)?;
+            }
+            println!("Key: {}", format.encode(&key.to_bytes()));
+            println!("KCV: {}", format.encode(&key.kcv()));
+        }
+        KeyCommand::Share {
+            key,
+            threshold,
+            shares,
+            format,
+        } => {
+            let format = Encoding::from(*format);
+            let key = load(key, keystore, None)?;
+            let shares = shamir::split(&key, *threshold, *shares).map_err(ValueError::from)?;
+            for share in &shares {
+                println!(
+                    "Share {}: {}",
+                    share.index(),
+                    format.encode(&share.to_bytes())
+                );
+            }
+            println!("KCV: {}", format.encode(&key.kcv()));
+        }
+        KeyCommand::Recover { shares, format } => {
+            let format = Encoding::from(*format);
+            let shares = shares
+                .iter()
+                .enumerate()
+                .map(|(index, share)| {
+                    load_share(share).map_err(|e| {
+                        ValueError::Argument(format!("share {}", index + 1), Box::new(e))
+                    })
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            let key = shamir::recover(&shares).map_err(ValueError::from)?;
+            println!("Key: {}", format.encode(&key.to_bytes()));
+            println!("KCV: {}", format.encode(&key.kcv()));
+        }
+    }
+    Ok(())
+}
+
+/// Load KEY, or the key named by `--key-id` from the keystore, checking
+/// that it may be used for `usage`.
+pub fn load(
+    args: &KeyArgs,
+    keystore: &KeystoreArgs,
+    usage: Option<KeyUsage>,
+) -> Result<Key, CliError> {
+    if let Some(name) = &args.key_id {
+        return keystore::load_key(keystore, name, usage);
+    }
+    let key = args
+        .key
+        .as_deref()
+        .expect("KEY is required without --key-id");
+    Ok(load_key(key, args.key_format)
+        .map_err(|e| ValueError::Argument("KEY".into(), Box::new(e)))?)
+}
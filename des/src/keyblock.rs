@@ -0,0 +1,87 @@
+use crate::{
+    CliError,
+    args::{KeyArgs, KeyblockCommand, KeystoreArgs, WrapArgs},
+    key,
+};
+use des_lib::{
+    encoding::Encoding,
+    key::{Key, KeyLength},
+    keyblock::{self, Algorithm, Header},
+    keystore::KeyUsage,
+};
+
+/// Run a `des keyblock` subcommand.
+pub fn run(command: &KeyblockCommand, keystore: &KeystoreArgs) -> Result<(), CliError> {
+    match command {
+        KeyblockCommand::Wrap(args) => println!("{}", wrap(args, keystore)?),
+        KeyblockCommand::Unwrap { key, block } => {
+            let kbpk = key::load(key, keystore, Some(KeyUsage::KeyWrapping))?;
+            let (header, key) = keyblock::unwrap(&kbpk, block)?;
+            print_header(&header);
+            println!("Key: {}", Encoding::Hex.encode(&key.to_bytes()));
+            println!("KCV: {}", Encoding::Hex.encode(&key.kcv()));
+        }
+        KeyblockCommand::Inspect { block } => print_header(&keyblock::inspect(block)?),
+    }
+    Ok(())
+}
+
+fn wrap(args: &WrapArgs, keystore: &KeystoreArgs) -> Result<String, CliError> {
+    let kbpk = key::load(&args.key, keystore, Some(KeyUsage::KeyWrapping))?;
+    let key = key::load(
+        &KeyArgs {
+            key: args.wrapped_key.clone(),
+            key_id: args.wrapped_key_id.clone(),
+            key_format: args.wrapped_key_format,
+        },
+        keystore,
+        None,
+    )?;
+    let header = Header {
+        version: args.block_version.into(),
+        key_version: args.key_version.clone(),
+        exportability: args.exportability.into(),
+        optional_blocks: args.optional_blocks.clone(),
+        ..Header::new(args.usage, algorithm(&key), args.mode_of_use.into())
+    };
+    Ok(keyblock::wrap(&kbpk, &header, &key)?)
+}
+
+/// The header algorithm matching the length of the wrapped key.
+const fn algorithm(key: &Key) -> Algorithm {
+    match key.length() {
+        KeyLength::Single => Algorithm::Des,
+        KeyLength::Double | KeyLength::Triple => Algorithm::Tdea,
+    }
+}
+
+fn print_header(header: &Header) {
+    println!(
+        "Version: {} ({})",
+        header.version,
+        header.version.description()
+    );
+    match header.usage.description() {
+        Some(description) => println!("Key usage: {} ({description})", header.usage),
+        None => println!("Key usage: {}", header.usage),
+    }
+    println!(
+        "Algorithm: {} ({})",
+        header.algorithm,
+        header.algorithm.description()
+    );
+    println!(
+        "Mode of use: {} ({})",
+        header.mode_of_use,
+        header.mode_of_use.description()
+    );
+    println!("Key version: {}", header.key_version);
+    println!(
+        "Exportability: {} ({})",
+        header.exportability,
+        header.exportability.description()
+    );
+    for block in &header.optional_blocks {
+        println!("Optional block {}: {}", block.id, block.data);
+    }
+}
@@ -0,0 +1,43 @@
+use crate::{args::KeygenArgs, with_path};
+use des_lib::{encoding::Encoding, key::Key};
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, Write},
+    path::Path,
+};
+
+/// Generate a key (and optionally an IV) and print or store it.
+pub fn run(args: &KeygenArgs) -> io::Result<()> {
+    let key = Key::generate(args.length.into());
+    let format = Encoding::from(args.format);
+    let encoded = format.encode(&key.to_bytes());
+
+    match &args.output {
+        Some(path) => {
+            let mut file = create_private(path)?;
+            writeln!(file, "{encoded}")?;
+            eprintln!("Key written to {}", path.display());
+        }
+        None => println!("Key: {encoded}"),
+    }
+    println!("KCV: {}", format.encode(&key.kcv()));
+
+    if args.iv {
+        let iv = rand::random::<u64>();
+        println!("IV:  {}", format.encode(&iv.to_be_bytes()));
+    }
+    Ok(())
+}
+
+/// Create a new file that only its owner can read and write. Existing files
+/// are never overwritten.
+pub fn create_private(path: &Path) -> io::Result<File> {
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options.open(path).map_err(|e| with_path(&e, path))
+}
@@ -0,0 +1,142 @@
+use crate::{
+    CliError,
+    args::{KeystoreArgs, KeystoreCommand, ValueError, load_key as parse_key},
+    keygen::create_private,
+    with_path,
+};
+use des_lib::{
+    encoding::Encoding,
+    key::Key,
+    keystore::{KeyUsage, Keystore, MasterKey},
+};
+use std::{
+    fs::{self, read_to_string},
+    io::{self, Write},
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Run a `des keystore` subcommand.
+pub fn run(command: &KeystoreCommand, args: &KeystoreArgs) -> Result<(), CliError> {
+    match command {
+        KeystoreCommand::Init { iterations } => {
+            let (keystore, _) = Keystore::create(passphrase(args)?, *iterations);
+            let mut file = create_private(&args.path)?;
+            write!(file, "{keystore}")?;
+            eprintln!("Keystore created at {}", args.path.display());
+        }
+        KeystoreCommand::Add {
+            name,
+            key,
+            key_format,
+            generate,
+            usage,
+        } => {
+            let mut keystore = open(&args.path)?;
+            let master = unlock(&keystore, args)?;
+            let key = match (key, generate) {
+                (_, Some(length)) => Key::generate((*length).into()),
+                (Some(key), None) => parse_key(key, *key_format)
+                    .map_err(|e| ValueError::Argument("KEY".into(), Box::new(e)))?,
+                (None, None) => unreachable!("clap requires KEY or --generate"),
+            };
+            keystore.add(&master, name, &key, (*usage).into())?;
+            save(&args.path, &keystore)?;
+            println!("KCV: {}", Encoding::Hex.encode(&key.kcv()));
+        }
+        KeystoreCommand::List => {
+            let keystore = open(&args.path)?;
+            for entry in keystore.entries() {
+                println!(
+                    "{:<16} {:<6} {:<12} {}  {}",
+                    entry.name(),
+                    entry.length(),
+                    entry.usage(),
+                    Encoding::Hex.encode(&entry.kcv()),
+                    format_time(entry.created())
+                );
+            }
+        }
+        KeystoreCommand::Remove { name } => {
+            let mut keystore = open(&args.path)?;
+            let master = unlock(&keystore, args)?;
+            keystore.remove(&master, name)?;
+            save(&args.path, &keystore)?;
+        }
+    }
+    Ok(())
+}
+
+/// Load the key called `name`, checking that it may be used for `usage`.
+pub fn load_key(args: &KeystoreArgs, name: &str, usage: Option<KeyUsage>) -> Result<Key, CliError> {
+    let keystore = open(&args.path)?;
+    if let Some(usage) = usage {
+        keystore.entry(name)?.check_usage(usage)?;
+    }
+    let master = unlock(&keystore, args)?;
+    Ok(keystore.key(&master, name)?)
+}
+
+fn open(path: &Path) -> Result<Keystore, CliError> {
+    let text = read_to_string(path).map_err(|e| with_path(&e, path))?;
+    Ok(text.parse()?)
+}
+
+fn unlock(keystore: &Keystore, args: &KeystoreArgs) -> Result<MasterKey, CliError> {
+    Ok(keystore.unlock(passphrase(args)?)?)
+}
+
+fn passphrase(args: &KeystoreArgs) -> Result<&[u8], CliError> {
+    args.passphrase
+        .as_deref()
+        .map(str::as_bytes)
+        .ok_or(CliError::MissingPassphrase)
+}
+
+/// Replace the keystore file, so an interrupted write never leaves it
+/// truncated.
+fn save(path: &Path, keystore: &Keystore) -> io::Result<()> {
+    let mut temporary = path.as_os_str().to_owned();
+    temporary.push(".tmp");
+    let temporary = Path::new(&temporary);
+    match fs::remove_file(temporary) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(with_path(&e, temporary)),
+        _ => {}
+    }
+
+    let mut file = create_private(temporary)?;
+    write!(file, "{keystore}")?;
+    file.sync_all()?;
+    fs::rename(temporary, path).map_err(|e| with_path(&e, path))
+}
+
+/// Format a time as `YYYY-MM-DD HH:MM:SS` in UTC.
+fn format_time(time: SystemTime) -> String {
+    let seconds = time
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+    let (days, seconds) = (seconds / 86_400, seconds % 86_400);
+
+    // Civil date from days since the epoch (Howard Hinnant's algorithm).
+    let days = days + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = era * 400 + year_of_era + u64::from(month <= 2);
+
+    format!(
+        "{year:04}-{month:02}-{day:02} {:02}:{:02}:{:02}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
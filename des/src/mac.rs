@@ -0,0 +1,48 @@
+use crate::{
+    CliError,
+    args::{ByteOrder, KeystoreArgs, MacArgs, Text, ValueError, decode},
+    is_stdio, key, with_path,
+};
+use des_lib::{encoding::Encoding, keystore::KeyUsage, mac};
+use std::{
+    fs,
+    io::{self, Read},
+};
+
+/// Run `des mac`.
+pub fn run(args: &MacArgs, keystore: &KeystoreArgs) -> Result<(), CliError> {
+    let key = key::load(&args.key, keystore, Some(KeyUsage::Mac))?;
+    let message = message(args)?;
+    let (algorithm, padding) = (args.algorithm.into(), args.padding.into());
+
+    if let Some(expected) = &args.verify {
+        let expected = decode(expected, Encoding::Hex)
+            .map_err(|e| ValueError::Argument("MAC".into(), Box::new(e)))?;
+        mac::verify(&key, algorithm, padding, &message, &expected)?;
+        println!("MAC verified");
+    } else {
+        let mac = mac::mac(&key, algorithm, padding, &message)?.to_be_bytes();
+        println!("{}", Encoding::Hex.encode(&mac[..usize::from(args.length)]));
+    }
+    Ok(())
+}
+
+/// The message given as TEXT or read from `--input`.
+fn message(args: &MacArgs) -> Result<Vec<u8>, CliError> {
+    if let Some(input) = &args.input {
+        if is_stdio(input) {
+            let mut message = Vec::new();
+            io::stdin().lock().read_to_end(&mut message)?;
+            return Ok(message);
+        }
+        return Ok(fs::read(input).map_err(|e| with_path(&e, input))?);
+    }
+
+    let text = args
+        .text
+        .as_deref()
+        .expect("TEXT is required without --input");
+    let (text, _) = Text::parse(text, args.input_format, ByteOrder::Big)
+        .map_err(|e| ValueError::Argument("TEXT".into(), Box::new(e)))?;
+    Ok(text.into_data(ByteOrder::Big).0)
+}
@@ -1,21 +1,577 @@
 mod args;
+mod cvv;
+mod emv;
+mod key;
+mod keyblock;
+mod keygen;
+mod keystore;
+mod mac;
+mod passphrase;
+mod pin;
+mod progress;
 
-use crate::args::{Args, Operation};
+use crate::{
+    args::{
+        Args, ByteOrder, CipherArgs, InputFormat, KeyDerivation, KeystoreArgs, Operation,
+        OutputFormat, Text, Utf8Mode, Value, ValueError, parse_key,
+    },
+    progress::Progress,
+};
 use clap::Parser;
-use des_lib::Des;
+use des_lib::{
+    BlockCipher, Des,
+    cvv::CvvError,
+    emv::EmvError,
+    encoding::Encoding,
+    kdf::{KdfError, KdfParams},
+    key::{Key, KeyError, KeyLength},
+    keyblock::KeyBlockError,
+    keystore::{KeyUsage, KeystoreError},
+    mac::MacError,
+    mode::Mode,
+    pin::PinError,
+    seal::{self, OpenReader, SealError, SealWriter},
+    stream::{DecryptReader, EncryptWriter},
+};
+use std::{
+    fs::{self, File},
+    io::{self, BufReader, BufWriter, IsTerminal, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    process::ExitCode,
+    str::Utf8Error,
+};
+use thiserror::Error;
 
-fn main() {
-    let args = Args::parse();
-    let des = Des::new(args.key.as_64());
+/// Bytes processed per step when streaming; large enough for the bulk modes
+/// to spread the work across threads.
+const STREAM_CHUNK: usize = 1024 * 1024;
 
-    match args.operation {
-        Operation::Encrypt => {
-            let ciphertext = des.encrypt(args.text.as_64());
+/// Input size from which progress is shown automatically on a terminal.
+const AUTO_PROGRESS_BYTES: u64 = 64 * 1024 * 1024;
+
+#[derive(Debug, Error)]
+enum CliError {
+    #[error(transparent)]
+    Value(#[from] ValueError),
+
+    #[error(transparent)]
+    Key(#[from] KeyError),
+
+    #[error(transparent)]
+    Keystore(#[from] KeystoreError),
+
+    #[error(transparent)]
+    Kdf(#[from] KdfError),
+
+    #[error(transparent)]
+    Mac(#[from] MacError),
+
+    #[error(transparent)]
+    Pin(#[from] PinError),
+
+    #[error(transparent)]
+    Cvv(#[from] CvvError),
+
+    #[error(transparent)]
+    KeyBlock(#[from] KeyBlockError),
+
+    #[error(transparent)]
+    Emv(#[from] EmvError),
+
+    #[error(transparent)]
+    Seal(#[from] SealError),
+
+    #[error(
+        "The keystore passphrase is required (--keystore-passphrase or DES_KEYSTORE_PASSPHRASE)"
+    )]
+    MissingPassphrase,
+
+    #[error(transparent)]
+    Io(#[from] io::Error),
+
+    #[error("Decrypted data is not valid UTF-8: {0}")]
+    InvalidUtf8(#[from] Utf8Error),
+
+    #[error("--output-format {0:?} only applies to single 64-bit blocks")]
+    UnsupportedFormat(OutputFormat),
+
+    #[error("--padding only applies to unauthenticated data; add --mode")]
+    PaddingWithoutMode,
+
+    #[error("--kdf with KEY requires --salt; use --passphrase to store a random salt")]
+    MissingSalt,
+
+    #[error("ISO format 4 PIN fields require --pan")]
+    PanRequired,
+
+    #[error("ISO format 4 PIN blocks are encrypted with AES; use `des pin build` for its fields")]
+    AesPinFormat,
+}
+
+fn main() -> ExitCode {
+    match run(&Args::parse()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(args: &Args) -> Result<(), CliError> {
+    if let Some(threads) = args.threads {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(threads.get())
+            .build_global()
+            .expect("Global thread pool is only configured once");
+    }
+
+    match &args.operation {
+        Operation::Encrypt(cipher) => crypt(cipher, &args.keystore, None, args.verbose),
+        Operation::Decrypt {
+            cipher,
+            output_format,
+            utf8,
+        } => {
+            let output = DecryptOutput {
+                format: output_format.clone(),
+                utf8: *utf8,
+            };
+            crypt(cipher, &args.keystore, Some(output), args.verbose)
+        }
+        Operation::Keygen(keygen) => Ok(keygen::run(keygen)?),
+        Operation::Keystore { command } => keystore::run(command, &args.keystore),
+        Operation::Key { command } => key::run(command, &args.keystore),
+        Operation::Mac(mac) => mac::run(mac, &args.keystore),
+        Operation::Pin { command } => pin::run(command, &args.keystore),
+        Operation::Cvv { command } => cvv::run(command, &args.keystore),
+        Operation::Keyblock { command } => keyblock::run(command, &args.keystore),
+        Operation::Emv { command } => emv::run(command, &args.keystore),
+    }
+}
+
+/// How decrypted TEXT is printed.
+struct DecryptOutput {
+    format: Option<OutputFormat>,
+    utf8: Utf8Mode,
+}
+
+/// TEXT or the input stream, opened before the key is known so that a key
+/// derivation header can be read from it.
+enum Input {
+    Text(Text),
+    Stream { source: Source, total: Option<u64> },
+}
+
+/// The input stream: a file, which can be read again, or stdin.
+enum Source {
+    File(File),
+    Stdin(io::Stdin),
+}
+
+impl Read for Source {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Self::File(file) => file.read(buf),
+            Self::Stdin(stdin) => stdin.read(buf),
+        }
+    }
+}
+
+impl Input {
+    fn open(args: &CipherArgs, decrypt: bool, verbose: bool) -> Result<Self, CliError> {
+        if let Some(input) = &args.input {
+            let (source, total) = if is_stdio(input) {
+                (Source::Stdin(io::stdin()), None)
+            } else {
+                let file = File::open(input).map_err(|e| with_path(&e, input))?;
+                let total = file.metadata()?.len();
+                (Source::File(file), Some(total))
+            };
+            return Ok(Self::Stream { source, total });
+        }
+
+        let text = args
+            .text
+            .as_deref()
+            .expect("TEXT is required without --input");
+        let parse = if decrypt {
+            Text::parse_ciphertext
+        } else {
+            Text::parse
+        };
+        let (text, interpretation) = parse(text, args.input_format, args.byte_order)
+            .map_err(|e| ValueError::Argument("TEXT".into(), Box::new(e)))?;
+        if verbose {
+            match &text {
+                Text::Block(value) => eprintln!("TEXT: {interpretation} ({value:X})"),
+                Text::Data { bytes, .. } => {
+                    eprintln!("TEXT: {interpretation} ({} bytes)", bytes.len());
+                }
+            }
+        }
+        Ok(Self::Text(text))
+    }
+
+    /// Read the key derivation header in front of the ciphertext.
+    fn read_params(&mut self, order: ByteOrder) -> Result<KdfParams, KdfError> {
+        match self {
+            Self::Stream { source, total } => {
+                let params = KdfParams::read(source)?;
+                *total = total.map(|total| total.saturating_sub(params.to_bytes().len() as u64));
+                Ok(params)
+            }
+            Self::Text(text) => {
+                let (bytes, encoding) = text.clone().into_data(order);
+                let mut rest = bytes.as_slice();
+                let params = KdfParams::read(&mut rest)?;
+                *text = Text::Data {
+                    bytes: rest.to_vec(),
+                    encoding,
+                };
+                Ok(params)
+            }
+        }
+    }
+}
+
+/// Encrypt, or decrypt when `output` is given, TEXT or the input stream.
+fn crypt(
+    args: &CipherArgs,
+    keystore: &KeystoreArgs,
+    output: Option<DecryptOutput>,
+    verbose: bool,
+) -> Result<(), CliError> {
+    let mut input = Input::open(args, output.is_some(), verbose)?;
+    let (key, header) = if let Some(passphrase) = &args.passphrase {
+        let encrypt = output.is_none();
+        let passphrase = passphrase::read(passphrase.as_deref(), encrypt)?;
+        let params = if encrypt {
+            KdfParams::generate(
+                args.kdf.unwrap_or_default().into(),
+                args.key_length.unwrap_or_default().into(),
+                args.iterations.get(),
+            )
+        } else {
+            input.read_params(args.byte_order)?
+        };
+        if verbose {
+            eprintln!(
+                "KEY: {} from passphrase via {}, {} iterations",
+                params.length(),
+                KeyDerivation::from(params.prf()),
+                params.iterations()
+            );
+        }
+        let key = params.derive(passphrase.as_bytes());
+        (key, encrypt.then(|| params.to_bytes()))
+    } else {
+        (load_key(args, keystore, verbose)?, None)
+    };
+    eprintln!("KCV: {}", Encoding::Hex.encode(&key.kcv()));
+
+    let input = match input {
+        Input::Stream { source, total } if args.mode.is_none() => {
+            return seal_stream(
+                args,
+                &key,
+                source,
+                total,
+                output.is_none(),
+                header.as_deref(),
+            );
+        }
+        input => input,
+    };
+
+    // Single keys use DES directly rather than three identical TDEA stages.
+    match key.length() {
+        KeyLength::Single => crypt_with(args, Des::new(key.parts()[0]), input, output, header),
+        KeyLength::Double | KeyLength::Triple => {
+            crypt_with(args, key.cipher(), input, output, header)
+        }
+    }
+}
+
+/// Load the key from the keystore, KEY used as a passphrase, or KEY itself.
+fn load_key(args: &CipherArgs, keystore: &KeystoreArgs, verbose: bool) -> Result<Key, CliError> {
+    if let Some(name) = &args.key_id {
+        let key = keystore::load_key(keystore, name, Some(KeyUsage::Encryption))?;
+        if verbose {
+            eprintln!("KEY: {} from keystore entry '{name}'", key.length());
+        }
+        return Ok(key);
+    }
+
+    let key = args
+        .key
+        .as_deref()
+        .expect("KEY is required without --key-id");
+    if let Some(kdf) = args.kdf {
+        let salt = args.salt.as_deref().ok_or(CliError::MissingSalt)?;
+        if verbose {
+            eprintln!("KEY: passphrase via {kdf}, {} iterations", args.iterations);
+        }
+        return Ok(kdf.derive(key, salt, args.iterations));
+    }
+
+    match parse_key(key, args.key_format, args.byte_order) {
+        Ok((value, interpretation)) => {
+            if verbose {
+                eprintln!("KEY: {interpretation}");
+            }
+            Ok(Key::single(value.as_64()))
+        }
+        // Keys too long for a single value are read as bytes.
+        Err(e) => {
+            let key = args::load_key(key, args.key_format)
+                .map_err(|_| ValueError::Argument("KEY".into(), Box::new(e)))?;
+            if verbose {
+                eprintln!("KEY: {}", key.length());
+            }
+            Ok(key)
+        }
+    }
+}
+
+/// Encrypt or decrypt `input` with an already keyed `cipher`, writing
+/// `header` in front of the ciphertext.
+fn crypt_with<C: BlockCipher>(
+    args: &CipherArgs,
+    cipher: C,
+    input: Input,
+    output: Option<DecryptOutput>,
+    header: Option<Vec<u8>>,
+) -> Result<(), CliError> {
+    let iv = args
+        .iv
+        .as_deref()
+        .map(|iv| Value::parse(iv, InputFormat::Auto, args.byte_order))
+        .transpose()
+        .map_err(|e| ValueError::Argument("IV".into(), Box::new(e)))?;
+    let mode = args.mode.unwrap_or_default().with_iv(iv.map(|(iv, _)| iv));
+    let padding = args.padding.unwrap_or_default().into();
+
+    let text = match input {
+        Input::Stream { source, total } => {
+            return Ok(stream(
+                args,
+                Box::new(source),
+                total,
+                cipher,
+                mode,
+                output.is_none(),
+                header.as_deref(),
+            )?);
+        }
+        // A header turns the single block into data it can be prepended to.
+        Input::Text(text) if header.is_some() => {
+            let (bytes, encoding) = text.into_data(args.byte_order);
+            Text::Data { bytes, encoding }
+        }
+        Input::Text(text) => text,
+    };
+
+    match (output, text) {
+        (None, Text::Block(value)) => {
+            let ciphertext = cipher.encrypt_block(value.as_64());
             println!("{ciphertext:016X}");
         }
-        Operation::Decrypt { output_format } => {
-            let plaintext = des.decrypt(args.text.as_64());
-            println!("{plaintext:016X}");
+        (Some(output), Text::Block(value)) => {
+            let plaintext = cipher.decrypt_block(value.as_64());
+            let format = output.format.unwrap_or_default();
+            println!(
+                "{}",
+                format.format(plaintext, args.byte_order, output.utf8)?
+            );
+        }
+        (None, Text::Data { bytes, encoding }) => {
+            let mut writer = EncryptWriter::new(header.unwrap_or_default(), cipher, mode, padding);
+            writer.write_all(&bytes)?;
+            println!("{}", encoding.encode(&writer.finish()?));
+        }
+        (Some(output), Text::Data { bytes, encoding }) => {
+            let format = output.format.unwrap_or_else(|| encoding.into());
+            if !format.supports_data() {
+                return Err(CliError::UnsupportedFormat(format));
+            }
+
+            let mut plaintext = Vec::new();
+            DecryptReader::new(bytes.as_slice(), cipher, mode, padding)
+                .read_to_end(&mut plaintext)?;
+            println!("{}", format.format_bytes(&plaintext, output.utf8)?);
         }
     }
+    Ok(())
+}
+
+/// Stream binary data from `reader` to the output through the selected mode
+/// and padding.
+fn stream<C: BlockCipher>(
+    args: &CipherArgs,
+    reader: Box<dyn Read>,
+    total: Option<u64>,
+    cipher: C,
+    mode: Mode,
+    encrypt: bool,
+    header: Option<&[u8]>,
+) -> io::Result<()> {
+    let writer = open_output(args)?;
+    let mut reader = with_progress(args, reader, total);
+    let padding = args.padding.unwrap_or_default().into();
+
+    if encrypt {
+        let mut writer = BufWriter::new(writer);
+        writer.write_all(header.unwrap_or_default())?;
+        let mut writer = EncryptWriter::new(writer, cipher, mode, padding);
+        copy_chunked(&mut reader, &mut writer)?;
+        writer.finish()?;
+    } else {
+        let mut source =
+            DecryptReader::with_capacity(STREAM_CHUNK, &mut reader, cipher, mode, padding);
+        let mut writer = BufWriter::new(writer);
+        copy_chunked(&mut source, &mut writer)?;
+        writer.flush()?;
+    }
+
+    reader.finish();
+    Ok(())
+}
+
+/// Seal, or open when decrypting, the input stream. No plaintext reaches
+/// stdout before the whole input has been authenticated, and an output file
+/// only appears once the tag checks out.
+fn seal_stream(
+    args: &CipherArgs,
+    key: &Key,
+    source: Source,
+    total: Option<u64>,
+    encrypt: bool,
+    header: Option<&[u8]>,
+) -> Result<(), CliError> {
+    if args.padding.is_some() {
+        return Err(CliError::PaddingWithoutMode);
+    }
+
+    if encrypt {
+        let mut reader = with_progress(args, Box::new(source), total);
+        let mut writer = BufWriter::new(open_output(args)?);
+        writer.write_all(header.unwrap_or_default())?;
+        let mut writer = SealWriter::new(key, writer)?;
+        copy_chunked(&mut reader, &mut writer)?;
+        writer.finish()?;
+        reader.finish();
+        return Ok(());
+    }
+
+    let output = args.output.as_deref().filter(|path| !is_stdio(path));
+    let (reader, total): (Box<dyn Read>, _) = match source {
+        // The output file is only renamed into place after the tag at the end.
+        Source::Stdin(stdin) if output.is_some() => (Box::new(stdin), total),
+        source => {
+            let (file, total) = verify_sealed(key, source, total)?;
+            (Box::new(file), total)
+        }
+    };
+    let mut reader = with_progress(args, reader, total);
+    open_sealed(key, &mut reader, output)?;
+    reader.finish();
+    Ok(())
+}
+
+/// Authenticate a sealed stream before any of it is decrypted, returning the
+/// same handle positioned back at the start of the sealed data. Stdin is
+/// spooled to an anonymous temporary file first, so that it can be read twice
+/// without holding it in memory.
+fn verify_sealed(
+    key: &Key,
+    source: Source,
+    total: Option<u64>,
+) -> Result<(File, Option<u64>), CliError> {
+    let (mut file, total) = match source {
+        Source::File(file) => (file, total),
+        Source::Stdin(mut stdin) => {
+            let mut spool = BufWriter::new(tempfile::tempfile()?);
+            copy_chunked(&mut stdin, &mut spool)?;
+            let mut file = spool.into_inner().map_err(io::IntoInnerError::into_error)?;
+            file.rewind()?;
+            let total = file.metadata()?.len();
+            (file, Some(total))
+        }
+    };
+    let start = file.stream_position()?;
+    seal::verify(key, BufReader::new(&mut file))?;
+    file.seek(SeekFrom::Start(start))?;
+    Ok((file, total))
+}
+
+/// Open a sealed stream into `output`, or stdout. An output file is written
+/// under a temporary name and only renamed into place once the tag checks
+/// out.
+fn open_sealed(key: &Key, reader: &mut impl Read, output: Option<&Path>) -> Result<(), CliError> {
+    let mut source = OpenReader::with_capacity(STREAM_CHUNK, key, reader)?;
+    let Some(path) = output else {
+        let mut writer = BufWriter::new(io::stdout().lock());
+        copy_chunked(&mut source, &mut writer)?;
+        return Ok(writer.flush()?);
+    };
+
+    let mut partial = path.as_os_str().to_owned();
+    partial.push(".partial");
+    let partial = PathBuf::from(partial);
+    let opened = File::create(&partial)
+        .map_err(|e| with_path(&e, &partial))
+        .and_then(|file| {
+            let mut writer = BufWriter::new(file);
+            copy_chunked(&mut source, &mut writer)?;
+            writer.flush()?;
+            fs::rename(&partial, path).map_err(|e| with_path(&e, path))
+        });
+    if opened.is_err() {
+        let _ = fs::remove_file(&partial);
+    }
+    Ok(opened?)
+}
+
+/// The file given by `--output`, or stdout.
+fn open_output(args: &CipherArgs) -> io::Result<Box<dyn Write>> {
+    Ok(match args.output.as_deref() {
+        Some(path) if !is_stdio(path) => {
+            Box::new(File::create(path).map_err(|e| with_path(&e, path))?)
+        }
+        _ => Box::new(io::stdout().lock()),
+    })
+}
+
+/// Wrap `reader` to report progress when requested, or automatically for
+/// large files on a terminal.
+fn with_progress(
+    args: &CipherArgs,
+    reader: Box<dyn Read>,
+    total: Option<u64>,
+) -> Progress<Box<dyn Read>> {
+    let show_progress = args.progress
+        || (io::stderr().is_terminal() && total.is_some_and(|size| size >= AUTO_PROGRESS_BYTES));
+    Progress::new(reader, total, show_progress)
+}
+
+/// Like [`io::copy`], but with a buffer of [`STREAM_CHUNK`] bytes.
+fn copy_chunked(reader: &mut impl Read, writer: &mut impl Write) -> io::Result<()> {
+    let mut buffer = vec![0; STREAM_CHUNK];
+    loop {
+        match reader.read(&mut buffer) {
+            Ok(0) => return Ok(()),
+            Ok(count) => writer.write_all(&buffer[..count])?,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+fn is_stdio(path: &Path) -> bool {
+    path.as_os_str() == "-"
+}
+
+fn with_path(error: &io::Error, path: &Path) -> io::Error {
+    io::Error::new(error.kind(), format!("{}: {error}", path.display()))
 }
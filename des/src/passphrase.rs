@@ -0,0 +1,34 @@
+use std::io;
+
+/// Use the passphrase given with `--passphrase`, or read it from the terminal
+/// without echo. New passphrases are asked for twice to catch typos.
+pub fn read(value: Option<&str>, confirm: bool) -> io::Result<String> {
+    let passphrase = if let Some(passphrase) = value {
+        passphrase.to_owned()
+    } else {
+        let passphrase = prompt("Passphrase: ")?;
+        if confirm && prompt("Confirm passphrase: ")? != passphrase {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Passphrases do not match",
+            ));
+        }
+        passphrase
+    };
+    if passphrase.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "Passphrase is empty",
+        ));
+    }
+    Ok(passphrase)
+}
+
+fn prompt(message: &str) -> io::Result<String> {
+    rpassword::prompt_password(message).map_err(|e| {
+        io::Error::new(
+            e.kind(),
+            format!("Cannot prompt for the passphrase ({e}); pass it as --passphrase=VALUE"),
+        )
+    })
+}
@@ -0,0 +1,115 @@
+use crate::{
+    CliError,
+    args::{KeyArgs, KeystoreArgs, PinCommand, PinFormatArg, ValueError, decode},
+    key,
+};
+use des_lib::{
+    encoding::Encoding,
+    key::Key,
+    keystore::KeyUsage,
+    pin::{self, PinFormat},
+};
+
+/// Run a `des pin` subcommand.
+pub fn run(command: &PinCommand, keystore: &KeystoreArgs) -> Result<(), CliError> {
+    match command {
+        PinCommand::Build { format, pan, pin } => {
+            if let Some(format) = format.format() {
+                let block = pin::build(format, pin, pan.as_deref(), rand::random())?;
+                println!("{}", Encoding::Hex.encode(&block.to_be_bytes()));
+            } else {
+                let pan = pan.as_deref().ok_or(CliError::PanRequired)?;
+                let (pin_field, account) = pin::build_format4(pin, pan, rand::random())?;
+                println!(
+                    "PIN field: {}",
+                    Encoding::Hex.encode(&pin_field.to_be_bytes())
+                );
+                println!(
+                    "PAN field: {}",
+                    Encoding::Hex.encode(&account.to_be_bytes())
+                );
+            }
+        }
+        PinCommand::Parse { pan, block } => {
+            let bytes = decode(block, Encoding::Hex).map_err(|e| invalid_block(Box::new(e)))?;
+            if let Ok(field) = <[u8; 16]>::try_from(bytes.as_slice()) {
+                let pin = pin::parse_format4(u128::from_be_bytes(field))?;
+                print_pin(&pin, "ISO format 4");
+            } else {
+                let (format, pin) = pin::parse(block_value(&bytes)?, pan.as_deref())?;
+                print_pin(&pin, &format.to_string());
+            }
+        }
+        PinCommand::Encrypt {
+            key,
+            format,
+            pan,
+            pin,
+        } => {
+            let key = load(key, keystore)?;
+            let block = pin::encrypt(&key, des_format(*format)?, pin, pan.as_deref())?;
+            println!("{}", Encoding::Hex.encode(&block.to_be_bytes()));
+        }
+        PinCommand::Decrypt { key, pan, block } => {
+            let key = load(key, keystore)?;
+            let (format, pin) = pin::decrypt(&key, parse_block(block)?, pan.as_deref())?;
+            print_pin(&pin, &format.to_string());
+        }
+        PinCommand::Translate {
+            key,
+            to_key,
+            to_key_id,
+            to_key_format,
+            format,
+            pan,
+            block,
+        } => {
+            let from = load(key, keystore)?;
+            let to = load(
+                &KeyArgs {
+                    key: to_key.clone(),
+                    key_id: to_key_id.clone(),
+                    key_format: *to_key_format,
+                },
+                keystore,
+            )?;
+            let format = format.map(des_format).transpose()?;
+            let block = pin::translate(parse_block(block)?, pan.as_deref(), &from, &to, format)?;
+            println!("{}", Encoding::Hex.encode(&block.to_be_bytes()));
+        }
+    }
+    Ok(())
+}
+
+fn load(args: &KeyArgs, keystore: &KeystoreArgs) -> Result<Key, CliError> {
+    key::load(args, keystore, Some(KeyUsage::PinEncryption))
+}
+
+/// The 64-bit format, rejecting format 4 which is encrypted with AES.
+fn des_format(format: PinFormatArg) -> Result<PinFormat, CliError> {
+    format.format().ok_or(CliError::AesPinFormat)
+}
+
+fn print_pin(pin: &str, format: &str) {
+    println!("PIN: {pin}");
+    println!("Format: {format}");
+}
+
+/// Parse an encrypted PIN block of 16 hex digits.
+fn parse_block(block: &str) -> Result<u64, CliError> {
+    let bytes = decode(block, Encoding::Hex).map_err(|e| invalid_block(Box::new(e)))?;
+    block_value(&bytes)
+}
+
+fn block_value(bytes: &[u8]) -> Result<u64, CliError> {
+    let block = <[u8; 8]>::try_from(bytes).map_err(|_| {
+        invalid_block(Box::new(ValueError::InvalidFormat(
+            "expected 16 hex digits".into(),
+        )))
+    })?;
+    Ok(u64::from_be_bytes(block))
+}
+
+fn invalid_block(error: Box<ValueError>) -> CliError {
+    ValueError::Argument("PIN block".into(), error).into()
+}
@@ -0,0 +1,74 @@
+use std::{
+    io::{self, Read, Write},
+    time::{Duration, Instant},
+};
+
+/// Minimum delay between two progress updates.
+const REPORT_INTERVAL: Duration = Duration::from_millis(250);
+
+const MIB: f64 = 1024.0 * 1024.0;
+
+/// Counts the bytes read through it and reports progress on stderr.
+#[derive(Debug)]
+pub struct Progress<R> {
+    inner: R,
+    total: Option<u64>,
+    processed: u64,
+    last_report: Option<Instant>,
+    enabled: bool,
+}
+
+impl<R: Read> Progress<R> {
+    /// Wrap `inner`, reporting against `total` bytes when the size is known.
+    pub const fn new(inner: R, total: Option<u64>, enabled: bool) -> Self {
+        Self {
+            inner,
+            total,
+            processed: 0,
+            last_report: None,
+            enabled,
+        }
+    }
+
+    /// Print the final state and end the progress line.
+    pub fn finish(&mut self) {
+        if self.enabled {
+            self.report();
+            eprintln!();
+        }
+    }
+
+    fn report(&mut self) {
+        #[allow(clippy::cast_precision_loss)]
+        let processed = self.processed as f64 / MIB;
+        let line = match self.total {
+            #[allow(clippy::cast_precision_loss)]
+            Some(total) if total > 0 => format!(
+                "\r{:5.1}% ({processed:.1} / {:.1} MiB)",
+                self.processed as f64 * 100.0 / total as f64,
+                total as f64 / MIB
+            ),
+            _ => format!("\r{processed:.1} MiB"),
+        };
+
+        let mut stderr = io::stderr().lock();
+        let _ = stderr.write_all(line.as_bytes());
+        let _ = stderr.flush();
+        self.last_report = Some(Instant::now());
+    }
+}
+
+impl<R: Read> Read for Progress<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let count = self.inner.read(buf)?;
+        self.processed += count as u64;
+
+        let due = self
+            .last_report
+            .is_none_or(|last| last.elapsed() >= REPORT_INTERVAL);
+        if self.enabled && due {
+            self.report();
+        }
+        Ok(count)
+    }
+}
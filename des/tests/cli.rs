@@ -0,0 +1,1108 @@
+//! End-to-end tests of the `des` binary. Expected values were produced with
+//! `openssl enc -des-ecb -nopad -K <key>`.
+
+use rstest::rstest;
+use std::{
+    io::Write,
+    process::{Command, Output, Stdio},
+};
+
+fn des(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_des"))
+        .args(args)
+        .output()
+        .expect("des binary runs")
+}
+
+fn des_with_stdin(args: &[&str], input: &[u8]) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_des"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("des binary runs");
+    child
+        .stdin
+        .take()
+        .expect("piped stdin")
+        .write_all(input)
+        .expect("write stdin");
+    child.wait_with_output().expect("des binary runs")
+}
+
+fn stdout(args: &[&str]) -> String {
+    let output = des(args);
+    assert!(
+        output.status.success(),
+        "des {args:?} failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout)
+        .expect("UTF-8 output")
+        .trim_end()
+        .to_owned()
+}
+
+#[rstest]
+#[case("ABCDEFGH", "ABCDEFGH", "D46EB4C9228013B7")]
+#[case("0x0123456789ABCDEF", "Now is t", "3FA40E8A984D4815")]
+#[case("keys1234", "password", "E6A84B90F47F2C25")]
+#[case("0x0123456789ABCDEF", "0x1234567890ABCDEF", "BD661569AE874E25")]
+fn encrypt_matches_openssl(#[case] key: &str, #[case] text: &str, #[case] expected: &str) {
+    assert_eq!(stdout(&["encrypt", "-k", key, text]), expected);
+}
+
+#[rstest]
+#[case("ABCDEFGH", "D46EB4C9228013B7", "ABCDEFGH")]
+#[case("keys1234", "0xE6A84B90F47F2C25", "password")]
+fn decrypt_text_output(#[case] key: &str, #[case] ciphertext: &str, #[case] expected: &str) {
+    assert_eq!(
+        stdout(&[
+            "decrypt",
+            "-k",
+            key,
+            "--input-format",
+            "hex",
+            ciphertext,
+            "-f",
+            "text"
+        ]),
+        expected
+    );
+}
+
+#[rstest]
+#[case("hello world!", "68656C6C6F20776F726C6421")]
+#[case("0x0123456789ABCDEF", "0123456789ABCDEF")]
+#[case("msg00903", "6D73673030393033")] // Ciphertext 1072306107539716
+fn decrypt_reads_encrypt_output(#[case] text: &str, #[case] expected: &str) {
+    let ciphertext = stdout(&["encrypt", "-k", "keys1234", text]);
+    assert_eq!(
+        stdout(&["decrypt", "-k", "keys1234", &ciphertext]),
+        expected
+    );
+}
+
+#[rstest]
+#[case("deadbeef", "EA051C06588F2FE8")]
+#[case("cafebabe", "96D26885B42D5459")]
+fn encrypt_reads_eight_characters_as_ascii(#[case] text: &str, #[case] expected: &str) {
+    assert_eq!(
+        stdout(&[
+            "encrypt",
+            "-k",
+            "0123456789ABCDEF",
+            "--input-format",
+            "ascii",
+            text
+        ]),
+        expected
+    );
+    assert_eq!(
+        stdout(&["encrypt", "-k", "0123456789ABCDEF", text]),
+        expected
+    );
+}
+
+#[test]
+fn little_endian_reverses_string_packing() {
+    // Reversed strings pack to the same 64-bit values in little-endian order.
+    let ciphertext = stdout(&[
+        "encrypt",
+        "--byte-order",
+        "little",
+        "-k",
+        "HGFEDCBA",
+        "HGFEDCBA",
+    ]);
+    assert_eq!(ciphertext, "D46EB4C9228013B7");
+
+    let plaintext = stdout(&[
+        "decrypt",
+        "--byte-order",
+        "little",
+        "-k",
+        "HGFEDCBA",
+        "0xD46EB4C9228013B7",
+        "-f",
+        "text",
+    ]);
+    assert_eq!(plaintext, "HGFEDCBA");
+}
+
+#[test]
+fn base64_data_roundtrip() {
+    let plaintext = "Tm93IGlzIHRoZSB0aW1lIGZvciBhbGwg";
+    let ciphertext = "dAGed4YcHltgWLpLTlsuIXm5yjB6hCddrZYqxFLErns=";
+    let base64 = ["-k", "keys1234", "--input-format", "base64"];
+
+    assert_eq!(
+        stdout(&[&["encrypt"][..], &base64, &[plaintext]].concat()),
+        ciphertext
+    );
+    assert_eq!(
+        stdout(&[&["decrypt"][..], &base64, &[ciphertext]].concat()),
+        plaintext
+    );
+}
+
+#[test]
+fn separated_hex_data() {
+    let ciphertext = stdout(&["encrypt", "-k", "ABCDEFGH", "41:42:43:44:45:46:47:48"]);
+    // One data block plus a full block of PKCS#7 padding.
+    assert_eq!(ciphertext.len(), 32);
+    assert!(ciphertext.starts_with("D46EB4C9228013B7"));
+}
+
+#[rstest]
+#[case(&["encrypt", "-k", "zz", "--key-format", "hex", "ABCDEFGH"])]
+#[case(&["encrypt", "-k", "ABCDEFGH", "--input-format", "decimal", "ABCDEFGH"])]
+#[case(&["encrypt", "-k", "ABCDEFGH", "--key-format", "file", "/nonexistent"])]
+fn invalid_values_fail(#[case] args: &[&str]) {
+    let output = des(args);
+    assert!(!output.status.success(), "des {args:?} should fail");
+    assert!(
+        String::from_utf8_lossy(&output.stderr).contains("Error:"),
+        "Missing error message"
+    );
+}
+
+/// Colon-separated bytes are always read as data, even when they form a
+/// single block.
+fn separated_hex(hex: &str) -> String {
+    hex.as_bytes()
+        .chunks(2)
+        .map(|pair| str::from_utf8(pair).expect("ASCII hex"))
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+#[rstest]
+#[case("héllo wörld", "71E3EA85273EC447375449EB04460FF5")]
+#[case("hi", "F0D0896AF5798B2B")]
+fn utf8_text_is_padded(#[case] text: &str, #[case] expected: &str) {
+    assert_eq!(stdout(&["encrypt", "-k", "ABCDEFGH", text]), expected);
+    assert_eq!(
+        stdout(&[
+            "decrypt",
+            "-k",
+            "ABCDEFGH",
+            &separated_hex(expected),
+            "-f",
+            "text"
+        ]),
+        text
+    );
+}
+
+#[test]
+fn strict_utf8_rejects_invalid_output() {
+    let plaintext = stdout(&[
+        "decrypt",
+        "-k",
+        "ABCDEFGH",
+        "0xD46EB4C9228013B7",
+        "-f",
+        "text",
+        "--utf8",
+        "strict",
+    ]);
+    assert_eq!(plaintext, "ABCDEFGH");
+
+    // Decrypting under the wrong key yields bytes that are not UTF-8.
+    let output = des(&[
+        "decrypt",
+        "-k",
+        "HGFEDCBA",
+        "0xD46EB4C9228013B7",
+        "-f",
+        "text",
+        "--utf8",
+        "strict",
+    ]);
+    assert!(!output.status.success(), "Invalid UTF-8 was accepted");
+}
+
+#[test]
+fn passphrase_key_requires_kdf() {
+    let output = des(&["encrypt", "-k", "correct horse", "ABCDEFGH"]);
+    assert!(!output.status.success(), "Passphrase used without --kdf");
+
+    let ciphertext = stdout(&[
+        "encrypt",
+        "--kdf",
+        "pbkdf2-sha256",
+        "--salt",
+        "NaCl",
+        "--iterations",
+        "1000",
+        "-k",
+        "correct horse",
+        "ABCDEFGH",
+    ]);
+    // Key CBA50AFB9ECE1B6C from `openssl kdf ... PBKDF2`.
+    assert_eq!(ciphertext, "8B8704396A932BB8");
+}
+
+#[rstest]
+#[case(&[][..], "requires --salt")]
+#[case(&["--salt="], "a value is required")]
+fn kdf_requires_a_salt(#[case] salt: &[&str], #[case] error: &str) {
+    let mut args = vec!["encrypt", "--kdf", "pbkdf2-sha256", "-k", "correct horse"];
+    args.extend(salt);
+    args.push("ABCDEFGH");
+    let output = des(&args);
+    assert!(!output.status.success(), "Key derived without a salt");
+    assert!(String::from_utf8_lossy(&output.stderr).contains(error));
+}
+
+#[test]
+fn verbose_output_hides_the_derived_key() {
+    let output = des(&[
+        "--verbose",
+        "encrypt",
+        "--kdf",
+        "pbkdf2-sha256",
+        "--salt",
+        "NaCl",
+        "--iterations",
+        "1000",
+        "-k",
+        "correct horse",
+        "ABCDEFGH",
+    ]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("KCV: "), "{stderr}");
+    assert!(!stderr.contains("CBA40BFB9ECE1A6D"), "{stderr}");
+}
+
+#[rstest]
+#[case("pbkdf2-sha256", "8B8704396A932BB8")]
+#[case("pbkdf2-sha1", "0022A95D66F03414")]
+fn kdf_functions(#[case] kdf: &str, #[case] expected: &str) {
+    let ciphertext = stdout(&[
+        "encrypt",
+        "--kdf",
+        kdf,
+        "--salt",
+        "NaCl",
+        "--iterations",
+        "1000",
+        "-k",
+        "correct horse",
+        "ABCDEFGH",
+    ]);
+    assert_eq!(ciphertext, expected);
+}
+
+#[rstest]
+#[case("pbkdf2-sha1", "des")]
+#[case("pbkdf2-sha256", "3tdea")]
+fn passphrase_stores_kdf_header(#[case] kdf: &str, #[case] length: &str) {
+    let encrypt = || {
+        stdout(&[
+            "encrypt",
+            "--passphrase=hunter2",
+            "--kdf",
+            kdf,
+            "--key-length",
+            length,
+            "--iterations",
+            "10",
+            "attack at dawn",
+        ])
+    };
+    let ciphertext = encrypt();
+    // "DESKDF", version 1.
+    assert!(ciphertext.starts_with("4445534B444601"), "{ciphertext}");
+    assert_ne!(ciphertext, encrypt(), "Salt is not random");
+
+    let decrypt = |passphrase: &str| {
+        des(&[
+            "decrypt",
+            &format!("--passphrase={passphrase}"),
+            "--input-format",
+            "hex",
+            "-f",
+            "text",
+            &ciphertext,
+        ])
+    };
+    let plaintext = decrypt("hunter2");
+    assert!(plaintext.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&plaintext.stdout),
+        "attack at dawn\n"
+    );
+    // Without a MAC, the padding of a wrong key can still happen to be valid.
+    let wrong = decrypt("wrong");
+    assert!(
+        !wrong.status.success() || wrong.stdout != plaintext.stdout,
+        "Wrong passphrase recovered the plaintext"
+    );
+}
+
+#[test]
+fn passphrase_streams_with_header() {
+    use std::fs;
+
+    let dir = std::env::temp_dir();
+    let id = std::process::id();
+    let plain = dir.join(format!("des-passphrase-{id}.txt"));
+    let encrypted = dir.join(format!("des-passphrase-{id}.enc"));
+    let decrypted = dir.join(format!("des-passphrase-{id}.out"));
+    let data = (0..=255).cycle().take(10_000).collect::<Vec<u8>>();
+    fs::write(&plain, &data).expect("write input");
+    let path = |path: &std::path::Path| path.to_str().expect("UTF-8 temp path").to_owned();
+
+    let common = ["--passphrase=pw", "--iterations", "10"];
+    let mut args = vec!["encrypt", "-i"];
+    let (plain_arg, encrypted_arg, decrypted_arg) =
+        (path(&plain), path(&encrypted), path(&decrypted));
+    args.extend([plain_arg.as_str(), "-o", encrypted_arg.as_str()]);
+    args.extend(common);
+    stdout(&args);
+
+    let mut args = vec!["decrypt", "-i", encrypted_arg.as_str(), "-o"];
+    args.push(decrypted_arg.as_str());
+    args.extend(common);
+    stdout(&args);
+    assert_eq!(fs::read(&decrypted).expect("decrypted output"), data);
+
+    let wrong = des(&[
+        "decrypt",
+        "--passphrase=wrong",
+        "--iterations",
+        "10",
+        "-i",
+        encrypted_arg.as_str(),
+    ]);
+    assert!(
+        String::from_utf8_lossy(&wrong.stderr).contains("Authentication failed"),
+        "Wrong passphrase not detected"
+    );
+
+    // Data encrypted without --passphrase has no header.
+    let output = des(&["decrypt", "--passphrase=pw", "-i", plain_arg.as_str()]);
+    assert!(
+        String::from_utf8_lossy(&output.stderr).contains("key derivation header"),
+        "Missing header not reported"
+    );
+
+    for file in [plain, encrypted, decrypted] {
+        fs::remove_file(file).expect("remove temp file");
+    }
+}
+
+#[test]
+fn streams_are_sealed_by_default() {
+    use std::fs;
+
+    let dir = std::env::temp_dir();
+    let id = std::process::id();
+    let plain = dir.join(format!("des-sealed-{id}.txt"));
+    let sealed = dir.join(format!("des-sealed-{id}.enc"));
+    let opened = dir.join(format!("des-sealed-{id}.out"));
+    let data = (0..=255).cycle().take(10_000).collect::<Vec<u8>>();
+    fs::write(&plain, &data).expect("write input");
+    let path = |path: &std::path::Path| path.to_str().expect("UTF-8 temp path").to_owned();
+    let (plain_arg, sealed_arg, opened_arg) = (path(&plain), path(&sealed), path(&opened));
+    let key = ["-k", "0x0123456789ABCDEF"];
+
+    let mut args = vec![
+        "encrypt",
+        "-i",
+        plain_arg.as_str(),
+        "-o",
+        sealed_arg.as_str(),
+    ];
+    args.extend(key);
+    stdout(&args);
+    let ciphertext = fs::read(&sealed).expect("sealed output");
+    // "DESAE", version 1, IV, CBC with PKCS#7 padding, CMAC tag.
+    assert!(ciphertext.starts_with(b"DESAE\x01"));
+    assert_eq!(ciphertext.len(), 14 + 10_008 + 8);
+
+    let mut args = vec![
+        "decrypt",
+        "-i",
+        sealed_arg.as_str(),
+        "-o",
+        opened_arg.as_str(),
+    ];
+    args.extend(key);
+    stdout(&args);
+    assert_eq!(fs::read(&opened).expect("opened output"), data);
+    fs::remove_file(&opened).expect("remove output");
+
+    let mut tampered = ciphertext;
+    tampered[5000] ^= 0x01;
+    fs::write(&sealed, &tampered).expect("write tampered input");
+    let output = des(&args);
+    assert!(!output.status.success(), "Tampered data accepted");
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Authentication failed"));
+    assert!(!opened.exists(), "Unauthenticated plaintext written");
+
+    // An explicit mode encrypts without authentication.
+    let mut args = vec![
+        "encrypt",
+        "-i",
+        plain_arg.as_str(),
+        "-o",
+        sealed_arg.as_str(),
+    ];
+    args.extend(key);
+    args.extend(["-m", "ecb"]);
+    stdout(&args);
+    assert_eq!(fs::metadata(&sealed).expect("output").len(), 10_008);
+
+    let mut args = vec!["encrypt", "-i", plain_arg.as_str(), "-p", "none"];
+    args.extend(key);
+    let output = des(&args);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("add --mode"));
+
+    for file in [plain, sealed] {
+        fs::remove_file(file).expect("remove temp file");
+    }
+}
+
+#[test]
+fn sealed_stdin_releases_nothing_unauthenticated() {
+    let dir = std::env::temp_dir();
+    let opened = dir.join(format!("des-sealed-stdin-{}.out", std::process::id()));
+    let opened_arg = opened.to_str().expect("UTF-8 temp path");
+    let data = (0..=255).cycle().take(10_000).collect::<Vec<u8>>();
+    let key = ["-k", "0x0123456789ABCDEF", "-i", "-"];
+
+    let sealed = des_with_stdin(&[&["encrypt"][..], &key].concat(), &data);
+    assert!(sealed.status.success());
+    let opened_stdout = des_with_stdin(&[&["decrypt"][..], &key].concat(), &sealed.stdout);
+    assert!(opened_stdout.status.success());
+    assert_eq!(opened_stdout.stdout, data);
+
+    let mut tampered = sealed.stdout;
+    let last = tampered.len() - 1;
+    tampered[last] ^= 0x01;
+    let output = des_with_stdin(&[&["decrypt"][..], &key].concat(), &tampered);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Authentication failed"));
+    assert!(
+        output.stdout.is_empty(),
+        "Unauthenticated plaintext written"
+    );
+
+    let output = des_with_stdin(
+        &[&["decrypt", "-o", opened_arg][..], &key].concat(),
+        &tampered,
+    );
+    assert!(!output.status.success(), "Tampered data accepted");
+    assert!(!opened.exists(), "Unauthenticated plaintext written");
+    assert!(
+        !dir.join(format!(
+            "des-sealed-stdin-{}.out.partial",
+            std::process::id()
+        ))
+        .exists(),
+        "Partial output left behind"
+    );
+}
+
+#[rstest]
+#[case("0123456789ABCDEFFEDCBA9876543210", "08D7B4")]
+#[case("0123456789ABCDEFFEDCBA987654321089ABCDEF01234567", "3FD539")]
+fn sealed_streams_accept_tdea_keys(#[case] key: &str, #[case] kcv: &str) {
+    use std::fs;
+
+    let dir = std::env::temp_dir();
+    let id = format!("{}-{}", std::process::id(), key.len());
+    let plain = dir.join(format!("des-sealed-tdea-{id}.txt"));
+    let sealed = dir.join(format!("des-sealed-tdea-{id}.enc"));
+    fs::write(&plain, "Sealed with a TDEA key").expect("write input");
+    let path = |path: &std::path::Path| path.to_str().expect("UTF-8 temp path").to_owned();
+    let (plain_arg, sealed_arg) = (path(&plain), path(&sealed));
+
+    let output = des(&["encrypt", "-k", key, "-i", &plain_arg, "-o", &sealed_arg]);
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr).trim(),
+        format!("KCV: {kcv}")
+    );
+    assert_eq!(
+        stdout(&["decrypt", "-k", key, "-i", &sealed_arg]),
+        "Sealed with a TDEA key"
+    );
+    // Only the first part of the key must not open it.
+    let output = des(&["decrypt", "-k", &key[..16], "-i", &sealed_arg]);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Authentication failed"));
+
+    for file in [plain, sealed] {
+        fs::remove_file(file).expect("remove temp file");
+    }
+}
+
+#[test]
+fn key_length_requires_passphrase() {
+    let output = des(&[
+        "encrypt",
+        "--key-length",
+        "3tdea",
+        "-k",
+        "0x0123456789ABCDEF",
+        "0x00",
+    ]);
+    assert!(
+        !output.status.success(),
+        "--key-length used without --passphrase"
+    );
+}
+
+#[rstest]
+#[case("des", 16)]
+#[case("2tdea", 32)]
+#[case("3tdea", 48)]
+fn keygen_prints_key_and_check_value(#[case] length: &str, #[case] hex_digits: usize) {
+    let output = stdout(&["keygen", "--length", length, "--iv"]);
+    let lines = output.lines().collect::<Vec<_>>();
+
+    let key = lines[0].strip_prefix("Key: ").expect("key line");
+    let kcv = lines[1].strip_prefix("KCV: ").expect("KCV line");
+    assert_eq!(key.len(), hex_digits, "Unexpected key length");
+    assert_eq!(kcv.len(), 6, "Unexpected KCV length");
+    assert!(lines[2].starts_with("IV:"), "Missing IV");
+
+    if length == "des" {
+        // The KCV is the encrypted zero block, truncated to three bytes.
+        let zero = stdout(&["encrypt", "--key-format", "hex", "-k", key, "0"]);
+        assert_eq!(&zero[..6], kcv);
+    }
+}
+
+#[test]
+fn keygen_base64() {
+    let output = stdout(&["keygen", "-f", "base64"]);
+    let key = output
+        .lines()
+        .next()
+        .and_then(|line| line.strip_prefix("Key: "))
+        .expect("key line");
+    assert_eq!(key.len(), 12, "8 bytes encode to 12 base64 characters");
+}
+
+#[cfg(unix)]
+#[test]
+fn keygen_writes_private_file() {
+    use std::{fs, os::unix::fs::PermissionsExt};
+
+    let path = std::env::temp_dir().join(format!("des-keygen-{}.key", std::process::id()));
+    let _ = fs::remove_file(&path);
+
+    let path_arg = path.to_str().expect("UTF-8 temp path");
+    let output = stdout(&["keygen", "-o", path_arg]);
+    assert!(output.starts_with("KCV: "), "Key printed with --output");
+
+    let mode = fs::metadata(&path).expect("key file").permissions().mode();
+    assert_eq!(mode & 0o777, 0o600, "Key file is not private");
+    assert!(
+        !des(&["keygen", "-o", path_arg]).status.success(),
+        "Existing key file overwritten"
+    );
+    fs::remove_file(&path).expect("remove key file");
+}
+
+#[rstest]
+#[case("0123456789ABCDEF", "zero-block", "D5D44F")]
+#[case("0x01:23:45:67:89:AB:CD:EF", "zero-block", "D5D44F")]
+#[case("ASNFZ4mrze8=", "zero-block", "D5D44F")]
+#[case("0123456789ABCDEF", "cmac", "03C8153AD5")]
+#[case("0123456789ABCDEFFEDCBA9876543210", "zero-block", "08D7B4")]
+#[case("0123456789ABCDEFFEDCBA9876543210", "cmac", "0A82458664")]
+fn key_kcv(#[case] key: &str, #[case] method: &str, #[case] expected: &str) {
+    assert_eq!(stdout(&["key", "kcv", "-k", key, "-m", method]), expected);
+}
+
+#[test]
+fn kcv_shown_when_key_is_loaded() {
+    let output = des(&["encrypt", "-k", "0x0123456789ABCDEF", "0"]);
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr).trim_end(),
+        "KCV: D5D44F"
+    );
+}
+
+#[test]
+fn key_kcv_rejects_invalid_length() {
+    let output = des(&["key", "kcv", "-k", "0123456789ABCD"]);
+    assert!(!output.status.success(), "7-byte key accepted");
+}
+
+#[rstest]
+#[case("0123456789ABCDEF", "D5D44F")]
+#[case("0123456789ABCDEFFEDCBA9876543210", "08D7B4")]
+fn key_split_and_combine(#[case] key: &str, #[case] kcv: &str) {
+    let split = stdout(&["key", "split", "-k", key, "-n", "3"]);
+    let lines = split.lines().collect::<Vec<_>>();
+    assert_eq!(lines.len(), 4, "Expected 3 components and the key KCV");
+    assert_eq!(lines[3], format!("KCV: {kcv}"));
+
+    let mut args = vec!["key", "combine", "--kcv", kcv];
+    for line in &lines[..3] {
+        let component = line.split_whitespace().nth(2).expect("component value");
+        args.extend(["-c", component]);
+    }
+    let combined = stdout(&args);
+    assert!(combined.contains(&format!("Key: {key}")), "{combined}");
+
+    args[3] = "000000";
+    assert!(!des(&args).status.success(), "Wrong KCV accepted");
+}
+
+#[rstest]
+#[case("0123456789ABCDEF", "D5D44F")]
+#[case("0123456789ABCDEFFEDCBA9876543210", "08D7B4")]
+fn key_share_and_recover(#[case] key: &str, #[case] kcv: &str) {
+    let shared = stdout(&["key", "share", "-k", key, "-m", "2", "-n", "3"]);
+    let lines = shared.lines().collect::<Vec<_>>();
+    assert_eq!(lines.len(), 4, "Expected 3 shares and the key KCV");
+    assert_eq!(lines[3], format!("KCV: {kcv}"));
+    let shares = lines[..3]
+        .iter()
+        .map(|line| line.split_whitespace().nth(2).expect("share value"))
+        .collect::<Vec<_>>();
+
+    let recovered = stdout(&["key", "recover", "-s", shares[2], "-s", shares[0]]);
+    assert_eq!(recovered, format!("Key: {key}\nKCV: {kcv}"));
+
+    let output = des(&["key", "recover", "-s", shares[1]]);
+    assert!(!output.status.success(), "Recovered from a single share");
+
+    // Flip the lowest bit of the last share byte.
+    let (head, last) = shares[1].split_at(shares[1].len() - 1);
+    let last = u8::from_str_radix(last, 16).expect("hex digit") ^ 1;
+    let corrupted = format!("{head}{last:X}");
+    let output = des(&["key", "recover", "-s", shares[0], "-s", &corrupted]);
+    assert!(!output.status.success(), "Corrupted share accepted");
+    assert!(String::from_utf8_lossy(&output.stderr).contains("key check value"));
+}
+
+#[test]
+fn keystore_key_id() {
+    use std::fs;
+
+    let path = std::env::temp_dir().join(format!("des-keystore-{}", std::process::id()));
+    let _ = fs::remove_file(&path);
+    let path_arg = path.to_str().expect("UTF-8 temp path");
+    let keystore = |args: &[&str]| {
+        let mut full = vec!["--keystore", path_arg, "--keystore-passphrase", "secret"];
+        full.extend(args);
+        des(&full)
+    };
+    let succeeds = |args: &[&str]| {
+        let output = keystore(args);
+        assert!(
+            output.status.success(),
+            "des {args:?} failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        String::from_utf8(output.stdout).expect("UTF-8 output")
+    };
+
+    succeeds(&["keystore", "init", "--iterations", "16"]);
+    let kcv = succeeds(&[
+        "keystore",
+        "add",
+        "pek",
+        "-k",
+        "0123456789ABCDEFFEDCBA9876543210",
+        "-u",
+        "encryption",
+    ]);
+    assert_eq!(kcv.trim_end(), "KCV: 08D7B4");
+    succeeds(&[
+        "keystore",
+        "add",
+        "zmk",
+        "-g",
+        "3tdea",
+        "-u",
+        "key-wrapping",
+    ]);
+
+    let list = succeeds(&["keystore", "list"]);
+    let lines = list.lines().collect::<Vec<_>>();
+    assert_eq!(lines.len(), 2, "{list}");
+    assert!(lines[0].starts_with("pek "), "{list}");
+    assert!(
+        lines[0].contains("2TDEA") && lines[0].contains("08D7B4"),
+        "{list}"
+    );
+    assert!(
+        !fs::read_to_string(&path)
+            .expect("keystore file")
+            .contains("0123456789ABCDEF"),
+        "Key stored in plaintext"
+    );
+
+    // 2TDEA, `openssl enc -des-ede -nopad`.
+    let ciphertext = succeeds(&["encrypt", "--key-id", "pek", "0x0123456789ABCDEF"]);
+    assert_eq!(ciphertext.trim_end(), "1A4D672DCA6CB335");
+    assert_eq!(
+        succeeds(&["key", "kcv", "--key-id", "pek"]).trim_end(),
+        "08D7B4"
+    );
+
+    let denied = keystore(&["encrypt", "--key-id", "zmk", "0x0123456789ABCDEF"]);
+    assert!(!denied.status.success(), "Key wrapping key used for data");
+    let denied = keystore(&["mac", "--key-id", "pek", "0x0123456789ABCDEF"]);
+    assert!(!denied.status.success(), "Encryption key used for a MAC");
+    let wrong = des(&[
+        "--keystore",
+        path_arg,
+        "--keystore-passphrase",
+        "wrong",
+        "key",
+        "kcv",
+        "--key-id",
+        "pek",
+    ]);
+    assert!(
+        String::from_utf8_lossy(&wrong.stderr).contains("Wrong keystore passphrase"),
+        "Wrong passphrase accepted"
+    );
+
+    succeeds(&["keystore", "remove", "pek"]);
+    assert!(
+        !keystore(&["key", "kcv", "--key-id", "pek"])
+            .status
+            .success()
+    );
+    fs::remove_file(&path).expect("remove keystore");
+}
+
+#[rstest]
+#[case("0")]
+#[case("10000001")]
+fn keystore_init_bounds_iterations(#[case] iterations: &str) {
+    let path = std::env::temp_dir().join(format!("des-keystore-bounds-{}", std::process::id()));
+    let output = des(&[
+        "--keystore",
+        path.to_str().expect("UTF-8 temporary path"),
+        "--keystore-passphrase",
+        "secret",
+        "keystore",
+        "init",
+        "--iterations",
+        iterations,
+    ]);
+    assert!(
+        !output.status.success(),
+        "Keystore created with {iterations}"
+    );
+    assert!(!path.exists());
+}
+
+/// ISO/IEC 9797-1 Annex B examples.
+#[rstest]
+#[case(&["-k", "0123456789ABCDEF"], "70A30640CC76DD8B")]
+#[case(&["-k", "0123456789ABCDEF", "-p", "2"], "10E1F0F108341B6D")]
+#[case(&["-a", "retail", "-k", "0123456789ABCDEFFEDCBA9876543210"], "A1C72E74EA3FA9B6")]
+#[case(&["-a", "retail", "-k", "0123456789ABCDEFFEDCBA9876543210", "-l", "4"], "A1C72E74")]
+fn mac_generate(#[case] options: &[&str], #[case] expected: &str) {
+    let mut args = vec!["mac", "--input-format", "text", "Now is the time for all "];
+    args.extend(options);
+    assert_eq!(stdout(&args), expected);
+}
+
+#[test]
+fn mac_verify() {
+    let verify = |mac| {
+        des(&[
+            "mac",
+            "-a",
+            "retail",
+            "-k",
+            "0123456789ABCDEFFEDCBA9876543210",
+            "--verify",
+            mac,
+            "--input-format",
+            "text",
+            "Now is the time for all ",
+        ])
+    };
+    assert!(verify("A1C72E74EA3FA9B6").status.success());
+    assert!(verify("A1C72E74").status.success());
+    let output = verify("A1C72E75");
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("MAC does not match"));
+}
+
+#[test]
+fn mac_rejects_invalid_options() {
+    let retail_single = des(&["mac", "-a", "retail", "-k", "0123456789ABCDEF", "x"]);
+    assert!(!retail_single.status.success());
+    assert!(String::from_utf8_lossy(&retail_single.stderr).contains("16-byte key"));
+    assert!(
+        !des(&["mac", "-k", "0123456789ABCDEF", "-l", "3", "x"])
+            .status
+            .success()
+    );
+}
+
+const PAN: &str = "4111111111111111";
+
+#[rstest]
+#[case(&["build", "1234", "--pan", PAN], "041225EEEEEEEEEE")]
+#[case(&["build", "-f", "2", "1234"], "241234FFFFFFFFFF")]
+#[case(&["parse", "041225EEEEEEEEEE", "--pan", PAN], "PIN: 1234\nFormat: ISO format 0")]
+#[case(&["decrypt", "-k", "0123456789ABCDEF", "--pan", PAN, "C30C31411AA3D043"], "PIN: 1234\nFormat: ISO format 0")]
+fn pin_blocks(#[case] options: &[&str], #[case] expected: &str) {
+    let mut args = vec!["pin"];
+    args.extend(options);
+    assert_eq!(stdout(&args), expected);
+}
+
+#[test]
+fn pin_format4_fields() {
+    let output = stdout(&["pin", "build", "-f", "4", "1234", "--pan", PAN]);
+    let (pin_field, account) = output.split_once('\n').expect("two fields");
+    assert!(pin_field.starts_with("PIN field: 441234AAAAAAAAAA"));
+    assert_eq!(account, "PAN field: 44111111111111111000000000000000");
+
+    let field = pin_field.trim_start_matches("PIN field: ");
+    assert_eq!(
+        stdout(&["pin", "parse", field]),
+        "PIN: 1234\nFormat: ISO format 4"
+    );
+}
+
+#[test]
+fn pin_encrypt_translate_decrypt() {
+    let terminal = "0123456789ABCDEF";
+    let zone = "0123456789ABCDEFFEDCBA9876543210";
+    let encrypted = stdout(&[
+        "pin", "encrypt", "-k", terminal, "-f", "3", "--pan", PAN, "98765",
+    ]);
+    let translated = stdout(&[
+        "pin",
+        "translate",
+        "-k",
+        terminal,
+        "--to-key",
+        zone,
+        "-f",
+        "1",
+        "--pan",
+        PAN,
+        &encrypted,
+    ]);
+    assert_eq!(
+        stdout(&["pin", "decrypt", "-k", zone, "--pan", PAN, &translated]),
+        "PIN: 98765\nFormat: ISO format 1"
+    );
+}
+
+#[test]
+fn pin_rejects_invalid_options() {
+    let error = |args: &[&str]| {
+        let output = des(args);
+        assert!(!output.status.success(), "des {args:?} succeeded");
+        String::from_utf8_lossy(&output.stderr).into_owned()
+    };
+    assert!(error(&["pin", "build", "1234"]).contains("require the PAN"));
+    assert!(
+        error(&[
+            "pin",
+            "decrypt",
+            "-k",
+            "0123456789ABCDEF",
+            "--pan",
+            "5500000000000004",
+            "C30C31411AA3D043"
+        ])
+        .contains("wrong key or PAN")
+    );
+    assert!(error(&["pin", "build", "123", "-f", "2"]).contains("PIN must be"));
+    assert!(
+        error(&[
+            "pin",
+            "encrypt",
+            "-k",
+            "0123456789ABCDEF",
+            "-f",
+            "4",
+            "--pan",
+            PAN,
+            "1234"
+        ])
+        .contains("AES")
+    );
+    assert!(
+        error(&["pin", "decrypt", "-k", "0123456789ABCDEF", "C30C3141"]).contains("16 hex digits")
+    );
+}
+
+const CVK: &str = "0123456789ABCDEFFEDCBA9876543210";
+
+#[rstest]
+#[case(&["-s", "101"], "561")]
+#[case(&["--type", "cvv2"], "636")]
+#[case(&["--type", "icvv"], "651")]
+fn cvv_generate(#[case] options: &[&str], #[case] expected: &str) {
+    let mut args = vec![
+        "cvv",
+        "generate",
+        "-k",
+        CVK,
+        "--pan",
+        "4123456789012345",
+        "-e",
+        "8701",
+    ];
+    args.extend(options);
+    assert_eq!(stdout(&args), expected);
+}
+
+#[test]
+fn cvv_verify() {
+    let verify = |cvv| {
+        des(&[
+            "cvv",
+            "verify",
+            "-k",
+            CVK,
+            "--pan",
+            "4123456789012345",
+            "-e",
+            "8701",
+            "-s",
+            "101",
+            cvv,
+        ])
+    };
+    assert!(verify("561").status.success());
+    let output = verify("562");
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("CVV does not match"));
+}
+
+#[test]
+fn cvv_rejects_invalid_options() {
+    let card = ["cvv", "generate", "--pan", "4123456789012345", "-e", "8701"];
+    let run = |options: &[&str]| des(&[&card[..], options].concat());
+    assert!(!run(&["-k", CVK]).status.success());
+    assert!(
+        !run(&["-k", CVK, "-s", "101", "--type", "cvv2"])
+            .status
+            .success()
+    );
+    let single = run(&["-k", "0123456789ABCDEF", "-s", "101"]);
+    assert!(!single.status.success());
+    assert!(String::from_utf8_lossy(&single.stderr).contains("16-byte CVK pair"));
+}
+
+const KBPK: &str = "89E88CF7931444F334BD7547FC3F380C";
+const KEY_BLOCK: &str =
+    "B0080P0TE00E0000F0CA30986E4EB77037AC13356385C3607407F9C7F9E94482CCAAD6BC9A8E3A2A";
+
+#[test]
+fn keyblock_unwrap() {
+    assert_eq!(
+        stdout(&["keyblock", "unwrap", "-k", KBPK, KEY_BLOCK]),
+        "Version: B (TDEA key derivation binding)\n\
+         Key usage: P0 (PIN encryption)\n\
+         Algorithm: T (TDEA)\n\
+         Mode of use: E (Encrypt only)\n\
+         Key version: 00\n\
+         Exportability: E (Exportable under a trusted key)\n\
+         Key: F039121BEC83D26B169BDCD5B22AAF8F\n\
+         KCV: CB9DEA"
+    );
+}
+
+#[rstest]
+fn keyblock_wrap_unwrap(#[values("a", "b", "c")] version: &str) {
+    let block = stdout(&[
+        "keyblock",
+        "wrap",
+        "-k",
+        KBPK,
+        "--wrapped-key",
+        "0123456789ABCDEF",
+        "--block-version",
+        version,
+        "-u",
+        "B1",
+        "-m",
+        "X",
+        "-x",
+        "N",
+        "--optional-block",
+        "KS=FFFF9876543210E00000",
+    ]);
+    assert!(block.starts_with(&version.to_uppercase()));
+    assert_eq!(&block[5..16], "B1DX00N0100");
+    let unwrapped = stdout(&["keyblock", "unwrap", "-k", KBPK, &block]);
+    assert!(unwrapped.contains("Key usage: B1 (Initial DUKPT key)\nAlgorithm: D (DES)"));
+    assert!(unwrapped.contains("Optional block KS: FFFF9876543210E00000"));
+    assert!(unwrapped.ends_with("Key: 0123456789ABCDEF\nKCV: D5D44F"));
+}
+
+#[test]
+fn keyblock_inspect() {
+    let header = stdout(&["keyblock", "inspect", KEY_BLOCK]);
+    assert!(header.starts_with("Version: B"));
+    assert!(header.ends_with("Exportability: E (Exportable under a trusted key)"));
+}
+
+#[test]
+fn keyblock_rejects_tampered_blocks() {
+    let tampered = KEY_BLOCK.replacen("P0TE", "P0TD", 1);
+    let output = des(&["keyblock", "unwrap", "-k", KBPK, &tampered]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("MAC does not match"));
+    let wrong_key = des(&["keyblock", "unwrap", "-k", CVK, KEY_BLOCK]);
+    assert!(!wrong_key.status.success());
+}
+
+const EMV_CARD: [&str; 9] = [
+    "emv",
+    "arqc",
+    "-k",
+    "0123456789ABCDEFFEDCBA9876543210",
+    "--pan",
+    "5413330089600010",
+    "--psn",
+    "1",
+    "--atc=001C",
+];
+const EMV_DATA: &str = "00000000100000000000000008400000000000084016051700123456785800001C03A00000";
+
+#[rstest]
+#[case(&[], "ARQC: CD8D1200EF1A51A1")]
+#[case(&["-p", "1"], "ARQC: 4C6EF77DF15A71FD")]
+#[case(&["--arc", "3030"], "ARQC: CD8D1200EF1A51A1\nARPC: 1A1FA9BE97344F20")]
+#[case(&["--verify", "CD8D1200EF1A51A1", "--csu", "00820000"], "ARQC verified\nARPC: 6F4303AA")]
+#[case(
+    &["--csu", "00820000", "--proprietary-data", "0102"],
+    "ARQC: CD8D1200EF1A51A1\nARPC: 3997C10B"
+)]
+fn emv_arqc(#[case] options: &[&str], #[case] expected: &str) {
+    let args = [&EMV_CARD[..], options, &[EMV_DATA]].concat();
+    assert_eq!(stdout(&args), expected);
+}
+
+#[test]
+fn emv_arqc_rejects_invalid_input() {
+    let run = |options: &[&str]| des(&[&EMV_CARD[..], options, &[EMV_DATA]].concat());
+    let wrong = run(&["--verify", "CD8D1200EF1A51A0"]);
+    assert!(!wrong.status.success());
+    assert!(String::from_utf8_lossy(&wrong.stderr).contains("ARQC does not match"));
+    assert!(
+        !run(&["--arc", "3030", "--csu", "00820000"])
+            .status
+            .success()
+    );
+    assert!(!run(&["--proprietary-data", "0102"]).status.success());
+    assert!(!run(&["--psn", "100"]).status.success());
+    assert!(!run(&["--atc", "1C"]).status.success());
+}